@@ -1,9 +1,16 @@
 use clap::Parser;
+use std::path::PathBuf;
+
+use crate::profile::PROFILE_ENV_VAR;
 
 #[derive(Parser)]
 #[command(name = "retsyn")]
 #[command(about = "A full text search program", long_about = None)]
 pub struct Cli {
+    /// Use the config, index and history of a named profile e.g. `work`, instead of the default profile
+    #[arg(long, value_name = "NAME", env = PROFILE_ENV_VAR)]
+    pub profile: Option<String>,
+
     /// Create a default config template and write it to the default config path
     #[arg(long)]
     pub default_config: bool,
@@ -11,4 +18,20 @@ pub struct Cli {
     /// Clear the search index so that it will be regenerated on the next launch
     #[arg(long)]
     pub clear_index: bool,
+
+    /// Delete the documents of one source e.g. `web_scrapbook_files` and index its files again
+    #[arg(long, value_name = "SOURCE", conflicts_with = "reindex_path")]
+    pub reindex_source: Option<String>,
+
+    /// Delete the documents of every file in a directory and below it, and index those files again
+    #[arg(long, value_name = "DIR")]
+    pub reindex_path: Option<PathBuf>,
+
+    /// Print what is in the search index: documents per source and kind, sizes, the largest documents and top terms
+    #[arg(long)]
+    pub stats: bool,
+
+    /// Merge the segments of the search index according to the configured merge policy and delete unused files
+    #[arg(long)]
+    pub compact_index: bool,
 }
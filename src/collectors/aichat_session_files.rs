@@ -4,12 +4,13 @@ use tracing::{debug, warn};
 
 use crate::{
     config::PathList,
-    model::index_entry::{IndexEntry, IndexPath, IndexPathSender},
-};
-use std::{
-    fs,
-    path::{Path, PathBuf},
+    model::{
+        decoded_text::DecodedText,
+        index_entry::{IndexEntry, IndexPath, IndexPathSender},
+        indexing_error::IndexingError,
+    },
 };
+use std::path::{Path, PathBuf};
 
 #[derive(Deserialize)]
 struct Session {
@@ -71,11 +72,14 @@ impl AichatSessionFiles {
                             // TODO check the file type here
                             // if this is a file, send it to the fulltext index to check if it is already indexed and up to date
                             debug!("sending path {}...", aichat_session_file.to_string_lossy());
-                            sender
+                            if sender
                                 .send(IndexPath::AichatSessionFile(
                                     aichat_session_file.to_path_buf(),
                                 ))
-                                .expect("should be able to send new entries to index");
+                                .is_err()
+                            {
+                                return;
+                            }
                         }
                     }
                 }
@@ -86,20 +90,37 @@ impl AichatSessionFiles {
         drop(sender);
     }
 
-    pub(crate) fn convert_path_to_entry(path: &Path) -> IndexEntry {
+    /// Finds the paths to re-index after a change to `path` that the file watcher reported
+    pub(crate) fn index_paths_for_change(&self, path: &Path) -> Vec<IndexPath> {
+        // sessions are only collected directly inside each directory
+        let in_session_dir = path
+            .parent()
+            .map(|parent| self.paths.iter().any(|root| root == parent))
+            .unwrap_or(false);
+
+        if in_session_dir
+            && path.is_file()
+            && path.extension().map(|e| e == "yaml").unwrap_or(false)
+        {
+            vec![IndexPath::AichatSessionFile(path.to_path_buf())]
+        } else {
+            vec![]
+        }
+    }
+
+    pub(crate) fn convert_path_to_entry(path: &Path) -> Result<IndexEntry, IndexingError> {
         debug!(
             "attempting to convert {} to entry...",
             path.to_string_lossy()
         );
 
-        // TODO properly handle non UTF-8 file contents
         // TODO handle very large files efficiently, maybe switch to a streaming library
-        let body = fs::read_to_string(&path).unwrap_or_default();
+        let decoded = DecodedText::read(path, None)?;
 
         // Deserialize the YAML into our Session structure.
         // TODO replace `from_str` with `from_reader`
         let session: Session =
-            serde_yaml::from_str(&body).expect("should be able to deserialize yaml");
+            serde_yaml::from_str(&decoded.text).map_err(|e| IndexingError::Parse(e.to_string()))?;
 
         // Extract title according to priority rules
         let title = Self::extract_title(&path);
@@ -117,12 +138,13 @@ impl AichatSessionFiles {
             .flatten()
             .collect();
 
-        IndexEntry::new(
+        Ok(IndexEntry::new(
             AICHAT_SESSION_FILES_SOURCE.to_owned(),
             path.to_string_lossy().to_string(),
             title,
             message_vec.join("\n"),
         )
+        .with_decoded_text(&decoded))
     }
 
     fn extract_title(path: &&Path) -> String {
@@ -0,0 +1,258 @@
+use ignore::Walk;
+use std::{
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+use tracing::{debug, warn};
+use tree_sitter_tags::{TagsConfiguration, TagsContext};
+
+use crate::{
+    config::PathList,
+    model::{
+        decoded_text::DecodedText,
+        file_watcher::FileWatcher,
+        fulltext_index::{KIND, LANG, LINE, SYMBOL},
+        index_entry::{IndexEntry, IndexPath, IndexPathSender},
+        indexing_error::IndexingError,
+    },
+};
+
+pub(crate) const CODE_SYMBOLS_SOURCE: &str = "code_symbols";
+
+/// A language that we can extract symbol definitions from
+struct SymbolLanguage {
+    /// The name used for the `lang` field
+    name: &'static str,
+    extensions: &'static [&'static str],
+    config: TagsConfiguration,
+}
+
+/// The tags configurations are built once since compiling the tags queries is expensive
+static SYMBOL_LANGUAGES: LazyLock<Vec<SymbolLanguage>> = LazyLock::new(|| {
+    [
+        (
+            "rust",
+            &["rs"][..],
+            TagsConfiguration::new(
+                tree_sitter_rust::LANGUAGE.into(),
+                tree_sitter_rust::TAGS_QUERY,
+                "",
+            ),
+        ),
+        (
+            "python",
+            &["py", "pyi"][..],
+            TagsConfiguration::new(
+                tree_sitter_python::LANGUAGE.into(),
+                tree_sitter_python::TAGS_QUERY,
+                "",
+            ),
+        ),
+        (
+            "javascript",
+            &["js", "mjs", "cjs", "jsx"][..],
+            TagsConfiguration::new(
+                tree_sitter_javascript::LANGUAGE.into(),
+                tree_sitter_javascript::TAGS_QUERY,
+                tree_sitter_javascript::LOCALS_QUERY,
+            ),
+        ),
+        (
+            "go",
+            &["go"][..],
+            TagsConfiguration::new(
+                tree_sitter_go::LANGUAGE.into(),
+                tree_sitter_go::TAGS_QUERY,
+                "",
+            ),
+        ),
+        (
+            "c",
+            &["c", "h"][..],
+            TagsConfiguration::new(
+                tree_sitter_c::LANGUAGE.into(),
+                tree_sitter_c::TAGS_QUERY,
+                "",
+            ),
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(name, extensions, config)| match config {
+        Ok(config) => Some(SymbolLanguage {
+            name,
+            extensions,
+            config,
+        }),
+        Err(e) => {
+            warn!("could not load the {} tags query: {}", name, e);
+            None
+        }
+    })
+    .collect()
+});
+
+/// A symbol definition found in a source file
+struct Symbol {
+    name: String,
+    kind: &'static str,
+    /// The one based line number of the definition
+    line: usize,
+    definition: String,
+    docs: Option<String>,
+}
+
+pub(crate) struct CodeSymbols {
+    paths: PathList,
+}
+
+impl CodeSymbols {
+    /// Creates a new CodeSymbols object that holds the paths for project directories.
+    ///
+    /// Tildes in the config are expanded on construction.
+    pub(crate) fn new(path_list: &PathList) -> Self {
+        let paths = path_list
+            .iter()
+            // expand tildes into absolute paths
+            .map(|p| PathBuf::from(shellexpand::tilde(&p.to_string_lossy()).into_owned()))
+            .collect();
+
+        CodeSymbols { paths }
+    }
+
+    /// Finds the language for a source file based on its extension
+    fn language(path: &Path) -> Option<&'static SymbolLanguage> {
+        let extension = path.extension()?.to_string_lossy().to_lowercase();
+        SYMBOL_LANGUAGES
+            .iter()
+            .find(|l| l.extensions.contains(&extension.as_str()))
+    }
+
+    /// Maps the tree-sitter syntax types onto short, ctags style kinds e.g. `kind:fn`
+    fn kind(syntax_type: &str) -> &'static str {
+        match syntax_type {
+            "function" | "method" => "fn",
+            "class" | "type" => "type",
+            "interface" => "trait",
+            "module" => "mod",
+            "macro" => "macro",
+            "constant" => "const",
+            _ => "symbol",
+        }
+    }
+
+    /// Collects all of the entries and sends them to the indexer.
+    ///
+    /// Ignore files are respected so that build output and vendored dependencies are skipped. This method does
+    /// this in a separate thread.
+    pub(crate) fn collect_entries(&self, sender: IndexPathSender) {
+        for dir in &self.paths {
+            for result in Walk::new(dir) {
+                match result {
+                    Err(e) => {
+                        // TODO collect these errors so the user can see what is not being indexed properly
+                        warn!("could not open path: {}", e)
+                    }
+
+                    Ok(entry) => {
+                        if entry.file_type().map(|e| e.is_file()).unwrap_or(false)
+                            && Self::language(entry.path()).is_some()
+                        {
+                            debug!("sending path {}...", entry.path().to_string_lossy());
+                            if sender
+                                .send(IndexPath::CodeFile(entry.path().to_path_buf()))
+                                .is_err()
+                            {
+                                return;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        // once we are done, close the channel
+        drop(sender);
+    }
+
+    /// Finds the paths to re-index after a change to `path` that the file watcher reported
+    pub(crate) fn index_paths_for_change(&self, path: &Path) -> Vec<IndexPath> {
+        match self.paths.iter().find(|root| path.starts_with(root)) {
+            Some(root)
+                if path.is_file()
+                    && Self::language(path).is_some()
+                    && !FileWatcher::is_ignored(root, path, false) =>
+            {
+                vec![IndexPath::CodeFile(path.to_path_buf())]
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Extracts the symbol definitions from the source code
+    fn symbols(language: &SymbolLanguage, source: &[u8]) -> Vec<Symbol> {
+        let mut context = TagsContext::new();
+        let tags = match context.generate_tags(&language.config, source, None) {
+            Ok((tags, _has_error)) => tags,
+            Err(e) => {
+                warn!("could not parse {} source: {}", language.name, e);
+                return vec![];
+            }
+        };
+
+        tags.filter_map(Result::ok)
+            .filter(|tag| tag.is_definition)
+            .map(|tag| Symbol {
+                name: String::from_utf8_lossy(&source[tag.name_range.clone()]).into_owned(),
+                kind: Self::kind(language.config.syntax_type_name(tag.syntax_type_id)),
+                line: tag.span.start.row + 1,
+                definition: String::from_utf8_lossy(&source[tag.line_range.clone()])
+                    .trim()
+                    .to_owned(),
+                docs: tag.docs,
+            })
+            .collect()
+    }
+
+    /// Creates one entry per symbol definition in a source file
+    pub(crate) fn convert_path_to_entries(
+        path: &Path,
+        max_bytes: Option<u64>,
+    ) -> Result<Vec<IndexEntry>, IndexingError> {
+        debug!(
+            "attempting to convert {} to entries...",
+            path.to_string_lossy()
+        );
+
+        let Some(language) = Self::language(path) else {
+            return Ok(vec![]);
+        };
+
+        // tree-sitter works on UTF-8, so e.g. Latin-1 sources are transcoded first
+        let decoded = DecodedText::read(path, max_bytes)?;
+
+        Ok(Self::symbols(language, decoded.text.as_bytes())
+            .into_iter()
+            .map(|symbol| {
+                // render the definition as markdown so the preview shows the code and its docs
+                let mut body = format!(
+                    "`{}` **{}** line {}\n\n```{}\n{}\n```\n",
+                    symbol.kind, symbol.name, symbol.line, language.name, symbol.definition
+                );
+                if let Some(docs) = &symbol.docs {
+                    body.push_str(&format!("\n{}\n", docs));
+                }
+
+                IndexEntry::new(
+                    CODE_SYMBOLS_SOURCE.to_owned(),
+                    path.to_string_lossy().to_string(),
+                    symbol.name.clone(),
+                    body,
+                )
+                .with_metadata(SYMBOL, symbol.name)
+                .with_metadata(KIND, symbol.kind)
+                .with_metadata(LANG, language.name)
+                .with_metadata(LINE, symbol.line.to_string())
+                .with_decoded_text(&decoded)
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,457 @@
+use exif::{In, Tag, Value};
+use ignore::Walk;
+use roxmltree::Document;
+use tracing::{debug, warn};
+
+use crate::{
+    config::PathList,
+    model::{
+        file_fingerprint::FileFingerprint,
+        file_watcher::FileWatcher,
+        fulltext_index::{CAMERA, DATE, KEYWORDS, PLACE},
+        index_entry::{IndexEntry, IndexPath, IndexPathSender},
+        indexing_error::IndexingError,
+    },
+};
+use std::{
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+pub(crate) const IMAGE_FILES_SOURCE: &str = "image_files";
+
+/// File extensions that we attempt to read metadata from
+const IMAGE_EXTENSIONS: [&str; 6] = ["jpg", "jpeg", "png", "heic", "heif", "webp"];
+
+// XMP namespaces for the properties that we index
+const RDF_NS: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+const DC_NS: &str = "http://purl.org/dc/elements/1.1/";
+const XMP_NS: &str = "http://ns.adobe.com/xap/1.0/";
+const EXIF_NS: &str = "http://ns.adobe.com/exif/1.0/";
+const TIFF_NS: &str = "http://ns.adobe.com/tiff/1.0/";
+const PHOTOSHOP_NS: &str = "http://ns.adobe.com/photoshop/1.0/";
+const IPTC_CORE_NS: &str = "http://iptc.org/std/Iptc4xmpCore/1.0/xmlns/";
+
+/// The metadata that we were able to pull out of an image
+///
+/// Values are filled in from XMP first, then IPTC, then EXIF. The first value found wins.
+#[derive(Default)]
+struct ImageMetadata {
+    title: Option<String>,
+    caption: Option<String>,
+    keywords: Vec<String>,
+    make: Option<String>,
+    model: Option<String>,
+    date: Option<String>,
+    sublocation: Option<String>,
+    city: Option<String>,
+    state: Option<String>,
+    country: Option<String>,
+}
+
+impl ImageMetadata {
+    fn set(slot: &mut Option<String>, value: &str) {
+        let value = value.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+        if slot.is_none() && !value.is_empty() {
+            *slot = Some(value.to_owned());
+        }
+    }
+
+    fn add_keyword(&mut self, keyword: &str) {
+        let keyword = keyword.trim();
+        if !keyword.is_empty() && !self.keywords.iter().any(|k| k == keyword) {
+            self.keywords.push(keyword.to_owned());
+        }
+    }
+
+    /// The camera make and model, avoiding repetition since many models already contain the make
+    fn camera(&self) -> Option<String> {
+        match (&self.make, &self.model) {
+            (Some(make), Some(model)) if model.starts_with(make.as_str()) => Some(model.clone()),
+            (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+            (make, model) => make.clone().or(model.clone()),
+        }
+    }
+
+    /// The place name, from most to least specific
+    fn place(&self) -> Option<String> {
+        let parts: Vec<&str> = [&self.sublocation, &self.city, &self.state, &self.country]
+            .into_iter()
+            .filter_map(|p| p.as_deref())
+            .collect();
+
+        (!parts.is_empty()).then(|| parts.join(", "))
+    }
+
+    fn apply_xmp_property(&mut self, namespace: &str, name: &str, values: &[&str]) {
+        let Some(first) = values.first() else {
+            return;
+        };
+
+        match (namespace, name) {
+            (DC_NS, "title") => Self::set(&mut self.title, first),
+            (DC_NS, "description") => Self::set(&mut self.caption, first),
+            (DC_NS, "subject") => values.iter().for_each(|v| self.add_keyword(v)),
+            (PHOTOSHOP_NS, "Headline") => Self::set(&mut self.title, first),
+            (PHOTOSHOP_NS, "DateCreated") => Self::set(&mut self.date, first),
+            (PHOTOSHOP_NS, "City") => Self::set(&mut self.city, first),
+            (PHOTOSHOP_NS, "State") => Self::set(&mut self.state, first),
+            (PHOTOSHOP_NS, "Country") => Self::set(&mut self.country, first),
+            (IPTC_CORE_NS, "Location") => Self::set(&mut self.sublocation, first),
+            (EXIF_NS, "DateTimeOriginal") => Self::set(&mut self.date, first),
+            (XMP_NS, "CreateDate") => Self::set(&mut self.date, first),
+            (TIFF_NS, "Make") => Self::set(&mut self.make, first),
+            (TIFF_NS, "Model") => Self::set(&mut self.model, first),
+            _ => (),
+        }
+    }
+
+    /// Reads the embedded XMP packet, if there is one
+    fn read_xmp(&mut self, bytes: &[u8]) {
+        let Some(start) = find_bytes(bytes, b"<x:xmpmeta") else {
+            return;
+        };
+        let end_tag = b"</x:xmpmeta>";
+        let Some(end) = find_bytes(&bytes[start..], end_tag) else {
+            return;
+        };
+        let packet = String::from_utf8_lossy(&bytes[start..start + end + end_tag.len()]);
+
+        let xmp = match Document::parse(&packet) {
+            Ok(xmp) => xmp,
+            Err(e) => {
+                warn!("could not parse XMP packet: {}", e);
+                return;
+            }
+        };
+
+        for node in xmp.descendants().filter(|n| n.is_element()) {
+            // simple properties may be written as attributes on the description
+            if node.has_tag_name((RDF_NS, "Description")) {
+                for attribute in node.attributes() {
+                    if let Some(namespace) = attribute.namespace() {
+                        self.apply_xmp_property(namespace, attribute.name(), &[attribute.value()]);
+                    }
+                }
+                continue;
+            }
+
+            let Some(namespace) = node.tag_name().namespace() else {
+                continue;
+            };
+
+            // array properties (Alt, Bag, Seq) hold their values in `rdf:li` elements
+            let list_items: Vec<&str> = node
+                .descendants()
+                .filter(|n| n.has_tag_name((RDF_NS, "li")))
+                .filter_map(|n| n.text())
+                .collect();
+            let values = if list_items.is_empty() {
+                node.text().into_iter().collect()
+            } else {
+                list_items
+            };
+
+            self.apply_xmp_property(namespace, node.tag_name().name(), &values);
+        }
+    }
+
+    /// Reads the IPTC-IIM records from a Photoshop image resource block, if there is one
+    fn read_iptc(&mut self, bytes: &[u8]) {
+        // the IPTC-NAA resource has the id 0x0404
+        let Some(resource_start) = find_bytes(bytes, b"8BIM\x04\x04") else {
+            return;
+        };
+        let mut pos = resource_start + 6;
+
+        // skip the resource name, a pascal string padded to an even length
+        let Some(&name_len) = bytes.get(pos) else {
+            return;
+        };
+        pos += (name_len as usize + 2) & !1;
+
+        let Some(size_bytes) = bytes.get(pos..pos + 4) else {
+            return;
+        };
+        let size = u32::from_be_bytes([size_bytes[0], size_bytes[1], size_bytes[2], size_bytes[3]]);
+        pos += 4;
+        let Some(records) = bytes.get(pos..pos + size as usize) else {
+            return;
+        };
+
+        // each record is a tag marker, record number, dataset number, two byte length and the value
+        let mut pos = 0;
+        while let Some(&[0x1c, record, dataset, len_hi, len_lo]) = records.get(pos..pos + 5) {
+            // extended length records are not used for any of the text datasets we care about
+            if len_hi & 0x80 != 0 {
+                break;
+            }
+            let len = u16::from_be_bytes([len_hi, len_lo]) as usize;
+            pos += 5;
+            let Some(value) = records.get(pos..pos + len) else {
+                break;
+            };
+            pos += len;
+
+            if record != 2 {
+                continue;
+            }
+            let value = String::from_utf8_lossy(value);
+            match dataset {
+                5 | 105 => Self::set(&mut self.title, &value),
+                25 => self.add_keyword(&value),
+                55 => Self::set(&mut self.date, &value),
+                90 => Self::set(&mut self.city, &value),
+                92 => Self::set(&mut self.sublocation, &value),
+                95 => Self::set(&mut self.state, &value),
+                101 => Self::set(&mut self.country, &value),
+                120 => Self::set(&mut self.caption, &value),
+                _ => (),
+            }
+        }
+    }
+
+    /// Reads the EXIF attributes via the container parsers in the exif crate
+    fn read_exif(&mut self, bytes: &[u8]) {
+        let exif = match exif::Reader::new().read_from_container(&mut Cursor::new(bytes)) {
+            Ok(exif) => exif,
+            Err(e) => {
+                debug!("no usable EXIF data: {}", e);
+                return;
+            }
+        };
+
+        let ascii = |tag: Tag| match exif.get_field(tag, In::PRIMARY).map(|f| &f.value) {
+            Some(Value::Ascii(values)) => values
+                .first()
+                .map(|v| String::from_utf8_lossy(v).into_owned()),
+            _ => None,
+        };
+
+        if let Some(description) = ascii(Tag::ImageDescription) {
+            Self::set(&mut self.caption, &description);
+        }
+        if let Some(make) = ascii(Tag::Make) {
+            Self::set(&mut self.make, &make);
+        }
+        if let Some(model) = ascii(Tag::Model) {
+            Self::set(&mut self.model, &model);
+        }
+        if let Some(date) = exif.get_field(Tag::DateTimeOriginal, In::PRIMARY) {
+            Self::set(&mut self.date, &date.display_value().to_string());
+        }
+    }
+}
+
+/// Returns the position of the first occurrence of needle in haystack
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+pub(crate) struct ImageFiles {
+    paths: PathList,
+}
+
+impl ImageFiles {
+    /// Creates a new ImageFiles object that holds the paths for directories of photos.
+    ///
+    /// Tildes in the config are expanded on construction.
+    pub(crate) fn new(path_list: &PathList) -> Self {
+        let paths = path_list
+            .iter()
+            // expand tildes into absolute paths
+            .map(|p| PathBuf::from(shellexpand::tilde(&p.to_string_lossy()).into_owned()))
+            .collect();
+
+        ImageFiles { paths }
+    }
+
+    /// Checks the file extension to see if this is an image that we can read metadata from
+    fn is_image(path: &Path) -> bool {
+        path.extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .map(|e| IMAGE_EXTENSIONS.contains(&e.as_str()))
+            .unwrap_or(false)
+    }
+
+    /// Collects all of the entries and sends them to the indexer.
+    ///
+    /// This method does this in a separate thread.
+    pub(crate) fn collect_entries(&self, sender: IndexPathSender) {
+        for dir in &self.paths {
+            for result in Walk::new(dir) {
+                match result {
+                    Err(e) => {
+                        // TODO collect these errors so the user can see what is not being indexed properly
+                        warn!("could not open path: {}", e)
+                    }
+
+                    Ok(entry) => {
+                        if entry.file_type().map(|e| e.is_file()).unwrap_or(false)
+                            && Self::is_image(entry.path())
+                        {
+                            debug!("sending path {}...", entry.path().to_string_lossy());
+                            if sender
+                                .send(IndexPath::ImageFile(entry.path().to_path_buf()))
+                                .is_err()
+                            {
+                                return;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        // once we are done, close the channel
+        drop(sender);
+    }
+
+    /// Finds the paths to re-index after a change to `path` that the file watcher reported
+    pub(crate) fn index_paths_for_change(&self, path: &Path) -> Vec<IndexPath> {
+        match self.paths.iter().find(|root| path.starts_with(root)) {
+            Some(root)
+                if path.is_file()
+                    && Self::is_image(path)
+                    && !FileWatcher::is_ignored(root, path, false) =>
+            {
+                vec![IndexPath::ImageFile(path.to_path_buf())]
+            }
+            _ => vec![],
+        }
+    }
+
+    pub(crate) fn convert_path_to_entry(path: &Path) -> Result<IndexEntry, IndexingError> {
+        debug!(
+            "attempting to convert {} to entry...",
+            path.to_string_lossy()
+        );
+
+        // TODO handle very large files efficiently, the metadata is usually near the start of the file
+        let bytes = fs::read(path)?;
+
+        let mut metadata = ImageMetadata::default();
+        metadata.read_xmp(&bytes);
+        metadata.read_iptc(&bytes);
+        metadata.read_exif(&bytes);
+
+        let file_name = path
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or("UNKNOWN_TITLE".to_string());
+
+        // fall back to the caption and then the file name if the image has no title
+        let title = metadata
+            .title
+            .clone()
+            .or(metadata.caption.clone())
+            .unwrap_or(file_name);
+
+        let camera = metadata.camera().unwrap_or_default();
+        let place = metadata.place().unwrap_or_default();
+        let date = metadata.date.clone().unwrap_or_default();
+        let keywords = metadata.keywords.join(", ");
+
+        // render the metadata as markdown so the body is searchable and has a readable snippet
+        let mut body = format!("# {}\n\n", title);
+        if let Some(caption) = &metadata.caption {
+            body.push_str(&format!("{}\n\n", caption));
+        }
+        for (label, value) in [
+            ("Keywords", &keywords),
+            ("Camera", &camera),
+            ("Captured", &date),
+            ("Place", &place),
+        ] {
+            if !value.is_empty() {
+                body.push_str(&format!("- **{}:** {}\n", label, value));
+            }
+        }
+
+        Ok(IndexEntry::new(
+            IMAGE_FILES_SOURCE.to_owned(),
+            path.to_string_lossy().to_string(),
+            title,
+            body,
+        )
+        .with_metadata(KEYWORDS, keywords)
+        .with_metadata(CAMERA, camera)
+        .with_metadata(DATE, date)
+        .with_metadata(PLACE, place)
+        .with_content_hash(FileFingerprint::hash_bytes(&bytes)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An IPTC-IIM record 2 dataset
+    fn dataset(dataset: u8, value: &str) -> Vec<u8> {
+        let mut record = vec![0x1c, 2, dataset];
+        record.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        record.extend_from_slice(value.as_bytes());
+        record
+    }
+
+    /// A Photoshop IPTC-NAA resource block holding the given records
+    fn iptc_block(records: &[u8]) -> Vec<u8> {
+        // an empty resource name is padded to two bytes
+        let mut block = b"Photoshop 3.0\08BIM\x04\x04\0\0".to_vec();
+        block.extend_from_slice(&(records.len() as u32).to_be_bytes());
+        block.extend_from_slice(records);
+        block
+    }
+
+    #[test]
+    fn reads_iptc_datasets() {
+        let records: Vec<u8> = [
+            dataset(5, "Beach day"),
+            dataset(25, "sea"),
+            dataset(25, "sand"),
+            dataset(25, "sea"),
+            dataset(55, "20240612"),
+            dataset(90, "Lisbon"),
+            dataset(92, "Praia"),
+            dataset(101, "Portugal"),
+            dataset(120, "Waves at sunset\0"),
+        ]
+        .concat();
+        let mut metadata = ImageMetadata::default();
+        metadata.read_iptc(&iptc_block(&records));
+
+        assert_eq!(metadata.title.as_deref(), Some("Beach day"));
+        assert_eq!(metadata.keywords, ["sea", "sand"]);
+        assert_eq!(metadata.date.as_deref(), Some("20240612"));
+        assert_eq!(metadata.place().as_deref(), Some("Praia, Lisbon, Portugal"));
+        assert_eq!(metadata.caption.as_deref(), Some("Waves at sunset"));
+    }
+
+    #[test]
+    fn keeps_values_read_before_iptc() {
+        let mut metadata = ImageMetadata {
+            title: Some("From XMP".to_owned()),
+            ..Default::default()
+        };
+        let records = [dataset(5, "From IPTC"), dataset(105, "Headline")].concat();
+        metadata.read_iptc(&iptc_block(&records));
+        assert_eq!(metadata.title.as_deref(), Some("From XMP"));
+    }
+
+    #[test]
+    fn skips_truncated_and_envelope_iptc_records() {
+        let mut records = [dataset(5, "Beach day"), dataset(120, "Waves at sunset")].concat();
+        records.truncate(records.len() - 4);
+        let mut metadata = ImageMetadata::default();
+        metadata.read_iptc(&iptc_block(&records));
+        assert_eq!(metadata.title.as_deref(), Some("Beach day"));
+        assert_eq!(metadata.caption, None);
+
+        // records from another IPTC record number are skipped
+        let mut envelope = dataset(5, "Envelope");
+        envelope[1] = 1;
+        let mut metadata = ImageMetadata::default();
+        metadata.read_iptc(&iptc_block(&envelope));
+        assert_eq!(metadata.title, None);
+    }
+}
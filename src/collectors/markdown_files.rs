@@ -3,12 +3,14 @@ use tracing::{debug, warn};
 
 use crate::{
     config::PathList,
-    model::index_entry::{IndexEntry, IndexPath, IndexPathSender},
-};
-use std::{
-    fs,
-    path::{Path, PathBuf},
+    model::{
+        decoded_text::DecodedText,
+        file_watcher::FileWatcher,
+        index_entry::{IndexEntry, IndexPath, IndexPathSender},
+        indexing_error::IndexingError,
+    },
 };
+use std::path::{Path, PathBuf};
 
 pub(crate) const MARKDOWN_FILES_SOURCE: &str = "markdown_files";
 
@@ -48,9 +50,13 @@ impl MarkdownFiles {
                             // TODO check the file type here
                             // if this is a file, send it to the fulltext index to check if it is already indexed and up to date
                             debug!("sending path {}...", entry.path().to_string_lossy());
-                            sender
+                            // the index stops taking paths once nobody waits for it anymore e.g. after switching profiles
+                            if sender
                                 .send(IndexPath::MarkdownFile(entry.path().to_path_buf()))
-                                .expect("should be able to send new entries to index");
+                                .is_err()
+                            {
+                                return;
+                            }
                         }
                     }
                 }
@@ -60,6 +66,16 @@ impl MarkdownFiles {
         drop(sender);
     }
 
+    /// Finds the paths to re-index after a change to `path` that the file watcher reported
+    pub(crate) fn index_paths_for_change(&self, path: &Path) -> Vec<IndexPath> {
+        match self.paths.iter().find(|root| path.starts_with(root)) {
+            Some(root) if path.is_file() && !FileWatcher::is_ignored(root, path, false) => {
+                vec![IndexPath::MarkdownFile(path.to_path_buf())]
+            }
+            _ => vec![],
+        }
+    }
+
     /// Extracts the title from markdown content according to priority rules:
     /// 1. Title from frontmatter (if present)
     /// 2. First level 1 heading (# Heading)
@@ -125,24 +141,68 @@ impl MarkdownFiles {
         None
     }
 
-    pub(crate) fn convert_path_to_entry(path: &Path) -> IndexEntry {
+    pub(crate) fn convert_path_to_entry(
+        path: &Path,
+        max_bytes: Option<u64>,
+    ) -> Result<IndexEntry, IndexingError> {
         debug!(
             "attempting to convert {} to entry...",
             path.to_string_lossy()
         );
 
-        // TODO properly handle non UTF-8 file contents
-        // TODO handle very large files efficiently
-        let body = fs::read_to_string(&path).unwrap_or_default();
+        let decoded = DecodedText::read(path, max_bytes)?;
 
         // Extract title according to priority rules
-        let title = Self::extract_title(&body, &path);
+        let title = Self::extract_title(&decoded.text, &path);
 
-        IndexEntry::new(
+        Ok(IndexEntry::new(
             MARKDOWN_FILES_SOURCE.to_owned(),
             path.to_string_lossy().to_string(),
             title,
-            body,
+            decoded.text.clone(),
         )
+        .with_decoded_text(&decoded))
+    }
+
+    /// Converts a file that is too large for a single document into one document per chunk of `chunk_bytes`
+    ///
+    /// Every part is titled after the file, so a hit in any of them is easy to place. Only the first chunk is read
+    /// here, the others are read as the entries are taken.
+    pub(crate) fn convert_path_to_chunked_entries(
+        path: &Path,
+        chunk_bytes: u64,
+    ) -> Result<impl Iterator<Item = Result<IndexEntry, IndexingError>> + Send + use<>, IndexingError>
+    {
+        debug!(
+            "attempting to convert {} to entries in chunks of {} bytes...",
+            path.to_string_lossy(),
+            chunk_bytes
+        );
+
+        let mut chunks = DecodedText::read_chunks(path, chunk_bytes)?;
+        // there is always a first chunk, even for an empty file
+        let first = chunks.next().transpose()?;
+        let title = first
+            .as_ref()
+            .map(|chunk| Self::extract_title(&chunk.text, path))
+            .unwrap_or_default();
+        let path = path.to_string_lossy().to_string();
+
+        Ok(first
+            .map(Ok)
+            .into_iter()
+            .chain(chunks)
+            .enumerate()
+            .map(move |(part, chunk)| {
+                chunk.map(|mut chunk| {
+                    IndexEntry::new(
+                        MARKDOWN_FILES_SOURCE.to_owned(),
+                        path.clone(),
+                        format!("{} (part {})", title, part + 1),
+                        std::mem::take(&mut chunk.text),
+                    )
+                    .with_decoded_text(&chunk)
+                })
+            }))
     }
 }
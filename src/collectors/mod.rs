@@ -1,3 +1,8 @@
 pub mod aichat_session_files;
+pub mod code_symbols;
+pub mod image_files;
 pub mod markdown_files;
+pub mod music_files;
+pub mod projects;
+pub mod reading_highlights;
 pub mod web_scrapbook_files;
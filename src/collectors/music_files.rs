@@ -0,0 +1,302 @@
+use ignore::Walk;
+use lofty::{
+    file::TaggedFileExt,
+    tag::{Accessor, ItemKey, Tag},
+};
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::{debug, warn};
+
+use crate::{
+    config::PathList,
+    model::{
+        file_watcher::FileWatcher,
+        fulltext_index::{ALBUM, ARTIST, DATE, GENRE, KIND, TRACK},
+        index_entry::{IndexEntry, IndexPath, IndexPathSender},
+    },
+};
+
+pub(crate) const MUSIC_FILES_SOURCE: &str = "music_files";
+
+/// File extensions of the audio formats whose tags we can read
+const AUDIO_EXTENSIONS: [&str; 10] = [
+    "mp3", "flac", "ogg", "oga", "opus", "m4a", "m4b", "mp4", "aac", "wv",
+];
+
+/// The kind of document that represents a single track
+pub(crate) const TRACK_KIND: &str = "track";
+
+/// The kind of document that groups the tracks of an album
+pub(crate) const ALBUM_KIND: &str = "album";
+
+/// The tags of a single track that we index
+#[derive(Default)]
+struct TrackTags {
+    title: Option<String>,
+    artist: Option<String>,
+    album_artist: Option<String>,
+    album: Option<String>,
+    track: Option<u32>,
+    year: Option<u16>,
+    genre: Option<String>,
+    lyrics: Option<String>,
+}
+
+impl TrackTags {
+    /// Reads the primary tag of the file, falling back to any other tag that is present
+    fn read(path: &Path) -> Self {
+        let tagged_file = match lofty::read_from_path(path) {
+            Ok(tagged_file) => tagged_file,
+            Err(e) => {
+                warn!("could not read tags from {}: {}", path.to_string_lossy(), e);
+                return Self::default();
+            }
+        };
+
+        match tagged_file.primary_tag().or(tagged_file.first_tag()) {
+            Some(tag) => Self::from_tag(tag),
+            None => Self::default(),
+        }
+    }
+
+    fn from_tag(tag: &Tag) -> Self {
+        let string = |key: ItemKey| tag.get_string(key).map(|s| s.to_owned());
+
+        Self {
+            title: tag.title().map(|t| t.into_owned()),
+            artist: tag.artist().map(|a| a.into_owned()),
+            album_artist: string(ItemKey::AlbumArtist),
+            album: tag.album().map(|a| a.into_owned()),
+            track: tag.track(),
+            year: tag.date().map(|d| d.year),
+            genre: tag.genre().map(|g| g.into_owned()),
+            lyrics: string(ItemKey::Lyrics).or(string(ItemKey::UnsyncLyrics)),
+        }
+    }
+}
+
+pub(crate) struct MusicFiles {
+    paths: PathList,
+    album_documents: bool,
+}
+
+impl MusicFiles {
+    /// Creates a new MusicFiles object that holds the paths for music libraries.
+    ///
+    /// Tildes in the config are expanded on construction.
+    pub(crate) fn new(path_list: &PathList, album_documents: bool) -> Self {
+        let paths = path_list
+            .iter()
+            // expand tildes into absolute paths
+            .map(|p| PathBuf::from(shellexpand::tilde(&p.to_string_lossy()).into_owned()))
+            .collect();
+
+        MusicFiles {
+            paths,
+            album_documents,
+        }
+    }
+
+    /// Checks the file extension to see if this is an audio file that we can read tags from
+    fn is_audio(path: &Path) -> bool {
+        path.extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .map(|e| AUDIO_EXTENSIONS.contains(&e.as_str()))
+            .unwrap_or(false)
+    }
+
+    /// Collects all of the entries and sends them to the indexer.
+    ///
+    /// Each track is sent on its own. If album documents are enabled, every directory holding audio files is sent
+    /// as an album once the walk is done. This method does this in a separate thread.
+    pub(crate) fn collect_entries(&self, sender: IndexPathSender) {
+        let mut album_dirs = BTreeSet::new();
+
+        for dir in &self.paths {
+            for result in Walk::new(dir) {
+                match result {
+                    Err(e) => {
+                        // TODO collect these errors so the user can see what is not being indexed properly
+                        warn!("could not open path: {}", e)
+                    }
+
+                    Ok(entry) => {
+                        if entry.file_type().map(|e| e.is_file()).unwrap_or(false)
+                            && Self::is_audio(entry.path())
+                        {
+                            debug!("sending path {}...", entry.path().to_string_lossy());
+                            if sender
+                                .send(IndexPath::MusicFile(entry.path().to_path_buf()))
+                                .is_err()
+                            {
+                                return;
+                            }
+
+                            if let Some(parent) = entry.path().parent() {
+                                album_dirs.insert(parent.to_path_buf());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if self.album_documents {
+            for album_dir in album_dirs {
+                debug!("sending album {}...", album_dir.to_string_lossy());
+                if sender.send(IndexPath::MusicAlbum(album_dir)).is_err() {
+                    return;
+                }
+            }
+        }
+
+        // once we are done, close the channel
+        drop(sender);
+    }
+
+    /// Finds the paths to re-index after a change to `path` that the file watcher reported
+    ///
+    /// The album is re-indexed as well when album documents are enabled, even if the track was deleted.
+    pub(crate) fn index_paths_for_change(&self, path: &Path) -> Vec<IndexPath> {
+        let Some(root) = self.paths.iter().find(|root| path.starts_with(root)) else {
+            return vec![];
+        };
+        if !Self::is_audio(path) || FileWatcher::is_ignored(root, path, false) {
+            return vec![];
+        }
+
+        let mut index_paths = vec![];
+        if path.is_file() {
+            index_paths.push(IndexPath::MusicFile(path.to_path_buf()));
+        }
+        if self.album_documents
+            && let Some(parent) = path.parent()
+            && parent.is_dir()
+        {
+            index_paths.push(IndexPath::MusicAlbum(parent.to_path_buf()));
+        }
+        index_paths
+    }
+
+    pub(crate) fn convert_path_to_entry(path: &Path) -> IndexEntry {
+        debug!(
+            "attempting to convert {} to entry...",
+            path.to_string_lossy()
+        );
+
+        let tags = TrackTags::read(path);
+
+        let title = tags.title.clone().unwrap_or_else(|| {
+            path.file_stem()
+                .map(|f| f.to_string_lossy().to_string())
+                .unwrap_or("UNKNOWN_TITLE".to_string())
+        });
+        let artist = tags.artist.clone().unwrap_or_default();
+        let album = tags.album.clone().unwrap_or_default();
+        let genre = tags.genre.clone().unwrap_or_default();
+        let year = tags.year.map(|y| y.to_string()).unwrap_or_default();
+        let track = tags.track.map(|t| t.to_string()).unwrap_or_default();
+
+        // render the tags as markdown so the preview is readable, lyrics make up the bulk of the body
+        let mut body = format!("# {}\n\n", title);
+        for (label, value) in [
+            ("Artist", &artist),
+            ("Album", &album),
+            ("Track", &track),
+            ("Year", &year),
+            ("Genre", &genre),
+        ] {
+            if !value.is_empty() {
+                body.push_str(&format!("- **{}:** {}\n", label, value));
+            }
+        }
+        if let Some(lyrics) = &tags.lyrics {
+            body.push_str(&format!("\n## Lyrics\n\n{}\n", lyrics));
+        }
+
+        IndexEntry::new(
+            MUSIC_FILES_SOURCE.to_owned(),
+            path.to_string_lossy().to_string(),
+            title,
+            body,
+        )
+        .with_metadata(KIND, TRACK_KIND)
+        .with_metadata(ARTIST, artist)
+        .with_metadata(ALBUM, album)
+        .with_metadata(TRACK, track)
+        .with_metadata(DATE, year)
+        .with_metadata(GENRE, genre)
+    }
+
+    /// Creates a single document for all of the tracks in an album directory
+    pub(crate) fn convert_album_to_entry(dir: &Path) -> IndexEntry {
+        debug!(
+            "attempting to convert album {} to entry...",
+            dir.to_string_lossy()
+        );
+
+        let mut tracks: Vec<(PathBuf, TrackTags)> = fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .filter_map(Result::ok)
+                    .map(|e| e.path())
+                    .filter(|p| p.is_file() && Self::is_audio(p))
+                    .map(|p| {
+                        let tags = TrackTags::read(&p);
+                        (p, tags)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        tracks.sort_by(|(a_path, a), (b_path, b)| a.track.cmp(&b.track).then(a_path.cmp(b_path)));
+
+        // the album level tags are taken from the first track that has them
+        let first = |f: fn(&TrackTags) -> Option<String>| tracks.iter().find_map(|(_, t)| f(t));
+        let title = first(|t| t.album.clone()).unwrap_or_else(|| {
+            dir.file_name()
+                .map(|f| f.to_string_lossy().to_string())
+                .unwrap_or("UNKNOWN_TITLE".to_string())
+        });
+        let artist = first(|t| t.album_artist.clone().or(t.artist.clone())).unwrap_or_default();
+        let genre = first(|t| t.genre.clone()).unwrap_or_default();
+        let year = first(|t| t.year.map(|y| y.to_string())).unwrap_or_default();
+
+        let mut body = format!("# {}\n\n", title);
+        for (label, value) in [("Artist", &artist), ("Year", &year), ("Genre", &genre)] {
+            if !value.is_empty() {
+                body.push_str(&format!("- **{}:** {}\n", label, value));
+            }
+        }
+        body.push_str("\n## Tracks\n\n");
+        for (track_path, tags) in &tracks {
+            let track_title = tags.title.clone().unwrap_or_else(|| {
+                track_path
+                    .file_stem()
+                    .map(|f| f.to_string_lossy().to_string())
+                    .unwrap_or_default()
+            });
+            match (&tags.track, &tags.artist) {
+                (Some(number), Some(track_artist)) if *track_artist != artist => {
+                    body.push_str(&format!("{}. {} — {}\n", number, track_title, track_artist))
+                }
+                (Some(number), _) => body.push_str(&format!("{}. {}\n", number, track_title)),
+                (None, _) => body.push_str(&format!("- {}\n", track_title)),
+            }
+        }
+
+        IndexEntry::new(
+            MUSIC_FILES_SOURCE.to_owned(),
+            dir.to_string_lossy().to_string(),
+            title.clone(),
+            body,
+        )
+        .with_metadata(KIND, ALBUM_KIND)
+        .with_metadata(ARTIST, artist)
+        .with_metadata(ALBUM, title)
+        .with_metadata(DATE, year)
+        .with_metadata(GENRE, genre)
+    }
+}
@@ -0,0 +1,334 @@
+use directories::BaseDirs;
+use ignore::WalkBuilder;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::{debug, warn};
+
+use crate::{
+    config::PathList,
+    model::{
+        decoded_text::DecodedText,
+        fulltext_index::KIND,
+        index_entry::{IndexEntry, IndexPath, IndexPathSender},
+    },
+};
+
+pub(crate) const PROJECTS_SOURCE: &str = "projects";
+
+/// The kind of document that represents a whole project
+pub(crate) const PROJECT_KIND: &str = "project";
+
+/// Files or directories whose presence marks the root of a project
+const PROJECT_MARKERS: [&str; 4] = [".git", "Cargo.toml", "package.json", "flake.nix"];
+
+/// README file names in order of preference
+const README_FILES: [&str; 6] = [
+    "README.md",
+    "README.markdown",
+    "README.org",
+    "README.rst",
+    "README.txt",
+    "README",
+];
+
+/// How deep below each configured directory we look for projects e.g. `~/Projects/group/project`
+const PROJECT_DEPTH: usize = 2;
+
+/// The name and description of a project as declared in its manifests
+#[derive(Default)]
+struct ProjectManifest {
+    name: Option<String>,
+    description: Option<String>,
+}
+
+impl ProjectManifest {
+    /// Reads the manifests in a project root, earlier markers take precedence
+    fn read(dir: &Path) -> Self {
+        let mut manifest = Self::default();
+        for other in [
+            Self::read_cargo_toml(&dir.join("Cargo.toml")),
+            Self::read_package_json(&dir.join("package.json")),
+            Self::read_flake_nix(&dir.join("flake.nix")),
+        ] {
+            manifest.name = manifest.name.or(other.name);
+            manifest.description = manifest.description.or(other.description);
+        }
+        manifest
+    }
+
+    fn read_cargo_toml(path: &Path) -> Self {
+        let Ok(content) = fs::read_to_string(path) else {
+            return Self::default();
+        };
+        let table = match content.parse::<toml::Table>() {
+            Ok(table) => table,
+            Err(e) => {
+                warn!("could not parse {}: {}", path.to_string_lossy(), e);
+                return Self::default();
+            }
+        };
+
+        // workspaces may only have a `workspace.package` table with the shared description
+        let package = table
+            .get("package")
+            .or(table.get("workspace").and_then(|w| w.get("package")));
+        let string = |key: &str| {
+            package
+                .and_then(|p| p.get(key))
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_owned())
+        };
+
+        Self {
+            name: string("name"),
+            description: string("description"),
+        }
+    }
+
+    fn read_package_json(path: &Path) -> Self {
+        let Ok(content) = fs::read_to_string(path) else {
+            return Self::default();
+        };
+        let json = match serde_json::from_str::<serde_json::Value>(&content) {
+            Ok(json) => json,
+            Err(e) => {
+                warn!("could not parse {}: {}", path.to_string_lossy(), e);
+                return Self::default();
+            }
+        };
+        let string = |key: &str| json.get(key).and_then(|v| v.as_str()).map(|s| s.to_owned());
+
+        Self {
+            name: string("name"),
+            description: string("description"),
+        }
+    }
+
+    /// Flakes only carry a description, which is nearly always a plain string on its own line
+    fn read_flake_nix(path: &Path) -> Self {
+        let Ok(content) = fs::read_to_string(path) else {
+            return Self::default();
+        };
+        let description = content.lines().find_map(|line| {
+            line.trim()
+                .strip_prefix("description")?
+                .trim_start()
+                .strip_prefix('=')?
+                .trim()
+                .strip_suffix(';')?
+                .trim()
+                .strip_prefix('"')?
+                .strip_suffix('"')
+                .map(|s| s.to_owned())
+        });
+
+        Self {
+            name: None,
+            description,
+        }
+    }
+}
+
+/// A project root with its manifests read
+#[derive(Debug, Clone)]
+pub(crate) struct Project {
+    /// The declared name of the project, falling back to its directory name
+    pub(crate) name: String,
+    description: Option<String>,
+}
+
+impl Project {
+    fn read(dir: &Path) -> Self {
+        let manifest = ProjectManifest::read(dir);
+        Self {
+            name: manifest.name.unwrap_or_else(|| {
+                dir.file_name()
+                    .map(|f| f.to_string_lossy().to_string())
+                    .unwrap_or("UNKNOWN_PROJECT".to_string())
+            }),
+            description: manifest.description,
+        }
+    }
+}
+
+/// Finds the project a path belongs to, caching the result for every directory that was checked
+#[derive(Default)]
+pub(crate) struct ProjectLookup {
+    /// `None` for directories that are not within a project, so they are only checked once as well
+    projects: HashMap<PathBuf, Option<Project>>,
+}
+
+impl ProjectLookup {
+    /// Returns the nearest enclosing project, if any, a project root is its own project
+    pub(crate) fn project(&mut self, path: &Path) -> Option<Project> {
+        // the path itself is not cached, most paths are files that are only looked up once
+        if Projects::is_project_root(path) {
+            return Some(Project::read(path));
+        }
+
+        let mut checked = Vec::new();
+        let mut project = None;
+        for dir in path.ancestors().skip(1) {
+            if let Some(cached) = self.projects.get(dir) {
+                project = cached.clone();
+                break;
+            }
+            checked.push(dir.to_path_buf());
+            if Projects::is_project_root(dir) {
+                project = Some(Project::read(dir));
+                break;
+            }
+        }
+
+        // every directory on the way belongs to the same project, or to none
+        for dir in checked {
+            self.projects.insert(dir, project.clone());
+        }
+        project
+    }
+}
+
+pub(crate) struct Projects {
+    paths: PathList,
+}
+
+impl Projects {
+    /// Creates a new Projects object that holds the directories that contain projects.
+    ///
+    /// Tildes in the config are expanded on construction.
+    pub(crate) fn new(path_list: &PathList) -> Self {
+        let paths = path_list
+            .iter()
+            // expand tildes into absolute paths
+            .map(|p| PathBuf::from(shellexpand::tilde(&p.to_string_lossy()).into_owned()))
+            .collect();
+
+        Projects { paths }
+    }
+
+    /// Checks for any of the project markers in a directory
+    ///
+    /// The home directory and the filesystem root are never treated as projects, even if e.g. dotfiles are kept
+    /// in a git repo, since everything would end up in the same project.
+    fn is_project_root(dir: &Path) -> bool {
+        if dir.parent().is_none() {
+            return false;
+        }
+        if let Some(base_dirs) = BaseDirs::new()
+            && dir == base_dirs.home_dir()
+        {
+            return false;
+        }
+
+        PROJECT_MARKERS
+            .iter()
+            .any(|marker| dir.join(marker).exists())
+    }
+
+    /// Collects all of the project roots and sends them to the indexer.
+    ///
+    /// Projects are searched for up to two levels below each configured directory. This method does this in a
+    /// separate thread.
+    pub(crate) fn collect_entries(&self, sender: IndexPathSender) {
+        for dir in &self.paths {
+            for result in WalkBuilder::new(dir).max_depth(Some(PROJECT_DEPTH)).build() {
+                match result {
+                    Err(e) => {
+                        // TODO collect these errors so the user can see what is not being indexed properly
+                        warn!("could not open path: {}", e)
+                    }
+
+                    Ok(entry) => {
+                        if entry.file_type().map(|e| e.is_dir()).unwrap_or(false)
+                            && Self::is_project_root(entry.path())
+                        {
+                            debug!("sending project {}...", entry.path().to_string_lossy());
+                            if sender
+                                .send(IndexPath::Project(entry.path().to_path_buf()))
+                                .is_err()
+                            {
+                                return;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        // once we are done, close the channel
+        drop(sender);
+    }
+
+    /// Finds the paths to re-index after a change to `path` that the file watcher reported
+    ///
+    /// Only changes to the markers and README of a project within two levels of a configured directory count, or a
+    /// project directory that was created or moved there.
+    pub(crate) fn index_paths_for_change(&self, path: &Path) -> Vec<IndexPath> {
+        let within_depth = |dir: &Path| {
+            self.paths.iter().any(|root| {
+                dir.strip_prefix(root)
+                    .map(|relative| relative.components().count() <= PROJECT_DEPTH)
+                    .unwrap_or(false)
+            })
+        };
+        let Some(file_name) = path.file_name().map(|f| f.to_string_lossy()) else {
+            return vec![];
+        };
+        let project_file = PROJECT_MARKERS.contains(&file_name.as_ref())
+            || README_FILES.contains(&file_name.as_ref());
+
+        // a marker or README belongs to the project in its parent, any other directory may be a project itself
+        let dir = match project_file {
+            true => path.parent(),
+            false => Some(path).filter(|path| path.is_dir()),
+        };
+        match dir {
+            Some(dir) if within_depth(dir) && Self::is_project_root(dir) => {
+                vec![IndexPath::Project(dir.to_path_buf())]
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Creates a single document for a project from its manifests, read by `ProjectLookup`, and its README
+    pub(crate) fn convert_path_to_entry(dir: &Path, project: Project) -> IndexEntry {
+        debug!(
+            "attempting to convert project {} to entry...",
+            dir.to_string_lossy()
+        );
+
+        let Project { name, description } = project;
+        let markers: Vec<&str> = PROJECT_MARKERS
+            .into_iter()
+            .filter(|marker| dir.join(marker).exists())
+            .collect();
+
+        // render the project as markdown so the preview is readable, the README makes up the bulk of the body
+        let mut body = format!("# {}\n\n", name);
+        if let Some(description) = &description {
+            body.push_str(&format!("{}\n\n", description));
+        }
+        body.push_str(&format!("- **Markers:** {}\n", markers.join(", ")));
+
+        let readme = README_FILES
+            .iter()
+            .find_map(|readme| DecodedText::read(&dir.join(readme), None).ok());
+        if let Some(readme) = &readme {
+            body.push_str(&format!("\n---\n\n{}\n", readme.text));
+        }
+
+        let entry = IndexEntry::new(
+            PROJECTS_SOURCE.to_owned(),
+            dir.to_string_lossy().to_string(),
+            name,
+            body,
+        )
+        .with_metadata(KIND, PROJECT_KIND);
+        match &readme {
+            Some(readme) => entry.with_decoded_text(readme),
+            None => entry,
+        }
+    }
+}
@@ -0,0 +1,691 @@
+use ignore::WalkBuilder;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+use crate::{
+    config::PathList,
+    model::{
+        decoded_text::DecodedText,
+        file_watcher::FileWatcher,
+        fulltext_index::{AUTHOR, BOOK, DATE, KIND, LINE, LOCATION},
+        index_entry::{IndexEntry, IndexPath, IndexPathSender},
+        indexing_error::IndexingError,
+    },
+};
+
+pub(crate) const READING_HIGHLIGHTS_SOURCE: &str = "reading_highlights";
+
+/// The file name that Kindles use to store all highlights, notes and bookmarks
+const KINDLE_CLIPPINGS_FILE_NAME: &str = "My Clippings.txt";
+
+/// The line that separates the clippings in a Kindle clippings file
+const KINDLE_CLIPPING_SEPARATOR: &str = "==========";
+
+/// A single highlight or note, the unit that we index
+struct Highlight {
+    book: String,
+    author: String,
+    kind: &'static str,
+    location: String,
+    date: String,
+    text: String,
+    note: String,
+    /// The line the highlight starts on within the file, if the file is one that can be read in an editor
+    line: Option<usize>,
+}
+
+impl Highlight {
+    fn into_entry(self, path: &Path) -> IndexEntry {
+        // render the highlight as markdown, highlights are shown as quotes with the note below them
+        let mut body = String::new();
+        if !self.location.is_empty() {
+            body.push_str(&format!("*{}*\n\n", self.location));
+        }
+        if !self.text.is_empty() {
+            for line in self.text.lines() {
+                body.push_str(&format!("> {}\n", line));
+            }
+            body.push('\n');
+        }
+        if !self.note.is_empty() {
+            body.push_str(&format!("{}\n", self.note));
+        }
+
+        IndexEntry::new(
+            READING_HIGHLIGHTS_SOURCE.to_owned(),
+            path.to_string_lossy().to_string(),
+            self.book.clone(),
+            body,
+        )
+        .with_metadata(KIND, self.kind)
+        .with_metadata(BOOK, self.book)
+        .with_metadata(AUTHOR, self.author)
+        .with_metadata(LOCATION, self.location)
+        .with_metadata(DATE, self.date)
+        .with_metadata(LINE, self.line.map(|l| l.to_string()).unwrap_or_default())
+    }
+}
+
+/// The subset of Lua values that KOReader writes to its metadata files
+#[derive(Debug)]
+enum LuaValue {
+    Nil,
+    /// None of the fields that we index are booleans, so only the type is kept
+    Bool,
+    Number(String),
+    String(String),
+    Table(Vec<(LuaValue, LuaValue)>),
+}
+
+impl LuaValue {
+    /// Looks up a string keyed entry in a table
+    fn get(&self, key: &str) -> Option<&LuaValue> {
+        match self {
+            LuaValue::Table(entries) => entries
+                .iter()
+                .find(|(k, _)| matches!(k, LuaValue::String(k) if k == key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Returns the values of a table in order, ignoring the keys
+    fn values(&self) -> impl Iterator<Item = &LuaValue> {
+        let entries = match self {
+            LuaValue::Table(entries) => entries.as_slice(),
+            _ => &[],
+        };
+        entries.iter().map(|(_, v)| v)
+    }
+
+    /// Renders strings and numbers as text, anything else is treated as missing
+    fn text(&self) -> Option<String> {
+        match self {
+            LuaValue::String(s) | LuaValue::Number(s) => Some(s.clone()),
+            _ => None,
+        }
+    }
+
+    fn get_text(&self, key: &str) -> String {
+        self.get(key).and_then(|v| v.text()).unwrap_or_default()
+    }
+}
+
+/// A minimal parser for the serialized Lua tables that KOReader writes e.g. `return { ["key"] = "value", }`
+struct LuaTableParser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> LuaTableParser<'a> {
+    fn parse(input: &'a str) -> Option<LuaValue> {
+        let mut parser = Self { input, pos: 0 };
+        parser.skip_whitespace_and_comments();
+        if parser.rest().starts_with("return") {
+            parser.pos += "return".len();
+        }
+        parser.value()
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn skip_whitespace_and_comments(&mut self) {
+        loop {
+            let trimmed = self.rest().trim_start();
+            self.pos = self.input.len() - trimmed.len();
+            if trimmed.starts_with("--") {
+                self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else {
+                return;
+            }
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Option<()> {
+        self.skip_whitespace_and_comments();
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    fn value(&mut self) -> Option<LuaValue> {
+        self.skip_whitespace_and_comments();
+        match self.peek()? {
+            '{' => self.table(),
+            '"' | '\'' => self.string().map(LuaValue::String),
+            _ => {
+                let word: String = self
+                    .rest()
+                    .chars()
+                    .take_while(|c| c.is_alphanumeric() || matches!(c, '.' | '-' | '+' | '_'))
+                    .collect();
+                self.pos += word.len();
+                match word.as_str() {
+                    "" => None,
+                    "nil" => Some(LuaValue::Nil),
+                    "true" | "false" => Some(LuaValue::Bool),
+                    _ => Some(LuaValue::Number(word)),
+                }
+            }
+        }
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let quote = self.peek()?;
+        self.pos += 1;
+        let mut value = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                c if c == quote => {
+                    self.pos += i + 1;
+                    return Some(value);
+                }
+                '\\' => match chars.next()?.1 {
+                    'n' => value.push('\n'),
+                    't' => value.push('\t'),
+                    'r' => value.push('\r'),
+                    // an escaped line break continues the string on the next line
+                    '\n' => value.push('\n'),
+                    escaped => value.push(escaped),
+                },
+                c => value.push(c),
+            }
+        }
+        None
+    }
+
+    fn table(&mut self) -> Option<LuaValue> {
+        self.expect('{')?;
+        let mut entries = Vec::new();
+        let mut next_index = 1;
+        loop {
+            self.skip_whitespace_and_comments();
+            match self.peek()? {
+                '}' => {
+                    self.pos += 1;
+                    return Some(LuaValue::Table(entries));
+                }
+                ',' | ';' => self.pos += 1,
+                '[' => {
+                    self.pos += 1;
+                    let key = self.value()?;
+                    self.expect(']')?;
+                    self.expect('=')?;
+                    entries.push((key, self.value()?));
+                }
+                c if c.is_alphabetic() || c == '_' => {
+                    // either a bare identifier key or a positional value like `true`
+                    let start = self.pos;
+                    let name: String = self
+                        .rest()
+                        .chars()
+                        .take_while(|c| c.is_alphanumeric() || *c == '_')
+                        .collect();
+                    self.pos += name.len();
+                    if self.expect('=').is_some() {
+                        entries.push((LuaValue::String(name), self.value()?));
+                    } else {
+                        self.pos = start;
+                        entries.push((LuaValue::Number(next_index.to_string()), self.value()?));
+                        next_index += 1;
+                    }
+                }
+                _ => {
+                    entries.push((LuaValue::Number(next_index.to_string()), self.value()?));
+                    next_index += 1;
+                }
+            }
+        }
+    }
+}
+
+pub(crate) struct ReadingHighlights {
+    paths: PathList,
+}
+
+impl ReadingHighlights {
+    /// Creates a new ReadingHighlights object that holds the paths to search for highlight files.
+    ///
+    /// Tildes in the config are expanded on construction.
+    pub(crate) fn new(path_list: &PathList) -> Self {
+        let paths = path_list
+            .iter()
+            // expand tildes into absolute paths
+            .map(|p| PathBuf::from(shellexpand::tilde(&p.to_string_lossy()).into_owned()))
+            .collect();
+
+        ReadingHighlights { paths }
+    }
+
+    /// Checks if this is a KOReader metadata file e.g. `Book.sdr/metadata.epub.lua`
+    fn is_koreader_metadata(path: &Path) -> bool {
+        let in_sdr_dir = path
+            .parent()
+            .and_then(|p| p.extension())
+            .map(|e| e == "sdr")
+            .unwrap_or(false);
+        let file_name = path
+            .file_name()
+            .map(|f| f.to_string_lossy())
+            .unwrap_or_default();
+
+        in_sdr_dir && file_name.starts_with("metadata.") && file_name.ends_with(".lua")
+    }
+
+    /// Collects all of the entries and sends them to the indexer.
+    ///
+    /// This method does this in a separate thread.
+    pub(crate) fn collect_entries(&self, sender: IndexPathSender) {
+        for dir in &self.paths {
+            // hidden files are included since some readers keep their metadata in dot directories
+            for result in WalkBuilder::new(dir).hidden(false).build() {
+                match result {
+                    Err(e) => {
+                        // TODO collect these errors so the user can see what is not being indexed properly
+                        warn!("could not open path: {}", e)
+                    }
+
+                    Ok(entry) => {
+                        if !entry.file_type().map(|e| e.is_file()).unwrap_or(false) {
+                            continue;
+                        }
+
+                        let path = entry.path().to_path_buf();
+                        let index_path =
+                            if path.file_name() == Some(KINDLE_CLIPPINGS_FILE_NAME.as_ref()) {
+                                IndexPath::KindleClippings(path)
+                            } else if Self::is_koreader_metadata(&path) {
+                                IndexPath::KoreaderMetadata(path)
+                            } else {
+                                continue;
+                            };
+
+                        debug!("sending path {}...", index_path);
+                        if sender.send(index_path).is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+        // once we are done, close the channel
+        drop(sender);
+    }
+
+    /// Finds the paths to re-index after a change to `path` that the file watcher reported
+    pub(crate) fn index_paths_for_change(&self, path: &Path) -> Vec<IndexPath> {
+        let Some(root) = self.paths.iter().find(|root| path.starts_with(root)) else {
+            return vec![];
+        };
+        if !path.is_file() || FileWatcher::is_ignored(root, path, true) {
+            return vec![];
+        }
+
+        if path.file_name() == Some(KINDLE_CLIPPINGS_FILE_NAME.as_ref()) {
+            vec![IndexPath::KindleClippings(path.to_path_buf())]
+        } else if Self::is_koreader_metadata(path) {
+            vec![IndexPath::KoreaderMetadata(path.to_path_buf())]
+        } else {
+            vec![]
+        }
+    }
+
+    /// Splits a Kindle title line like `Book Title (Author Name)` into the title and author
+    fn split_kindle_title(line: &str) -> (String, String) {
+        let line = line.trim_start_matches('\u{feff}').trim();
+        match (line.ends_with(')'), line.rfind(" (")) {
+            (true, Some(start)) => (
+                line[..start].trim().to_owned(),
+                line[start + 2..line.len() - 1].trim().to_owned(),
+            ),
+            _ => (line.to_owned(), String::new()),
+        }
+    }
+
+    /// Parses a Kindle info line like `- Your Highlight on page 12 | Location 170-172 | Added on Monday, ...`
+    fn parse_kindle_info(line: &str) -> (&'static str, String, String) {
+        let mut kind = "highlight";
+        let mut locations = Vec::new();
+        let mut date = String::new();
+
+        for (i, part) in line.trim_start_matches('-').split('|').enumerate() {
+            let part = part.trim();
+            if let Some(added) = part.strip_prefix("Added on") {
+                date = added.trim().to_owned();
+                continue;
+            }
+
+            let part = if i == 0 {
+                let lower = part.to_lowercase();
+                if lower.contains("your note") {
+                    kind = "note";
+                } else if lower.contains("your bookmark") {
+                    kind = "bookmark";
+                }
+                // drop the `Your Highlight on` prefix, keeping e.g. `page 12`
+                ["page", "Page", "location", "Location"]
+                    .iter()
+                    .find_map(|marker| part.find(marker).map(|i| &part[i..]))
+                    .unwrap_or_default()
+            } else {
+                part
+            };
+
+            if !part.is_empty() {
+                locations.push(part.to_owned());
+            }
+        }
+
+        (kind, locations.join(", "), date)
+    }
+
+    /// Creates one entry per highlight or note in a Kindle `My Clippings.txt` file
+    pub(crate) fn convert_kindle_clippings_to_entries(
+        path: &Path,
+        max_bytes: Option<u64>,
+    ) -> Result<Vec<IndexEntry>, IndexingError> {
+        debug!(
+            "attempting to convert {} to entries...",
+            path.to_string_lossy()
+        );
+
+        // older Kindles write the clippings with a BOM, others in the encoding of the device
+        let decoded = DecodedText::read(path, max_bytes)?;
+        let contents = &decoded.text;
+
+        let mut highlights: Vec<Highlight> = Vec::new();
+        let mut clipping_line = 1;
+        for clipping in contents.split(KINDLE_CLIPPING_SEPARATOR) {
+            // the title line of the clipping, after the newline that follows the separator
+            let line = clipping_line
+                + clipping[..clipping.len() - clipping.trim_start().len()]
+                    .matches('\n')
+                    .count();
+            clipping_line += clipping.matches('\n').count();
+
+            let mut lines = clipping.trim().lines();
+            let (Some(title_line), Some(info_line)) = (lines.next(), lines.next()) else {
+                continue;
+            };
+
+            let (book, author) = Self::split_kindle_title(title_line);
+            let (kind, location, date) = Self::parse_kindle_info(info_line);
+            let text = lines.collect::<Vec<&str>>().join("\n").trim().to_owned();
+
+            // bookmarks have no text so there is nothing to search for
+            if text.is_empty() {
+                continue;
+            }
+
+            // Kindles append a new clipping whenever a highlight is extended, skip exact duplicates
+            if highlights
+                .iter()
+                .any(|h| h.book == book && h.location == location && h.text == text)
+            {
+                continue;
+            }
+
+            let (text, note) = if kind == "note" {
+                (String::new(), text)
+            } else {
+                (text, String::new())
+            };
+
+            highlights.push(Highlight {
+                book,
+                author,
+                kind,
+                location,
+                date,
+                text,
+                note,
+                line: Some(line),
+            });
+        }
+
+        Ok(highlights
+            .into_iter()
+            .map(|h| h.into_entry(path).with_decoded_text(&decoded))
+            .collect())
+    }
+
+    /// Creates one entry per highlight or note in a KOReader `metadata.*.lua` file
+    ///
+    /// Both the current `annotations` table and the older `highlight` table are supported.
+    pub(crate) fn convert_koreader_metadata_to_entries(
+        path: &Path,
+    ) -> Result<Vec<IndexEntry>, IndexingError> {
+        debug!(
+            "attempting to convert {} to entries...",
+            path.to_string_lossy()
+        );
+
+        let decoded = DecodedText::read(path, None)?;
+        let Some(metadata) = LuaTableParser::parse(&decoded.text) else {
+            return Err(IndexingError::Parse(
+                "not a valid KOReader metadata table".to_owned(),
+            ));
+        };
+
+        // fall back to the name of the book file that the sdr directory belongs to
+        let doc_props = metadata.get("doc_props");
+        let book = doc_props
+            .map(|p| p.get_text("title"))
+            .filter(|t| !t.is_empty())
+            .unwrap_or_else(|| {
+                path.parent()
+                    .and_then(|p| p.file_stem())
+                    .map(|f| f.to_string_lossy().to_string())
+                    .unwrap_or("UNKNOWN_TITLE".to_string())
+            });
+        // multiple authors are separated by newlines
+        let author = doc_props
+            .map(|p| p.get_text("authors").replace('\n', ", "))
+            .unwrap_or_default();
+
+        let annotations: Vec<&LuaValue> = match metadata.get("annotations") {
+            Some(annotations) => annotations.values().collect(),
+            // older versions group highlights by page
+            None => metadata
+                .get("highlight")
+                .map(|pages| pages.values().flat_map(|page| page.values()).collect())
+                .unwrap_or_default(),
+        };
+
+        Ok(annotations
+            .into_iter()
+            .filter_map(|annotation| {
+                let text = annotation.get_text("text").trim().to_owned();
+                let note = annotation.get_text("note").trim().to_owned();
+                if text.is_empty() && note.is_empty() {
+                    return None;
+                }
+
+                let page = match annotation.get("pageno").or(annotation.get("page")) {
+                    Some(LuaValue::Number(page)) => format!("page {}", page),
+                    _ => String::new(),
+                };
+                let location = [annotation.get_text("chapter"), page]
+                    .into_iter()
+                    .filter(|l| !l.is_empty())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+
+                Some(Highlight {
+                    book: book.clone(),
+                    author: author.clone(),
+                    kind: if note.is_empty() { "highlight" } else { "note" },
+                    location,
+                    date: annotation.get_text("datetime"),
+                    text,
+                    note,
+                    line: None,
+                })
+            })
+            .map(|h| h.into_entry(path).with_decoded_text(&decoded))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// Writes `contents` to `file_name` in a fresh directory, which is removed once the returned guard is dropped
+    fn write_file(file_name: &str, contents: &str) -> (TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(file_name);
+        fs::write(&path, contents).unwrap();
+        (dir, path)
+    }
+
+    fn metadata<'a>(entry: &'a IndexEntry, field: &str) -> Option<&'a str> {
+        entry
+            .metadata()
+            .iter()
+            .find(|(f, _)| *f == field)
+            .map(|(_, v)| v.as_str())
+    }
+
+    #[test]
+    fn splits_kindle_titles() {
+        assert_eq!(
+            ReadingHighlights::split_kindle_title("\u{feff}Dune (Herbert, Frank)"),
+            ("Dune".to_owned(), "Herbert, Frank".to_owned())
+        );
+        assert_eq!(
+            ReadingHighlights::split_kindle_title("Notes (draft) v2"),
+            ("Notes (draft) v2".to_owned(), String::new())
+        );
+    }
+
+    #[test]
+    fn parses_kindle_info_lines() {
+        assert_eq!(
+            ReadingHighlights::parse_kindle_info(
+                "- Your Highlight on page 12 | Location 170-172 | Added on Monday, 1 January 2024 10:00:00"
+            ),
+            (
+                "highlight",
+                "page 12, Location 170-172".to_owned(),
+                "Monday, 1 January 2024 10:00:00".to_owned()
+            )
+        );
+        assert_eq!(
+            ReadingHighlights::parse_kindle_info("- Your Note on Location 180 | Added on Tuesday")
+                .0,
+            "note"
+        );
+    }
+
+    #[test]
+    fn converts_kindle_clippings() {
+        let (_dir, path) = write_file(
+            KINDLE_CLIPPINGS_FILE_NAME,
+            "Dune (Frank Herbert)\n\
+             - Your Highlight on Location 10-12 | Added on Monday\n\
+             \n\
+             Fear is the mind-killer.\n\
+             ==========\n\
+             Dune (Frank Herbert)\n\
+             - Your Highlight on Location 10-12 | Added on Monday\n\
+             \n\
+             Fear is the mind-killer.\n\
+             ==========\n\
+             Dune (Frank Herbert)\n\
+             - Your Bookmark on Location 20 | Added on Monday\n\
+             \n\
+             \n\
+             ==========\n\
+             Dune (Frank Herbert)\n\
+             - Your Note on Location 12 | Added on Tuesday\n\
+             \n\
+             the litany\n\
+             ==========\n",
+        );
+
+        let entries = ReadingHighlights::convert_kindle_clippings_to_entries(&path, None).unwrap();
+        // the duplicate highlight and the bookmark are skipped
+        assert_eq!(entries.len(), 2);
+
+        assert_eq!(entries[0].title(), "Dune");
+        assert_eq!(metadata(&entries[0], AUTHOR), Some("Frank Herbert"));
+        assert_eq!(metadata(&entries[0], KIND), Some("highlight"));
+        assert_eq!(metadata(&entries[0], LOCATION), Some("Location 10-12"));
+        assert_eq!(metadata(&entries[0], LINE), Some("1"));
+        assert!(entries[0].body().contains("> Fear is the mind-killer."));
+
+        assert_eq!(metadata(&entries[1], KIND), Some("note"));
+        assert_eq!(metadata(&entries[1], LINE), Some("16"));
+        assert!(entries[1].body().contains("the litany"));
+    }
+
+    #[test]
+    fn parses_lua_tables() {
+        let value = LuaTableParser::parse(
+            "-- we can read Lua syntax here!\n\
+             return {\n\
+                 [\"title\"] = \"A \\\"quoted\\\" title\",\n\
+                 [\"pages\"] = 320,\n\
+                 [\"done\"] = true,\n\
+                 [\"list\"] = { [1] = \"one\", [2] = \"two\", },\n\
+             }",
+        )
+        .unwrap();
+
+        assert_eq!(value.get_text("title"), "A \"quoted\" title");
+        assert_eq!(value.get_text("pages"), "320");
+        assert!(matches!(value.get("done"), Some(LuaValue::Bool)));
+        let list: Vec<String> = value
+            .get("list")
+            .unwrap()
+            .values()
+            .filter_map(|v| v.text())
+            .collect();
+        assert_eq!(list, ["one", "two"]);
+        assert!(LuaTableParser::parse("return { [\"open\"] = ").is_none());
+    }
+
+    #[test]
+    fn converts_koreader_metadata() {
+        let (_dir, path) = write_file(
+            "metadata.epub.lua",
+            "return {\n\
+                 [\"annotations\"] = {\n\
+                     [1] = { [\"chapter\"] = \"One\", [\"pageno\"] = 5, [\"text\"] = \"first\", [\"datetime\"] = \"2024-01-01 10:00:00\", },\n\
+                     [2] = { [\"pageno\"] = 9, [\"text\"] = \"second\", [\"note\"] = \"a note\", },\n\
+                     [3] = { [\"pageno\"] = 12, },\n\
+                 },\n\
+                 [\"doc_props\"] = { [\"title\"] = \"Dune\", [\"authors\"] = \"Frank Herbert\\\nBrian Herbert\", },\n\
+             }",
+        );
+
+        let entries = ReadingHighlights::convert_koreader_metadata_to_entries(&path).unwrap();
+        // the annotation without text or note is skipped
+        assert_eq!(entries.len(), 2);
+
+        assert_eq!(entries[0].title(), "Dune");
+        assert_eq!(
+            metadata(&entries[0], AUTHOR),
+            Some("Frank Herbert, Brian Herbert")
+        );
+        assert_eq!(metadata(&entries[0], KIND), Some("highlight"));
+        assert_eq!(metadata(&entries[0], LOCATION), Some("One, page 5"));
+        assert_eq!(metadata(&entries[0], DATE), Some("2024-01-01 10:00:00"));
+
+        assert_eq!(metadata(&entries[1], KIND), Some("note"));
+        assert_eq!(metadata(&entries[1], LOCATION), Some("page 9"));
+    }
+}
@@ -5,12 +5,13 @@ use tracing::{debug, warn};
 
 use crate::{
     config::PathList,
-    model::index_entry::{IndexEntry, IndexPath, IndexPathSender},
-};
-use std::{
-    fs,
-    path::{Path, PathBuf},
+    model::{
+        decoded_text::DecodedText,
+        index_entry::{IndexEntry, IndexPath, IndexPathSender},
+        indexing_error::IndexingError,
+    },
 };
+use std::path::{Path, PathBuf};
 
 pub(crate) const WEB_SCRAPBOOK_FILES_SOURCE: &str = "web_scrapbook_files";
 
@@ -54,9 +55,12 @@ impl WebScrapbookFiles {
                             // TODO check the file type here
                             // if this is a file, send it to the fulltext index to check if it is already indexed and up to date
                             debug!("sending path {}...", web_scrapbook_index.to_string_lossy());
-                            sender
+                            if sender
                                 .send(IndexPath::WebScrapBookFile(web_scrapbook_index))
-                                .expect("should be able to send new entries to index");
+                                .is_err()
+                            {
+                                return;
+                            }
                         }
                     }
                 }
@@ -66,15 +70,35 @@ impl WebScrapbookFiles {
         drop(sender);
     }
 
-    pub(crate) fn convert_path_to_entry(path: &Path) -> IndexEntry {
+    /// Finds the paths to re-index after a change to `path` that the file watcher reported
+    ///
+    /// Any change inside an archived page's directory e.g. to one of its resources re-indexes its `index.html`.
+    pub(crate) fn index_paths_for_change(&self, path: &Path) -> Vec<IndexPath> {
+        for root in &self.paths {
+            let Ok(relative) = path.strip_prefix(root) else {
+                continue;
+            };
+            if let Some(item_dir) = relative.components().next() {
+                let web_scrapbook_index = root.join(item_dir).join("index.html");
+                if web_scrapbook_index.is_file() {
+                    return vec![IndexPath::WebScrapBookFile(web_scrapbook_index)];
+                }
+            }
+        }
+        vec![]
+    }
+
+    pub(crate) fn convert_path_to_entry(
+        path: &Path,
+        max_bytes: Option<u64>,
+    ) -> Result<IndexEntry, IndexingError> {
         debug!(
             "attempting to convert {} to entry...",
             path.to_string_lossy()
         );
 
-        // TODO properly handle non UTF-8 file contents
-        // TODO handle very large files efficiently, maybe switch to a streaming library
-        let body = fs::read_to_string(&path).unwrap_or_default();
+        let decoded = DecodedText::read_markup(path, max_bytes)?;
+        let body = decoded.text.clone();
 
         // Extract title according to priority rules
         let title = Self::extract_title(&body);
@@ -98,12 +122,13 @@ impl WebScrapbookFiles {
         // TODO run this through the readability library
         let markdown_body = doc.md(None);
 
-        IndexEntry::new(
+        Ok(IndexEntry::new(
             WEB_SCRAPBOOK_FILES_SOURCE.to_owned(),
             path.to_string_lossy().to_string(),
             title,
             markdown_body.to_string(),
         )
+        .with_decoded_text(&decoded))
     }
 
     fn extract_title(body: &str) -> String {
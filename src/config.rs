@@ -1,15 +1,27 @@
 use confique::Config;
 use confique::toml;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
 use std::fs;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tracing::info;
 
-use crate::ui::retsyn_app::PROJECT_DIRS;
+use crate::collectors::{
+    aichat_session_files::AICHAT_SESSION_FILES_SOURCE, code_symbols::CODE_SYMBOLS_SOURCE,
+    image_files::IMAGE_FILES_SOURCE, markdown_files::MARKDOWN_FILES_SOURCE,
+    music_files::MUSIC_FILES_SOURCE, projects::PROJECTS_SOURCE,
+    reading_highlights::READING_HIGHLIGHTS_SOURCE, web_scrapbook_files::WEB_SCRAPBOOK_FILES_SOURCE,
+};
+use crate::model::compaction::IndexMergePolicy;
+use crate::model::size_limits::{LargeFilePolicy, SizeLimit, SizeLimits};
+use crate::model::volume::{self, VolumeLabels};
+use crate::profile::Profile;
 
 pub(crate) type PathList = Vec<PathBuf>;
 
-#[derive(Config, Clone)]
+#[derive(Config, Clone, Serialize)]
 pub struct Conf {
     #[config(default = ["~/.config/aichat/sessions"])]
     pub(crate) aichat_session_files: PathList,
@@ -20,12 +32,168 @@ pub struct Conf {
 
     #[config(default = ["~/WebScrapbook"])]
     pub(crate) web_scrapbook_files: PathList,
+
+    /// Directories containing photos whose EXIF, XMP and IPTC metadata should be indexed
+    #[config(default = ["~/Pictures"])]
+    pub(crate) image_files: PathList,
+
+    /// Directories containing music whose audio tags should be indexed
+    #[config(default = ["~/Music"])]
+    pub(crate) music_files: PathList,
+
+    /// Also index one document per album that groups its tracks
+    #[config(default = false)]
+    pub(crate) music_album_documents: bool,
+
+    /// The command that tracks and albums are handed to when they are opened, the path is appended as the last argument
+    #[config(default = "xdg-open")]
+    pub(crate) music_player_command: String,
+
+    /// Files or directories containing Kindle `My Clippings.txt` files and KOReader `.sdr` directories
+    #[config(default = ["~/Books"])]
+    pub(crate) reading_highlight_files: PathList,
+
+    /// Project directories whose source code symbol definitions should be indexed
+    #[config(default = ["~/Projects"])]
+    pub(crate) code_symbol_files: PathList,
+
+    /// Directories containing projects, each project up to two levels down is indexed as a document of its own
+    #[config(default = ["~/Projects"])]
+    pub(crate) project_dirs: PathList,
+
+    /// Names for drives that have no filesystem UUID to tell them apart e.g. `external = "/media/backup"`, keyed by
+    /// name with the directory the drive is mounted at. The files of a drive are recognized wherever it is mounted, and
+    /// stay searchable while it is not.
+    #[config(default = {})]
+    pub(crate) volume_labels: VolumeLabels,
+
+    /// Files larger than this many bytes are handled according to `large_file_policy`
+    #[config(default = 10_000_000)]
+    pub(crate) max_file_bytes: u64,
+
+    /// What to do with files above the size limit: "skip" them, "truncate" them to the limit, or "stream" them as a
+    /// series of documents of up to the limit each. Only markdown files can be streamed, other sources truncate
+    /// instead with a warning when the config is loaded, and sources that need the whole file to parse it e.g. aichat
+    /// sessions always skip.
+    #[config(default = "truncate")]
+    pub(crate) large_file_policy: LargeFilePolicy,
+
+    /// Size limits for individual sources that override the two settings above, keyed by source name e.g.
+    /// `markdown_files = { max_bytes = 50_000_000, policy = "stream" }`
+    #[config(default = {})]
+    pub(crate) source_size_limits: HashMap<String, SizeLimit>,
+
+    /// The number of threads converting files into documents, 0 uses one per CPU
+    #[config(default = 0)]
+    pub(crate) conversion_workers: usize,
+
+    /// The memory the index writer may use to buffer documents before flushing them to disk, in bytes
+    #[config(default = 50_000_000)]
+    pub(crate) writer_heap_bytes: usize,
+
+    /// The number of threads the index writer uses, 0 picks one per CPU up to what `writer_heap_bytes` allows. Each
+    /// thread needs at least 15 MB of the writer memory, a larger number is lowered to fit.
+    #[config(default = 0)]
+    pub(crate) writer_threads: usize,
+
+    /// Run indexing at the lowest CPU and IO priority, so it only uses what the rest of the system leaves over
+    #[config(default = true)]
+    pub(crate) low_priority_indexing: bool,
+
+    /// Pause indexing while you are typing searches, and resume once the search box was idle for `idle_after_seconds`
+    #[config(default = false)]
+    pub(crate) index_only_when_idle: bool,
+
+    /// How long the search box has to be idle before indexing resumes, see `index_only_when_idle`
+    #[config(default = 10)]
+    pub(crate) idle_after_seconds: u64,
+
+    /// Commit after this many documents were added, so they become searchable and indexing can resume from there
+    #[config(default = 10_000)]
+    pub(crate) commit_every_documents: usize,
+
+    /// Commit after documents with this many bytes of text were added
+    #[config(default = 100_000_000)]
+    pub(crate) commit_every_bytes: usize,
+
+    /// Commit after this many seconds, if anything was added
+    #[config(default = 6)]
+    pub(crate) commit_every_seconds: u64,
+
+    /// How index segments are merged: "log" merges segments of similar size, "single" also compacts the index into one
+    /// segment, and "none" never merges
+    #[config(default = "log")]
+    pub(crate) merge_policy: IndexMergePolicy,
+
+    /// Merge segments of similar size once there are this many of them
+    #[config(default = 8)]
+    pub(crate) merge_min_segments: usize,
+
+    /// Merge a segment once this share of its documents was deleted, from 0.0 to 1.0
+    #[config(default = 0.5)]
+    pub(crate) merge_deleted_ratio: f32,
+
+    /// Compact the index once indexing is done, merging its segments and deleting the files they no longer use
+    #[config(default = true)]
+    pub(crate) compact_after_indexing: bool,
+
+    /// Where the search index is kept, by default in the cache directory of the profile. The index is built anew in a
+    /// directory that does not hold one yet.
+    pub(crate) index_dir: Option<PathBuf>,
+
+    /// Keep watching the directories above for changes after the initial indexing and index them as they happen
+    #[config(default = true)]
+    pub(crate) watch_files: bool,
+}
+
+/// A root directory of a source that is inside another root, or is the same directory
+///
+/// The files below it are only indexed once. Within a source listing it is redundant, across sources a file that
+/// both of them report is converted by one and records each of them as its source.
+#[derive(Debug, Clone)]
+pub(crate) struct RootOverlap {
+    pub(crate) source: &'static str,
+    pub(crate) root: PathBuf,
+    /// The source that lists `outer_root`, which may be `source` itself
+    pub(crate) outer_source: &'static str,
+    /// The root that contains `root`, both with symlinks resolved
+    pub(crate) outer_root: PathBuf,
+}
+
+impl fmt::Display for RootOverlap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.source != self.outer_source {
+            write!(
+                f,
+                "{} lists {} which overlaps {} of {}, files that both report are indexed once",
+                self.source,
+                self.root.display(),
+                self.outer_root.display(),
+                self.outer_source
+            )
+        } else if self.root == self.outer_root {
+            write!(
+                f,
+                "{} lists {} more than once",
+                self.source,
+                self.root.display()
+            )
+        } else {
+            write!(
+                f,
+                "{} lists {} which is inside {}",
+                self.source,
+                self.root.display(),
+                self.outer_root.display()
+            )
+        }
+    }
 }
 
 impl Conf {
-    /// Get the default config file path
+    /// Get the config file path of the active profile
     pub fn config_path() -> PathBuf {
-        PROJECT_DIRS.config_dir().to_path_buf().join("retsyn.toml")
+        Profile::active().config_path()
     }
 
     /// Check if the config file exists
@@ -36,7 +204,12 @@ impl Conf {
     /// Write the default config template to the config file path if it doesn't exist
     pub fn write_default_config() -> io::Result<PathBuf> {
         let config_path = Self::config_path();
+        Self::write_default_config_to(&config_path)?;
+        Ok(config_path)
+    }
 
+    /// Write the default config template to the given path if it doesn't exist, e.g. for a new profile
+    pub(crate) fn write_default_config_to(config_path: &Path) -> io::Result<()> {
         if config_path.exists() {
             return Err(io::Error::new(
                 io::ErrorKind::AlreadyExists,
@@ -57,35 +230,271 @@ impl Conf {
             "writing default config to: {}...",
             config_path.to_string_lossy()
         );
-        fs::write(&config_path, template)?;
+        fs::write(config_path, template)?;
 
-        Ok(config_path)
+        Ok(())
+    }
+
+    /// The expanded root directories of each source, used to tell a deleted file from a missing root
+    pub(crate) fn source_roots(&self) -> HashMap<&'static str, PathList> {
+        let expand = |path_list: &PathList| -> PathList {
+            path_list
+                .iter()
+                // expand tildes into absolute paths
+                .map(|p| PathBuf::from(shellexpand::tilde(&p.to_string_lossy()).into_owned()))
+                .collect()
+        };
+
+        HashMap::from([
+            (
+                AICHAT_SESSION_FILES_SOURCE,
+                expand(&self.aichat_session_files),
+            ),
+            (MARKDOWN_FILES_SOURCE, expand(&self.markdown_files)),
+            (
+                WEB_SCRAPBOOK_FILES_SOURCE,
+                expand(&self.web_scrapbook_files),
+            ),
+            (IMAGE_FILES_SOURCE, expand(&self.image_files)),
+            (MUSIC_FILES_SOURCE, expand(&self.music_files)),
+            (
+                READING_HIGHLIGHTS_SOURCE,
+                expand(&self.reading_highlight_files),
+            ),
+            (CODE_SYMBOLS_SOURCE, expand(&self.code_symbol_files)),
+            (PROJECTS_SOURCE, expand(&self.project_dirs)),
+        ])
+    }
+
+    /// The roots that are inside another root, once symlinks are resolved, sorted by source
+    ///
+    /// A root inside a root of its own source is reported first. Project roots are only compared with each other,
+    /// projects are directories that no other source reports.
+    pub(crate) fn overlapping_roots(&self) -> Vec<RootOverlap> {
+        let mut roots: Vec<(&'static str, PathBuf)> = self
+            .source_roots()
+            .into_iter()
+            .flat_map(|(source, roots)| {
+                roots
+                    .into_iter()
+                    .map(move |root| (source, volume::canonical_path(&root)))
+            })
+            .collect();
+        // the listing order within a source decides which of two equal roots is reported
+        roots.sort_by_key(|(source, _root)| *source);
+
+        let mut overlaps = Vec::new();
+        for (index, (source, root)) in roots.iter().enumerate() {
+            let overlaps_root =
+                |(outer_index, (outer_source, outer_root)): &(usize, &(&'static str, PathBuf))| {
+                    if source != outer_source
+                        && (*source == PROJECTS_SOURCE || *outer_source == PROJECTS_SOURCE)
+                    {
+                        return false;
+                    }
+                    // a root listed twice is reported once, by its second listing
+                    if root == outer_root {
+                        *outer_index < index
+                    } else {
+                        root.starts_with(outer_root)
+                    }
+                };
+            let outer = roots
+                .iter()
+                .enumerate()
+                .filter(overlaps_root)
+                .min_by_key(|(_, (outer_source, _))| outer_source != source);
+            if let Some((_, (outer_source, outer_root))) = outer {
+                overlaps.push(RootOverlap {
+                    source,
+                    root: root.clone(),
+                    outer_source,
+                    outer_root: outer_root.clone(),
+                });
+            }
+        }
+        overlaps.sort_by(|a, b| (a.source, &a.root).cmp(&(b.source, &b.root)));
+        overlaps
+    }
+
+    /// The sources that are set to stream their large files but cannot, with what they do with them instead
+    ///
+    /// Only markdown files can be streamed. Sources that need the whole file to parse it skip large files, the other
+    /// text sources truncate them. Sources without roots are left out since their limit never applies.
+    pub(crate) fn unstreamable_sources(&self) -> Vec<(&'static str, LargeFilePolicy)> {
+        let size_limits = SizeLimits::new(self);
+        let source_roots = self.source_roots();
+        [
+            (AICHAT_SESSION_FILES_SOURCE, LargeFilePolicy::Skip),
+            (WEB_SCRAPBOOK_FILES_SOURCE, LargeFilePolicy::Truncate),
+            (READING_HIGHLIGHTS_SOURCE, LargeFilePolicy::Truncate),
+            (CODE_SYMBOLS_SOURCE, LargeFilePolicy::Truncate),
+        ]
+        .into_iter()
+        .filter(|(source, _fallback)| {
+            size_limits.for_source(source).policy == LargeFilePolicy::Stream
+                && source_roots
+                    .get(source)
+                    .is_some_and(|roots| !roots.is_empty())
+        })
+        .collect()
     }
 
     /// Save the current configuration to the config file
     pub fn save(&self) -> io::Result<PathBuf> {
         let config_path = Self::config_path();
+        self.save_to(&config_path)?;
+        Ok(config_path)
+    }
 
+    /// Save the current configuration to the given path, replacing what is there
+    pub(crate) fn save_to(&self, config_path: &Path) -> io::Result<()> {
         // Create parent directories if they don't exist
         if let Some(parent) = config_path.parent() {
             fs::create_dir_all(parent)?;
         }
 
-        // Serialize the config to TOML
-        // TODO use confique/serde not this AI generated crap
-        let mut toml_string = String::new();
-        toml_string.push_str("# Retsyn Configuration File\n\n");
-        toml_string.push_str("# Directories containing loose markdown files to index\n");
-        toml_string.push_str("markdown_files = [\n");
-        for path in &self.markdown_files {
-            toml_string.push_str(&format!("  \"{}\",\n", path.display()));
-        }
-        toml_string.push_str("]\n");
+        // every setting is written, the comments of the default config are not kept
+        let settings = ::toml::to_string_pretty(self).map_err(io::Error::other)?;
+        let toml_string = format!("# Retsyn Configuration File\n\n{}", settings);
 
         // Write to file
         // TODO use atomic write for config files
-        fs::write(&config_path, toml_string)?;
+        fs::write(config_path, toml_string)
+    }
+}
 
-        Ok(config_path)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saves_every_setting() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+        let mut config = Conf::builder().load().unwrap();
+        config.markdown_files = vec![PathBuf::from("~/Notes")];
+        config.index_dir = Some(PathBuf::from("/srv/index"));
+        config.volume_labels =
+            VolumeLabels::from([("backup".to_owned(), PathBuf::from("/media/backup"))]);
+        config.large_file_policy = LargeFilePolicy::Stream;
+        config.source_size_limits = HashMap::from([(
+            MARKDOWN_FILES_SOURCE.to_owned(),
+            SizeLimit {
+                max_bytes: 1_000,
+                policy: LargeFilePolicy::Skip,
+            },
+        )]);
+        config.merge_policy = IndexMergePolicy::Single;
+        config.watch_files = false;
+        config.save_to(&config_path).unwrap();
+
+        let saved = Conf::builder().file(&config_path).load().unwrap();
+        assert_eq!(saved.markdown_files, config.markdown_files);
+        assert_eq!(saved.index_dir, config.index_dir);
+        assert_eq!(saved.volume_labels, config.volume_labels);
+        assert_eq!(saved.large_file_policy, LargeFilePolicy::Stream);
+        let limit = saved.source_size_limits[MARKDOWN_FILES_SOURCE];
+        assert_eq!(
+            (limit.max_bytes, limit.policy),
+            (1_000, LargeFilePolicy::Skip)
+        );
+        assert_eq!(saved.merge_policy, IndexMergePolicy::Single);
+        assert!(!saved.watch_files);
+        assert_eq!(saved.commit_every_documents, config.commit_every_documents);
+    }
+
+    #[test]
+    fn finds_sources_that_cannot_stream() {
+        let mut config = Conf::builder().load().unwrap();
+        config.web_scrapbook_files.clear();
+        config.large_file_policy = LargeFilePolicy::Stream;
+        config.source_size_limits = HashMap::from([(
+            READING_HIGHLIGHTS_SOURCE.to_owned(),
+            SizeLimit {
+                max_bytes: 1_000,
+                policy: LargeFilePolicy::Truncate,
+            },
+        )]);
+
+        // markdown files stream, and a source without roots never reads a file
+        assert_eq!(
+            config.unstreamable_sources(),
+            vec![
+                (AICHAT_SESSION_FILES_SOURCE, LargeFilePolicy::Skip),
+                (CODE_SYMBOLS_SOURCE, LargeFilePolicy::Truncate),
+            ]
+        );
+
+        config.large_file_policy = LargeFilePolicy::Truncate;
+        assert!(config.unstreamable_sources().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn finds_roots_inside_other_roots() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        fs::create_dir_all(dir.join("notes/journal")).unwrap();
+        fs::create_dir_all(dir.join("projects")).unwrap();
+        std::os::unix::fs::symlink(dir.join("notes"), dir.join("link")).unwrap();
+        let dir = fs::canonicalize(dir).unwrap();
+
+        let mut config = Conf::builder().load().unwrap();
+        for roots in [
+            &mut config.aichat_session_files,
+            &mut config.web_scrapbook_files,
+            &mut config.image_files,
+            &mut config.music_files,
+            &mut config.reading_highlight_files,
+            &mut config.code_symbol_files,
+        ] {
+            roots.clear();
+        }
+        config.markdown_files = vec![
+            dir.join("notes"),
+            dir.join("notes/journal"),
+            dir.join("link"),
+            dir.join("projects"),
+        ];
+        config.image_files = vec![dir.join("notes/journal")];
+        // projects are directories, no other source reports them
+        config.project_dirs = vec![dir.join("notes")];
+
+        let overlaps: Vec<(&str, PathBuf, &str, PathBuf)> = config
+            .overlapping_roots()
+            .into_iter()
+            .map(|overlap| {
+                let RootOverlap {
+                    source,
+                    root,
+                    outer_source,
+                    outer_root,
+                } = overlap;
+                (source, root, outer_source, outer_root)
+            })
+            .collect();
+        let notes = dir.join("notes");
+        let journal = dir.join("notes/journal");
+        assert_eq!(
+            overlaps,
+            vec![
+                // a root inside a root of another source
+                (
+                    IMAGE_FILES_SOURCE,
+                    journal.clone(),
+                    MARKDOWN_FILES_SOURCE,
+                    notes.clone()
+                ),
+                // the symlink is the same directory listed a second time
+                (
+                    MARKDOWN_FILES_SOURCE,
+                    notes.clone(),
+                    MARKDOWN_FILES_SOURCE,
+                    notes.clone()
+                ),
+                (MARKDOWN_FILES_SOURCE, journal, MARKDOWN_FILES_SOURCE, notes),
+            ]
+        );
     }
 }
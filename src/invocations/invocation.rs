@@ -9,7 +9,6 @@ use std::{
 use time::{OffsetDateTime, format_description};
 
 use crate::invocations::invocation_list::InvocationList;
-use crate::ui::retsyn_app::PROJECT_DIRS;
 
 /// The minimum number of invocations to load for frecency calculations
 pub(crate) const MIN_INVOCATION_NUM: usize = 1000;
@@ -67,7 +66,8 @@ impl Invocation {
         }
     }
 
-    pub(crate) fn cache_file(date: OffsetDateTime) -> PathBuf {
+    /// The invocation log of a day in the cache directory of a profile
+    pub(crate) fn cache_file(cache_dir: &Path, date: OffsetDateTime) -> PathBuf {
         // Format date as YYYY-MM-DD
         let format = format_description::parse("[year]-[month]-[day]")
             .expect("cache file timestamp format to be vaild");
@@ -79,7 +79,7 @@ impl Invocation {
             "{INVOCATION_FILE_PREFIX}{}{INVOCATION_FILE_SUFFIX}",
             date_str
         );
-        PROJECT_DIRS.cache_dir().join(log_filename)
+        cache_dir.join(log_filename)
         // TODO need to only return valid files
     }
 
@@ -3,13 +3,15 @@ use crate::{
         Action, INVOCATION_FILE_PREFIX, Invocation, MAX_INVOCATION_RETENTION, MIN_INVOCATION_NUM,
     },
     model::search_result::SearchResult,
-    ui::retsyn_app::PROJECT_DIRS,
 };
+use atomicwrites::{AtomicFile, OverwriteBehavior::AllowOverwrite};
 use color_eyre::Result;
 use std::{
     cmp::Reverse,
+    collections::HashMap,
     fs::{self, DirEntry, File},
     ops::{Deref, DerefMut},
+    path::Path,
 };
 use tracing::warn;
 
@@ -67,9 +69,19 @@ impl InvocationList {
         self.add_invocation(action, query, &item.path, &item.title, "");
     }
 
-    fn cache_files() -> Result<impl Iterator<Item = DirEntry>> {
-        let cache_files = PROJECT_DIRS.cache_dir();
-        Ok(fs::read_dir(cache_files)?
+    /// The last time each path had an action invoked on it, in seconds since the epoch
+    pub(crate) fn last_invoked_by_path(&self) -> HashMap<String, u64> {
+        let mut last_invoked: HashMap<String, u64> = HashMap::new();
+        for invocation in self.invocations.iter().filter(|i| !i.path.is_empty()) {
+            let timestamp = last_invoked.entry(invocation.path.clone()).or_default();
+            *timestamp = (*timestamp).max(invocation.timestamp);
+        }
+        last_invoked
+    }
+
+    /// The invocation logs in the cache directory of a profile
+    fn cache_files(cache_dir: &Path) -> Result<impl Iterator<Item = DirEntry>> {
+        Ok(fs::read_dir(cache_dir)?
             .filter_map(Result::ok)
             .filter(|entry| match entry.metadata() {
                 Ok(metadata) => {
@@ -83,9 +95,9 @@ impl InvocationList {
             }))
     }
 
-    pub(crate) fn load_from_cache() -> Result<InvocationList> {
+    pub(crate) fn load_from_cache(cache_dir: &Path) -> Result<InvocationList> {
         // return all files in an iterator
-        let mut cache_files = Self::cache_files()?.collect::<Vec<DirEntry>>();
+        let mut cache_files = Self::cache_files(cache_dir)?.collect::<Vec<DirEntry>>();
 
         // sort the files
         cache_files.sort_by_cached_key(|i| Reverse(i.file_name()));
@@ -139,6 +151,46 @@ impl InvocationList {
 
         Ok(invocations)
     }
+
+    /// Points the invocations of moved items at their new paths so their history stays attached
+    ///
+    /// `renames` maps old paths to new ones. Only the cache files that mention an old path are rewritten. Returns
+    /// the number of invocations that were moved.
+    pub(crate) fn rename_paths(
+        cache_dir: &Path,
+        renames: &HashMap<String, String>,
+    ) -> Result<usize> {
+        let mut renamed = 0;
+
+        for cache_file in Self::cache_files(cache_dir)? {
+            let mut rdr = csv::Reader::from_reader(File::open(cache_file.path())?);
+            let mut invocations = rdr
+                .deserialize()
+                .collect::<Result<Vec<Invocation>, csv::Error>>()?;
+
+            let mut changed = false;
+            for invocation in &mut invocations {
+                if let Some(new_path) = renames.get(&invocation.path) {
+                    invocation.path = new_path.clone();
+                    changed = true;
+                    renamed += 1;
+                }
+            }
+
+            if changed {
+                AtomicFile::new(cache_file.path(), AllowOverwrite).write(|f| {
+                    let mut wtr = csv::Writer::from_writer(f);
+                    for invocation in &invocations {
+                        wtr.serialize(invocation)?;
+                    }
+                    wtr.flush()?;
+                    Ok::<(), csv::Error>(())
+                })?;
+            }
+        }
+
+        Ok(renamed)
+    }
 }
 
 impl<'a> IntoIterator for &'a InvocationList {
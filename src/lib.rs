@@ -4,4 +4,5 @@ pub mod config;
 pub mod invocations;
 pub mod messages;
 pub mod model;
+pub mod profile;
 pub mod ui;
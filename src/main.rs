@@ -3,7 +3,11 @@ use color_eyre::eyre::{self, Result};
 use directories::ProjectDirs;
 use eframe::egui;
 use retsyn::{
-    cli::Cli, config::Conf, model::fulltext_index::FulltextIndex, ui::retsyn_app::RetsynApp,
+    cli::Cli,
+    config::Conf,
+    model::{fulltext_index::FulltextIndex, reindex_target::ReindexTarget},
+    profile::Profile,
+    ui::retsyn_app::RetsynApp,
 };
 use std::process::exit;
 use tracing::info;
@@ -57,6 +61,11 @@ fn main() -> Result<()> {
         eprintln!("Failed to setup tracing: {}", e);
     }
 
+    // Handle --profile flag, everything below reads and writes the files of the active profile
+    if let Some(name) = &cli.profile {
+        Profile::new(name).map_err(|e| eyre::eyre!(e))?.activate();
+    }
+
     // Handle --default-config flag
     if cli.default_config {
         #[expect(
@@ -79,13 +88,18 @@ fn main() -> Result<()> {
 
     // Handle --clear-index flag
     if cli.clear_index {
+        // the config may move the index out of the cache directory
+        let config = Profile::active()
+            .load_config()
+            .map_err(|e| eyre::eyre!("could not load config: {}", e))?;
+
         #[expect(
             clippy::print_stderr,
             clippy::print_stdout,
             reason = "We want to notify the user on the CLI directly rather than trace these actions."
         )]
         // TODO unify error handling and return error to be displayed later
-        match FulltextIndex::clear_index() {
+        match FulltextIndex::clear_index(&config) {
             Ok(()) => {
                 println!("Search index cleared successfully");
                 exit(0);
@@ -97,6 +111,80 @@ fn main() -> Result<()> {
         }
     }
 
+    // Handle --stats flag
+    if cli.stats {
+        let config = Profile::active()
+            .load_config()
+            .map_err(|e| eyre::eyre!("could not load config: {}", e))?;
+
+        #[expect(
+            clippy::print_stderr,
+            clippy::print_stdout,
+            reason = "We want to notify the user on the CLI directly rather than trace these actions."
+        )]
+        match FulltextIndex::stats_now(config) {
+            Ok(stats) => {
+                print!("{}", stats);
+                exit(0);
+            }
+            Err(e) => {
+                eprintln!("Error reading index stats: {}", e);
+                exit(1);
+            }
+        }
+    }
+
+    // Handle --compact-index flag
+    if cli.compact_index {
+        let config = Profile::active()
+            .load_config()
+            .map_err(|e| eyre::eyre!("could not load config: {}", e))?;
+
+        #[expect(
+            clippy::print_stderr,
+            clippy::print_stdout,
+            reason = "We want to notify the user on the CLI directly rather than trace these actions."
+        )]
+        match FulltextIndex::compact_now(config) {
+            Ok(report) => {
+                println!("Compacted the search index: {}", report);
+                exit(0);
+            }
+            Err(e) => {
+                eprintln!("Error compacting index: {}", e);
+                exit(1);
+            }
+        }
+    }
+
+    // Handle --reindex-source and --reindex-path flags, clap rejects passing both
+    let reindex_target = match (&cli.reindex_source, &cli.reindex_path) {
+        (Some(source), _) => Some(ReindexTarget::source(source).map_err(|e| eyre::eyre!(e))?),
+        (None, Some(path)) => Some(ReindexTarget::subtree(path)?),
+        (None, None) => None,
+    };
+    if let Some(target) = reindex_target {
+        let config = Profile::active()
+            .load_config()
+            .map_err(|e| eyre::eyre!("could not load config: {}", e))?;
+
+        #[expect(
+            clippy::print_stderr,
+            clippy::print_stdout,
+            reason = "We want to notify the user on the CLI directly rather than trace these actions."
+        )]
+        match FulltextIndex::reindex_now(config, target.clone()) {
+            Ok(files_indexed) => {
+                println!("Reindexed {} files for {}", files_indexed, target);
+                exit(0);
+            }
+            Err(e) => {
+                eprintln!("Error reindexing {}: {}", target, e);
+                exit(1);
+            }
+        }
+    }
+
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([800.0, 600.0])
@@ -1,5 +1,21 @@
-// TODO might want to change this to an enum so we can add an index update event
-pub(crate) struct IndexRequest {
+use crate::model::reindex_target::ReindexTarget;
+
+/// A request for the indexing thread, searches go to the search service instead
+pub(crate) enum IndexRequest {
+    /// Delete the documents of a source or directory and convert their files again
+    Reindex(ReindexTarget),
+    /// Merge the segments of the index and delete the files they no longer use
+    Compact,
+    /// Hold indexing until it is resumed
+    Pause,
+    Resume,
+    /// Gather statistics about what is in the index
+    Stats,
+    /// The user typed into the search box, indexing waits for them to stop if it only runs while they are idle
+    SearchActivity,
+}
+
+pub(crate) struct SearchRequest {
     pub(crate) request_id: usize,
     pub(crate) query: String,
     pub(crate) limit: usize,
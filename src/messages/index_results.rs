@@ -1,9 +1,25 @@
-use crate::model::fulltext_index::{IndexStatus, SearchResultsAndErrors};
+use tantivy::TantivyError;
+
+use crate::model::{
+    compaction::CompactionReport,
+    fulltext_index::{IndexStatus, SearchResultsAndErrors},
+    index_stats::IndexStats,
+    indexing_error::IndexingError,
+    resource_governor::PauseReason,
+};
 
 pub(crate) enum IndexResults {
-    // TODO change to a proper error type
-    Error(String),
+    /// An error that stopped indexing, files that fail on their own are recorded in the error ledger instead
+    Error(IndexingError),
     Status(IndexStatus),
+    /// The index committed and searches see the new documents from now on, tagged with the commit's opstamp
+    Committed(tantivy::Opstamp),
+    /// Indexing was put on hold for the given reason, or resumed if there is none
+    Paused(Option<PauseReason>),
+    /// What is in the index, as requested
+    Stats(Result<IndexStats, TantivyError>),
+    /// The index was compacted
+    Compacted(CompactionReport),
     SearchResults {
         request_id: usize,
         opstamp: tantivy::Opstamp,
@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs, io, path::Path};
+use tantivy::{
+    SegmentMeta,
+    index::SegmentId,
+    indexer::{LogMergePolicy, MergePolicy, NoMergePolicy},
+};
+
+use crate::config::Conf;
+
+/// How the segments of the index are merged, while indexing and when the index is compacted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndexMergePolicy {
+    /// Merge segments of similar size once there are enough of them, or once enough of their documents were deleted
+    Log,
+    /// Merge like "log" while indexing, and compact the whole index into a single segment
+    Single,
+    /// Never merge segments, compacting only deletes the files no segment uses anymore
+    None,
+}
+
+impl IndexMergePolicy {
+    /// The name of the policy in the config file
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            IndexMergePolicy::Log => "log",
+            IndexMergePolicy::Single => "single",
+            IndexMergePolicy::None => "none",
+        }
+    }
+}
+
+/// The merge policy the index writer applies in the background after each commit
+pub(crate) fn merge_policy(config: &Conf) -> Box<dyn MergePolicy> {
+    match config.merge_policy {
+        IndexMergePolicy::Log | IndexMergePolicy::Single => Box::new(log_merge_policy(config)),
+        IndexMergePolicy::None => Box::new(NoMergePolicy),
+    }
+}
+
+fn log_merge_policy(config: &Conf) -> LogMergePolicy {
+    let mut log_merge_policy = LogMergePolicy::default();
+    log_merge_policy.set_min_num_segments(config.merge_min_segments);
+    log_merge_policy.set_del_docs_ratio_before_merge(config.merge_deleted_ratio);
+    log_merge_policy
+}
+
+/// The groups of segments that compacting the index merges, each group into a segment of its own
+pub(crate) fn merge_candidates(config: &Conf, segments: &[SegmentMeta]) -> Vec<Vec<SegmentId>> {
+    match config.merge_policy {
+        IndexMergePolicy::Log => log_merge_policy(config)
+            .compute_merge_candidates(segments)
+            .into_iter()
+            .map(|candidate| candidate.0)
+            .collect(),
+        IndexMergePolicy::Single if segments.len() > 1 => {
+            vec![segments.iter().map(SegmentMeta::id).collect()]
+        }
+        IndexMergePolicy::Single | IndexMergePolicy::None => vec![],
+    }
+}
+
+/// The size of all files in the index directory, in bytes
+pub(crate) fn index_size(index_dir: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(index_dir)? {
+        let metadata = entry?.metadata()?;
+        if metadata.is_file() {
+            size += metadata.len();
+        }
+    }
+    Ok(size)
+}
+
+/// What compacting the index did
+#[derive(Debug, Clone)]
+pub struct CompactionReport {
+    pub segments_before: usize,
+    pub segments_after: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+impl CompactionReport {
+    pub fn bytes_reclaimed(&self) -> u64 {
+        self.bytes_before.saturating_sub(self.bytes_after)
+    }
+}
+
+impl fmt::Display for CompactionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} segments down to {}, {:.1} MB reclaimed ({:.1} MB left)",
+            self.segments_before,
+            self.segments_after,
+            self.bytes_reclaimed() as f64 / 1_000_000.0,
+            self.bytes_after as f64 / 1_000_000.0,
+        )
+    }
+}
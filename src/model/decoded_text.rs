@@ -0,0 +1,425 @@
+use chardetng::EncodingDetector;
+use encoding_rs::{Decoder, Encoding, UTF_8, UTF_16BE, UTF_16LE};
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Read},
+    path::Path,
+};
+
+use crate::model::{file_fingerprint::FileFingerprint, indexing_error::IndexingError};
+
+/// How many bytes at the start of a file are searched for a charset declaration or UTF-16 without a BOM
+const SNIFF_LENGTH: usize = 4096;
+
+/// How the encoding of a file was determined, from most to least reliable
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum EncodingSource {
+    ByteOrderMark,
+    /// A `<meta charset>` or XML declaration in the file itself
+    Declared,
+    /// The file decodes as UTF-8 without errors
+    ValidUtf8,
+    /// Statistical detection, `confident` is false if another encoding was just as likely
+    Sniffed {
+        confident: bool,
+    },
+}
+
+impl fmt::Display for EncodingSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodingSource::ByteOrderMark => write!(f, "byte order mark"),
+            EncodingSource::Declared => write!(f, "declared charset"),
+            EncodingSource::ValidUtf8 => write!(f, "valid UTF-8"),
+            EncodingSource::Sniffed { confident: true } => write!(f, "detected"),
+            EncodingSource::Sniffed { confident: false } => write!(f, "guessed"),
+        }
+    }
+}
+
+/// The contents of a text file transcoded to UTF-8, along with the encoding it was read as
+pub(crate) struct DecodedText {
+    pub(crate) text: String,
+    pub(crate) encoding: &'static Encoding,
+    pub(crate) source: EncodingSource,
+    /// Whether some bytes were not valid in the encoding and had to be replaced
+    pub(crate) had_errors: bool,
+    /// Whether only the start of the file was read because it is larger than its size limit
+    pub(crate) truncated: bool,
+    /// The hash of the whole file, `None` if only the start of it was read
+    pub(crate) content_hash: Option<u64>,
+}
+
+impl DecodedText {
+    /// Reads a plain text file, the encoding comes from its BOM or contents
+    ///
+    /// Only the first `max_bytes` are read if given, a character cut in half at the end is dropped.
+    pub(crate) fn read(path: &Path, max_bytes: Option<u64>) -> Result<Self, IndexingError> {
+        let (bytes, truncated) = Self::read_bytes(path, max_bytes)?;
+        Ok(Self {
+            truncated,
+            content_hash: (!truncated).then(|| FileFingerprint::hash_bytes(&bytes)),
+            ..Self::decode(&bytes, false, !truncated)
+        })
+    }
+
+    /// Reads an HTML or XML file, a charset declared in the file is honoured if there is no BOM
+    pub(crate) fn read_markup(path: &Path, max_bytes: Option<u64>) -> Result<Self, IndexingError> {
+        let (bytes, truncated) = Self::read_bytes(path, max_bytes)?;
+        Ok(Self {
+            truncated,
+            content_hash: (!truncated).then(|| FileFingerprint::hash_bytes(&bytes)),
+            ..Self::decode(&bytes, true, !truncated)
+        })
+    }
+
+    /// Reads a plain text file in chunks of about `chunk_bytes` each, so it can be indexed as a series of documents
+    ///
+    /// The encoding is detected from the first chunk. Chunks end on a line break where possible, so lines are not
+    /// split across documents. Each chunk is only read once the one before it was taken, so the chunks are not
+    /// hashed, the file has to be hashed on its own.
+    pub(crate) fn read_chunks(path: &Path, chunk_bytes: u64) -> Result<TextChunks, IndexingError> {
+        let mut file = File::open(path)?;
+        let mut buffer = vec![0; chunk_bytes.max(1) as usize];
+        let read = Self::fill_buffer(&mut file, &mut buffer)?;
+        let (encoding, source) = Self::detect(&buffer[..read], false, read < buffer.len());
+
+        Ok(TextChunks {
+            file,
+            buffer,
+            buffered: Some(read),
+            encoding,
+            source,
+            decoder: encoding.new_decoder(),
+            carry: String::new(),
+            finished: false,
+            yielded: false,
+        })
+    }
+
+    /// Decodes the contents of a file, `complete` is false if the bytes are only the start of it
+    pub(crate) fn decode(bytes: &[u8], markup: bool, complete: bool) -> Self {
+        let (encoding, source) = Self::detect(bytes, markup, complete);
+
+        // the decoder strips the BOM and replaces invalid sequences, so the text is always usable. An incomplete
+        // sequence at the end of a partial read is held back rather than replaced.
+        let mut decoder = encoding.new_decoder();
+        let mut text = String::with_capacity(
+            decoder
+                .max_utf8_buffer_length(bytes.len())
+                .unwrap_or(bytes.len() * 3),
+        );
+        let (_result, _read, had_errors) = decoder.decode_to_string(bytes, &mut text, complete);
+
+        Self {
+            text,
+            encoding,
+            source,
+            had_errors,
+            truncated: false,
+            content_hash: None,
+        }
+    }
+
+    fn read_bytes(path: &Path, max_bytes: Option<u64>) -> io::Result<(Vec<u8>, bool)> {
+        let file = File::open(path)?;
+        let size = file.metadata()?.len();
+        let mut bytes = Vec::new();
+        match max_bytes {
+            Some(max_bytes) if size > max_bytes => {
+                file.take(max_bytes).read_to_end(&mut bytes)?;
+                Ok((bytes, true))
+            }
+            _ => {
+                (&file).read_to_end(&mut bytes)?;
+                Ok((bytes, false))
+            }
+        }
+    }
+
+    /// Reads until the buffer is full or the file ends, returning how many bytes were read
+    fn fill_buffer(file: &mut File, buffer: &mut [u8]) -> io::Result<usize> {
+        let mut filled = 0;
+        while filled < buffer.len() {
+            match file.read(&mut buffer[filled..])? {
+                0 => break,
+                read => filled += read,
+            }
+        }
+        Ok(filled)
+    }
+
+    fn detect(bytes: &[u8], markup: bool, complete: bool) -> (&'static Encoding, EncodingSource) {
+        // a partial read may end in the middle of a character, that does not make it invalid UTF-8
+        let valid_utf8 = match std::str::from_utf8(bytes) {
+            Ok(_) => true,
+            Err(e) => !complete && e.error_len().is_none(),
+        };
+
+        if let Some((encoding, _bom_length)) = Encoding::for_bom(bytes) {
+            (encoding, EncodingSource::ByteOrderMark)
+        } else if let Some(encoding) = markup.then(|| Self::declared_encoding(bytes)).flatten() {
+            (encoding, EncodingSource::Declared)
+        } else if let Some(encoding) = Self::utf16_without_bom(bytes) {
+            // checked before UTF-8 since the zero bytes of UTF-16 are valid UTF-8 as well
+            (encoding, EncodingSource::Sniffed { confident: true })
+        } else if valid_utf8 {
+            (UTF_8, EncodingSource::ValidUtf8)
+        } else {
+            // `guess_assess` reports almost every guess as confident, any statistical guess may be wrong though
+            let mut detector = EncodingDetector::new();
+            detector.feed(bytes, complete);
+            let encoding = detector.guess(None, true);
+            (encoding, EncodingSource::Sniffed { confident: false })
+        }
+    }
+
+    /// The name of the encoding as recorded on the document e.g. `windows-1252`
+    pub(crate) fn encoding_name(&self) -> &'static str {
+        self.encoding.name()
+    }
+
+    /// Describes why the text may be garbled, `None` if the encoding is certain and decoded cleanly
+    pub(crate) fn uncertainty(&self) -> Option<String> {
+        if self.had_errors {
+            Some(format!(
+                "some bytes are not valid {} ({}) and were replaced",
+                self.encoding_name(),
+                self.source
+            ))
+        } else if self.source == (EncodingSource::Sniffed { confident: false }) {
+            Some(format!(
+                "the encoding was guessed as {}, the text may be garbled",
+                self.encoding_name()
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Finds a `charset=` in a meta tag or an `encoding=` in an XML declaration near the start of the file
+    fn declared_encoding(bytes: &[u8]) -> Option<&'static Encoding> {
+        let head =
+            String::from_utf8_lossy(&bytes[..bytes.len().min(SNIFF_LENGTH)]).to_ascii_lowercase();
+        ["charset=", "encoding="].into_iter().find_map(|attribute| {
+            let start = head.find(attribute)? + attribute.len();
+            let label: String = head[start..]
+                .trim_start_matches(['"', '\''])
+                .chars()
+                .take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':' | '.'))
+                .collect();
+            let encoding = Encoding::for_label(label.as_bytes())?;
+            // a declaration that could be read as ASCII rules out UTF-16, browsers fall back to UTF-8 here too
+            if encoding == UTF_16LE || encoding == UTF_16BE {
+                Some(UTF_8)
+            } else {
+                Some(encoding)
+            }
+        })
+    }
+
+    /// Detects UTF-16 text without a BOM from the zero bytes that mostly-ASCII text leaves in every other byte
+    fn utf16_without_bom(bytes: &[u8]) -> Option<&'static Encoding> {
+        let head = &bytes[..bytes.len().min(SNIFF_LENGTH)];
+        if head.len() < 4 {
+            return None;
+        }
+
+        let pairs = head.len() / 2;
+        let zeros_at = |offset: usize| {
+            head.iter()
+                .skip(offset)
+                .step_by(2)
+                .filter(|b| **b == 0)
+                .count()
+        };
+        let (even_zeros, odd_zeros) = (zeros_at(0), zeros_at(1));
+        if odd_zeros * 2 > pairs && even_zeros * 10 < pairs {
+            Some(UTF_16LE)
+        } else if even_zeros * 2 > pairs && odd_zeros * 10 < pairs {
+            Some(UTF_16BE)
+        } else {
+            None
+        }
+    }
+}
+
+/// The chunks of a plain text file, read and decoded one at a time, see `DecodedText::read_chunks`
+pub(crate) struct TextChunks {
+    file: File,
+    buffer: Vec<u8>,
+    /// How many bytes at the start of the buffer were read but not decoded yet
+    buffered: Option<usize>,
+    encoding: &'static Encoding,
+    source: EncodingSource,
+    decoder: Decoder,
+    /// The text after the last line break of the previous chunk, it starts the next one
+    carry: String,
+    finished: bool,
+    /// Whether a chunk was returned yet, an empty file still gives one empty chunk
+    yielded: bool,
+}
+
+impl Iterator for TextChunks {
+    type Item = Result<DecodedText, IndexingError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            let read = match self.buffered.take() {
+                Some(read) => read,
+                None => match DecodedText::fill_buffer(&mut self.file, &mut self.buffer) {
+                    Ok(read) => read,
+                    Err(e) => {
+                        self.finished = true;
+                        return Some(Err(e.into()));
+                    }
+                },
+            };
+            let last = read < self.buffer.len();
+            self.finished = last;
+
+            let mut text = std::mem::take(&mut self.carry);
+            text.reserve(
+                self.decoder
+                    .max_utf8_buffer_length(read)
+                    .unwrap_or(read * 3),
+            );
+            let (_result, _read, had_errors) =
+                self.decoder
+                    .decode_to_string(&self.buffer[..read], &mut text, last);
+
+            if !last && let Some(line_end) = text.rfind('\n') {
+                self.carry = text.split_off(line_end + 1);
+            }
+            if !text.is_empty() || (last && !self.yielded) {
+                self.yielded = true;
+                return Some(Ok(DecodedText {
+                    text,
+                    encoding: self.encoding,
+                    source: self.source,
+                    had_errors,
+                    truncated: false,
+                    content_hash: None,
+                }));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use encoding_rs::WINDOWS_1252;
+    use std::fs;
+
+    use super::*;
+
+    fn utf16(text: &str, big_endian: bool) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|unit| {
+                if big_endian {
+                    unit.to_be_bytes()
+                } else {
+                    unit.to_le_bytes()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn decodes_by_byte_order_mark() {
+        let utf8 = [b"\xEF\xBB\xBF".as_slice(), "Grüße".as_bytes()].concat();
+        let utf16le = [b"\xFF\xFE".as_slice(), &utf16("Grüße", false)].concat();
+        let utf16be = [b"\xFE\xFF".as_slice(), &utf16("Grüße", true)].concat();
+
+        for (bytes, encoding) in [(utf8, UTF_8), (utf16le, UTF_16LE), (utf16be, UTF_16BE)] {
+            let decoded = DecodedText::decode(&bytes, false, true);
+            assert_eq!(decoded.encoding, encoding);
+            assert_eq!(decoded.source, EncodingSource::ByteOrderMark);
+            // the BOM is not part of the text
+            assert_eq!(decoded.text, "Grüße");
+            assert_eq!(decoded.uncertainty(), None);
+        }
+    }
+
+    #[test]
+    fn honours_declared_charset_in_markup() {
+        let html = b"<html><head><meta charset=\"ISO-8859-1\"></head><body>caf\xE9</body></html>";
+        let decoded = DecodedText::decode(html, true, true);
+        assert_eq!(decoded.encoding, WINDOWS_1252);
+        assert_eq!(decoded.source, EncodingSource::Declared);
+        assert!(decoded.text.contains("café"));
+
+        let xml = b"<?xml version='1.0' encoding='windows-1252'?><note>\x93quoted\x94</note>";
+        let decoded = DecodedText::decode(xml, true, true);
+        assert_eq!(decoded.encoding, WINDOWS_1252);
+        assert_eq!(decoded.source, EncodingSource::Declared);
+        assert!(decoded.text.contains("\u{201C}quoted\u{201D}"));
+
+        // plain text is not searched for declarations
+        let decoded = DecodedText::decode(html, false, true);
+        assert_ne!(decoded.source, EncodingSource::Declared);
+    }
+
+    #[test]
+    fn reads_declared_utf16_as_utf8() {
+        // a declaration readable as ASCII means the file cannot actually be UTF-16
+        let xml = "<?xml version=\"1.0\" encoding=\"UTF-16\"?><note>Grüße</note>";
+        let decoded = DecodedText::decode(xml.as_bytes(), true, true);
+        assert_eq!(decoded.encoding, UTF_8);
+        assert_eq!(decoded.source, EncodingSource::Declared);
+        assert_eq!(decoded.text, xml);
+    }
+
+    #[test]
+    fn detects_utf16_without_byte_order_mark() {
+        let text = "Plain notes saved by an old editor";
+        for (big_endian, encoding) in [(false, UTF_16LE), (true, UTF_16BE)] {
+            let decoded = DecodedText::decode(&utf16(text, big_endian), false, true);
+            assert_eq!(decoded.encoding, encoding);
+            assert_eq!(decoded.source, EncodingSource::Sniffed { confident: true });
+            assert_eq!(decoded.text, text);
+            assert_eq!(decoded.uncertainty(), None);
+        }
+    }
+
+    #[test]
+    fn reports_sniffed_encoding_as_uncertain() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("latin.txt");
+        fs::write(
+            &path,
+            b"Le caf\xE9 est ouvert, la cr\xE8me br\xFBl\xE9e aussi.",
+        )
+        .unwrap();
+
+        let decoded = DecodedText::read(&path, None).unwrap();
+        assert_eq!(decoded.encoding, WINDOWS_1252);
+        assert_eq!(decoded.source, EncodingSource::Sniffed { confident: false });
+        assert_eq!(decoded.text, "Le café est ouvert, la crème brûlée aussi.");
+        assert!(!decoded.had_errors);
+        assert!(decoded.content_hash.is_some());
+        assert!(decoded.uncertainty().unwrap().contains("windows-1252"));
+    }
+
+    #[test]
+    fn drops_character_cut_off_by_partial_read() {
+        let bytes = "Grüße".as_bytes();
+        // the read ends after the first byte of "ü"
+        let decoded = DecodedText::decode(&bytes[..3], false, false);
+        assert_eq!(decoded.encoding, UTF_8);
+        assert_eq!(decoded.source, EncodingSource::ValidUtf8);
+        assert_eq!(decoded.text, "Gr");
+        assert_eq!(decoded.uncertainty(), None);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        fs::write(&path, bytes).unwrap();
+        let decoded = DecodedText::read(&path, Some(3)).unwrap();
+        assert!(decoded.truncated);
+        assert_eq!(decoded.content_hash, None);
+        assert_eq!(decoded.text, "Gr");
+        assert_eq!(decoded.uncertainty(), None);
+    }
+}
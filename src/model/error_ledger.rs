@@ -0,0 +1,235 @@
+use atomicwrites::{AtomicFile, OverwriteBehavior::AllowOverwrite};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt, fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+use tracing::{info, warn};
+
+use crate::model::file_fingerprint::FileFingerprint;
+
+/// The step of the pipeline at which a file failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum IndexingStage {
+    /// The file was indexed, but its encoding had to be guessed
+    Decoding,
+    Converting,
+    Indexing,
+}
+
+impl fmt::Display for IndexingStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexingStage::Decoding => write!(f, "decoding"),
+            IndexingStage::Converting => write!(f, "converting"),
+            IndexingStage::Indexing => write!(f, "indexing"),
+        }
+    }
+}
+
+/// A file that could not be indexed, and the state it was in at the time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct IndexingFailure {
+    pub(crate) path: String,
+    pub(crate) source: String,
+    pub(crate) stage: IndexingStage,
+    pub(crate) message: String,
+    /// The modification time of the file as a unix timestamp, `None` if it could not be read
+    modified: Option<i64>,
+    size: Option<u64>,
+}
+
+impl IndexingFailure {
+    pub(crate) fn new(path: &Path, source: &str, stage: IndexingStage, message: String) -> Self {
+        let fingerprint = FileFingerprint::stat(path);
+        Self {
+            path: path.to_string_lossy().to_string(),
+            source: source.to_owned(),
+            stage,
+            message,
+            modified: fingerprint.map(|f| f.modified.into_timestamp_secs()),
+            size: fingerprint.map(|f| f.size),
+        }
+    }
+
+    /// Checks if the file is still in the state it failed in, retrying it would fail the same way
+    fn unchanged(&self) -> bool {
+        let fingerprint = FileFingerprint::stat(Path::new(&self.path));
+        self.modified == fingerprint.map(|f| f.modified.into_timestamp_secs())
+            && self.size == fingerprint.map(|f| f.size)
+    }
+}
+
+/// The source and path a failure is recorded under, a file reported by more than one source can fail in each
+type FailureKey = (String, String);
+
+/// The files that failed to index, persisted next to the index so they are only retried once they change
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct ErrorLedger {
+    #[serde(with = "failure_list")]
+    failures: BTreeMap<FailureKey, IndexingFailure>,
+    /// Where the ledger was loaded from and is saved to, in the cache directory of a profile
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl ErrorLedger {
+    /// Reads the ledger of the previous run, starting a fresh one if there is none or it cannot be read
+    pub(crate) fn load(path: &Path) -> Self {
+        let empty = || Self {
+            failures: BTreeMap::new(),
+            path: path.to_path_buf(),
+        };
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(_) => return empty(),
+        };
+
+        match serde_json::from_str::<Self>(&contents) {
+            Ok(ledger) => Self {
+                path: path.to_path_buf(),
+                ..ledger
+            },
+            Err(e) => {
+                warn!(
+                    "could not read the indexing error ledger {}: {}",
+                    path.to_string_lossy(),
+                    e
+                );
+                empty()
+            }
+        }
+    }
+
+    pub(crate) fn save(&self) {
+        let ledger_file = AtomicFile::new(&self.path, AllowOverwrite);
+        let result = serde_json::to_vec_pretty(self)
+            .map_err(std::io::Error::other)
+            .and_then(|json| {
+                ledger_file
+                    .write(|f| f.write_all(&json))
+                    .map_err(std::io::Error::from)
+            });
+
+        if let Err(e) = result {
+            warn!(
+                "could not write the indexing error ledger {}: {}",
+                self.path.to_string_lossy(),
+                e
+            );
+        }
+    }
+
+    pub(crate) fn record(&mut self, failure: IndexingFailure) {
+        warn!(
+            "problem with {} while {}: {}",
+            failure.path, failure.stage, failure.message
+        );
+        self.failures
+            .insert((failure.source.clone(), failure.path.clone()), failure);
+    }
+
+    /// Forgets the failure of a source once it indexed the file, a failure of another source stays
+    pub(crate) fn clear(&mut self, source: &str, path: &str) {
+        if self
+            .failures
+            .remove(&(source.to_owned(), path.to_owned()))
+            .is_some()
+        {
+            info!("{} indexed fine by {} after a problem before", path, source);
+        }
+    }
+
+    /// Checks if a source failed on a path before and the file has not changed since
+    ///
+    /// Decoding problems do not count, those files were indexed.
+    pub(crate) fn failed_unchanged(&self, source: &str, path: &str) -> bool {
+        self.failures
+            .get(&(source.to_owned(), path.to_owned()))
+            .map(|failure| failure.stage != IndexingStage::Decoding && failure.unchanged())
+            .unwrap_or(false)
+    }
+
+    /// Forgets the failures that match, so their files are retried even if they did not change
+    pub(crate) fn forget_where(&mut self, matches: impl Fn(&IndexingFailure) -> bool) {
+        self.failures.retain(|_key, failure| !matches(failure));
+    }
+
+    /// Drops the failures of paths that their source no longer reports, keyed by source and path
+    pub(crate) fn retain_reported(&mut self, reported_paths: &HashSet<FailureKey>) {
+        self.failures
+            .retain(|key, _failure| reported_paths.contains(key));
+    }
+
+    pub(crate) fn failures(&self) -> impl Iterator<Item = &IndexingFailure> {
+        self.failures.values()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.failures.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Saves the failures as a list, JSON object keys cannot be pairs and each failure holds its source and path anyway
+mod failure_list {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::collections::BTreeMap;
+
+    use super::{FailureKey, IndexingFailure};
+
+    pub(super) fn serialize<S: Serializer>(
+        failures: &BTreeMap<FailureKey, IndexingFailure>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(failures.values())
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<FailureKey, IndexingFailure>, D::Error> {
+        Ok(Vec::<IndexingFailure>::deserialize(deserializer)?
+            .into_iter()
+            .map(|failure| ((failure.source.clone(), failure.path.clone()), failure))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_failures_of_each_source_apart() {
+        let path = Path::new("/notes/lib.rs");
+        let mut ledger = ErrorLedger::default();
+        for source in ["code_symbols", "markdown_files"] {
+            ledger.record(IndexingFailure::new(
+                path,
+                source,
+                IndexingStage::Converting,
+                "failed".to_owned(),
+            ));
+        }
+        assert_eq!(ledger.len(), 2);
+
+        // indexing the file with one source leaves the failure of the other
+        ledger.clear("markdown_files", "/notes/lib.rs");
+        assert_eq!(ledger.len(), 1);
+        assert!(ledger.failed_unchanged("code_symbols", "/notes/lib.rs"));
+        assert!(!ledger.failed_unchanged("markdown_files", "/notes/lib.rs"));
+
+        let saved: ErrorLedger =
+            serde_json::from_str(&serde_json::to_string(&ledger).unwrap()).unwrap();
+        let failures: Vec<_> = saved
+            .failures()
+            .map(|failure| (failure.source.as_str(), failure.path.as_str()))
+            .collect();
+        assert_eq!(failures, vec![("code_symbols", "/notes/lib.rs")]);
+    }
+}
@@ -0,0 +1,173 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::Path,
+    time::SystemTime,
+};
+use tantivy::{DateTime, schema::DateTimePrecision};
+use time::OffsetDateTime;
+use tracing::warn;
+use xxhash_rust::xxh3::{Xxh3, xxh3_64};
+
+/// The state of a file on disk when it was indexed, used to decide if it needs to be indexed again
+///
+/// Directories (e.g. albums and projects) only get a modification time and size, their contents are not hashed. The
+/// modification time of a directory is the newest of its own and those of the files directly in it, since editing a
+/// file e.g. retagging a track does not change the modification time of the directory it is in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FileFingerprint {
+    pub(crate) modified: DateTime,
+    pub(crate) size: u64,
+    pub(crate) content_hash: Option<u64>,
+}
+
+impl FileFingerprint {
+    /// Reads the modification time and size of a path without touching its contents
+    pub(crate) fn stat(path: &Path) -> Option<Self> {
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                warn!("could not get metadata for {}: {}", path.display(), e);
+                return None;
+            }
+        };
+
+        let modified = match metadata.modified() {
+            Ok(modified) if metadata.is_dir() => DateTime::from_utc(OffsetDateTime::from(
+                Self::newest_file_modified(path, modified),
+            )),
+            Ok(modified) => DateTime::from_utc(OffsetDateTime::from(modified)),
+            Err(e) => {
+                warn!(
+                    "could not get modification date for {}: {}",
+                    path.display(),
+                    e
+                );
+                return None;
+            }
+        };
+
+        Some(Self {
+            // tantivy stores dates with second precision, so compare at that precision
+            modified: modified.truncate(DateTimePrecision::Seconds),
+            size: metadata.len(),
+            content_hash: None,
+        })
+    }
+
+    /// The newest modification time of the files directly in a directory, or `modified` if it is newer
+    fn newest_file_modified(dir: &Path, modified: SystemTime) -> SystemTime {
+        fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .filter_map(Result::ok)
+            .filter_map(|entry| entry.metadata().ok())
+            .filter(|metadata| metadata.is_file())
+            .filter_map(|metadata| metadata.modified().ok())
+            .fold(modified, SystemTime::max)
+    }
+
+    /// Reads the modification time and size, and hashes the contents if this is a file
+    pub(crate) fn read(path: &Path) -> Option<Self> {
+        let mut fingerprint = Self::stat(path)?;
+        if path.is_file() {
+            match Self::hash_file(path) {
+                Ok(hash) => fingerprint.content_hash = Some(hash),
+                Err(e) => warn!("could not hash {}: {}", path.display(), e),
+            }
+        }
+        Some(fingerprint)
+    }
+
+    /// Hashes contents that were already read, the same way `hash_file` does
+    pub(crate) fn hash_bytes(bytes: &[u8]) -> u64 {
+        xxh3_64(bytes)
+    }
+
+    /// Hashes the contents of a file without reading it into memory all at once
+    pub(crate) fn hash_file(path: &Path) -> io::Result<u64> {
+        let mut file = File::open(path)?;
+        let mut hasher = Xxh3::new();
+        let mut buffer = [0; 64 * 1024];
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+        Ok(hasher.digest())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::decoded_text::DecodedText;
+    use std::time::Duration;
+
+    #[test]
+    fn hashes_read_bytes_like_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("note.md");
+        let contents = "# Note\n\n".repeat(20_000);
+        fs::write(&path, &contents).unwrap();
+
+        let hash = FileFingerprint::hash_file(&path).unwrap();
+        assert_eq!(FileFingerprint::hash_bytes(contents.as_bytes()), hash);
+        assert_eq!(
+            FileFingerprint::read(&path).unwrap().content_hash,
+            Some(hash)
+        );
+
+        // converters hash what they read, unless they only read the start of the file
+        assert_eq!(
+            DecodedText::read(&path, None).unwrap().content_hash,
+            Some(hash)
+        );
+        assert_eq!(
+            DecodedText::read(&path, Some(100)).unwrap().content_hash,
+            None
+        );
+        // chunks are indexed before the rest of the file is read, so they leave the hash to the fingerprint
+        let chunks: Vec<_> = DecodedText::read_chunks(&path, 4096)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|chunk| chunk.content_hash.is_none()));
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| chunk.text.as_str())
+                .collect::<String>(),
+            contents
+        );
+    }
+
+    #[test]
+    fn dates_directories_by_their_newest_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        let track = dir.join("01.flac");
+        fs::write(&track, "track").unwrap();
+        let before = FileFingerprint::stat(&dir).unwrap();
+
+        // retagging a track only changes the track, not the directory
+        let later = SystemTime::now() + Duration::from_secs(60);
+        File::options()
+            .write(true)
+            .open(&track)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        let after = FileFingerprint::stat(&dir).unwrap();
+
+        assert!(after.modified > before.modified);
+        assert_eq!(
+            after.modified,
+            FileFingerprint::stat(&track).unwrap().modified
+        );
+        assert_eq!(after.content_hash, None);
+    }
+}
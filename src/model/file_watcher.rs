@@ -0,0 +1,217 @@
+use ignore::{Match, Walk, gitignore::Gitignore};
+use notify_debouncer_mini::{
+    DebounceEventResult, Debouncer, new_debouncer,
+    notify::{RecommendedWatcher, RecursiveMode},
+};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::mpsc::{Receiver, channel},
+    time::Duration,
+};
+use tracing::{info, warn};
+
+use crate::{
+    collectors::{
+        aichat_session_files::AichatSessionFiles, code_symbols::CodeSymbols,
+        image_files::ImageFiles, markdown_files::MarkdownFiles, music_files::MusicFiles,
+        projects::Projects, reading_highlights::ReadingHighlights,
+        web_scrapbook_files::WebScrapbookFiles,
+    },
+    config::{Conf, PathList},
+    model::index_entry::IndexPath,
+};
+
+/// How long to wait for a burst of changes e.g. an editor's save dance to settle before re-indexing
+const WATCH_DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// The ignore files that the collectors' walks respect, in order of precedence
+const IGNORE_FILES: [&str; 2] = [".ignore", ".gitignore"];
+
+/// Watches the collector roots and maps changed paths to the paths that need to be re-indexed
+///
+/// The debounced events wait in a channel until they are taken with `next_events`, so changes made while the index
+/// is busy are not lost. The watch stops when this is dropped.
+pub(crate) struct FileWatcher {
+    aichat_session_files: AichatSessionFiles,
+    markdown_files: MarkdownFiles,
+    web_scrapbook_files: WebScrapbookFiles,
+    image_files: ImageFiles,
+    music_files: MusicFiles,
+    reading_highlights: ReadingHighlights,
+    code_symbols: CodeSymbols,
+    projects: Projects,
+    events: Receiver<DebounceEventResult>,
+    _debouncer: Debouncer<RecommendedWatcher>,
+}
+
+impl FileWatcher {
+    /// Starts watching every root that exists, roots nested inside another root are only watched once
+    pub(crate) fn new(
+        config: &Conf,
+        roots: PathList,
+    ) -> notify_debouncer_mini::notify::Result<Self> {
+        let (event_sender, events) = channel();
+        let mut debouncer = new_debouncer(WATCH_DEBOUNCE, event_sender)?;
+
+        let roots: Vec<PathBuf> = roots.into_iter().filter(|root| root.exists()).collect();
+        for root in &roots {
+            if roots
+                .iter()
+                .any(|other| other != root && root.starts_with(other))
+            {
+                continue;
+            }
+
+            info!("watching {} for changes...", root.to_string_lossy());
+            if let Err(e) = debouncer.watcher().watch(root, RecursiveMode::Recursive) {
+                // TODO collect these errors so the user can see what is not being watched
+                warn!("could not watch {}: {}", root.to_string_lossy(), e)
+            }
+        }
+
+        Ok(Self {
+            aichat_session_files: AichatSessionFiles::new(&config.aichat_session_files),
+            markdown_files: MarkdownFiles::new(&config.markdown_files),
+            web_scrapbook_files: WebScrapbookFiles::new(&config.web_scrapbook_files),
+            image_files: ImageFiles::new(&config.image_files),
+            music_files: MusicFiles::new(&config.music_files, config.music_album_documents),
+            reading_highlights: ReadingHighlights::new(&config.reading_highlight_files),
+            code_symbols: CodeSymbols::new(&config.code_symbol_files),
+            projects: Projects::new(&config.project_dirs),
+            events,
+            _debouncer: debouncer,
+        })
+    }
+
+    /// Takes the next batch of debounced events, if there is one
+    pub(crate) fn next_events(&self) -> Option<DebounceEventResult> {
+        self.events.try_recv().ok()
+    }
+
+    /// Asks every collector which paths need to be re-indexed after a change to `path`
+    ///
+    /// A directory e.g. one that was created or moved into a root is walked the way the collectors walk their roots,
+    /// and every path in it is checked. The result has no duplicates, a path may still be claimed by more than one
+    /// source.
+    pub(crate) fn index_paths_for_change(&self, path: &Path) -> Vec<IndexPath> {
+        let mut seen = HashSet::new();
+        if !path.is_dir() {
+            return self
+                .collector_index_paths(path)
+                .filter(|index_path| seen.insert(index_path.clone()))
+                .collect();
+        }
+
+        let mut index_paths = Vec::new();
+        // the walk starts with the directory itself
+        for result in Walk::new(path) {
+            match result {
+                Ok(entry) => index_paths.extend(
+                    self.collector_index_paths(entry.path())
+                        .filter(|index_path| seen.insert(index_path.clone())),
+                ),
+                Err(e) => warn!("could not open path: {}", e),
+            }
+        }
+        index_paths
+    }
+
+    /// The paths that each collector needs to re-index after a change to a single path
+    fn collector_index_paths(&self, path: &Path) -> impl Iterator<Item = IndexPath> {
+        [
+            self.aichat_session_files.index_paths_for_change(path),
+            self.markdown_files.index_paths_for_change(path),
+            self.web_scrapbook_files.index_paths_for_change(path),
+            self.image_files.index_paths_for_change(path),
+            self.music_files.index_paths_for_change(path),
+            self.reading_highlights.index_paths_for_change(path),
+            self.code_symbols.index_paths_for_change(path),
+            self.projects.index_paths_for_change(path),
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    /// Checks if the collectors' walks would have skipped this path
+    ///
+    /// Hidden paths are skipped unless `include_hidden` is set, as are paths matched by an ignore file between the
+    /// root and the path. The nearest ignore file wins.
+    pub(crate) fn is_ignored(root: &Path, path: &Path, include_hidden: bool) -> bool {
+        let Ok(relative) = path.strip_prefix(root) else {
+            return true;
+        };
+
+        if !include_hidden
+            && relative
+                .components()
+                .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+        {
+            return true;
+        }
+
+        let is_dir = path.is_dir();
+        for dir in path.ancestors().skip(1) {
+            for ignore_file in IGNORE_FILES {
+                let ignore_path = dir.join(ignore_file);
+                if !ignore_path.is_file() {
+                    continue;
+                }
+
+                let (gitignore, error) = Gitignore::new(&ignore_path);
+                if let Some(e) = error {
+                    warn!("could not parse {}: {}", ignore_path.to_string_lossy(), e);
+                }
+                match gitignore.matched_path_or_any_parents(path, is_dir) {
+                    Match::Ignore(_) => return true,
+                    Match::Whitelist(_) => return false,
+                    Match::None => {}
+                }
+            }
+
+            if dir == root {
+                break;
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use confique::Config;
+    use std::fs;
+
+    #[test]
+    fn walks_directories_moved_into_a_root() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path().to_path_buf();
+        let moved = root.join("moved");
+        fs::create_dir_all(moved.join("nested")).unwrap();
+        fs::create_dir_all(moved.join(".hidden")).unwrap();
+        for file in ["a.md", "nested/b.md", ".hidden/c.md"] {
+            fs::write(moved.join(file), "# note").unwrap();
+        }
+
+        let mut config = Conf::builder().load().unwrap();
+        config.markdown_files = vec![root.clone()];
+        let file_watcher = FileWatcher::new(&config, vec![root.clone()]).unwrap();
+
+        // hidden files are skipped the way the collectors' walks skip them
+        let index_paths = file_watcher.index_paths_for_change(&moved);
+        let mut paths: Vec<&Path> = index_paths.iter().map(IndexPath::path).collect();
+        paths.sort();
+        assert_eq!(paths, [moved.join("a.md"), moved.join("nested/b.md")]);
+        assert!(
+            index_paths
+                .iter()
+                .all(|index_path| matches!(index_path, IndexPath::MarkdownFile(_)))
+        );
+
+        let index_paths = file_watcher.index_paths_for_change(&moved.join("a.md"));
+        assert_eq!(index_paths.len(), 1);
+        assert_eq!(index_paths[0].path(), moved.join("a.md"));
+    }
+}
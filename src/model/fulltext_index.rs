@@ -1,65 +1,202 @@
-use atomicwrites::{AtomicFile, OverwriteBehavior::AllowOverwrite};
+use crossbeam_channel::{SendError, TryRecvError, bounded};
 use std::{
+    collections::{BTreeSet, HashMap, HashSet},
     fs::{self, create_dir_all},
-    io::Write,
+    panic::{self, AssertUnwindSafe},
     path::{Path, PathBuf},
     sync::{
-        LazyLock,
-        mpsc::{Receiver, Sender, channel},
+        Arc, RwLock,
+        mpsc::{Receiver, RecvTimeoutError, Sender, channel},
     },
     thread::{self, spawn},
     time::{Duration, Instant},
 };
 use tantivy::{
-    Index, IndexReader, IndexSettings, IndexWriter, ReloadPolicy, TantivyDocument, TantivyError,
-    Term,
-    collector::{Count, TopDocs},
+    DocAddress, Index, IndexReader, IndexWriter, Opstamp, ReloadPolicy, TantivyDocument,
+    TantivyError, Term,
+    collector::{Count, DocSetCollector, TopDocs},
     directory::{ManagedDirectory, MmapDirectory},
-    query::{QueryParser, QueryParserError, TermQuery},
+    query::{BooleanQuery, Occur, Query, QueryParserError, TermQuery},
     schema::{
-        DateOptions, Field as TantivyField, INDEXED, IndexRecordOption, Schema, TextFieldIndexing,
-        TextOptions,
+        DateOptions, FAST, Field as TantivyField, INDEXED, IndexRecordOption, STORED, Schema,
+        TextFieldIndexing, TextOptions, Value,
     },
-    snippet::SnippetGenerator,
 };
-use time::OffsetDateTime;
 use tracing::{debug, info, warn};
 
 use crate::{
     collectors::{
-        aichat_session_files::AichatSessionFiles, markdown_files::MarkdownFiles,
-        web_scrapbook_files::WebScrapbookFiles,
+        aichat_session_files::{AICHAT_SESSION_FILES_SOURCE, AichatSessionFiles},
+        code_symbols::{CODE_SYMBOLS_SOURCE, CodeSymbols},
+        image_files::{IMAGE_FILES_SOURCE, ImageFiles},
+        markdown_files::{MARKDOWN_FILES_SOURCE, MarkdownFiles},
+        music_files::{MUSIC_FILES_SOURCE, MusicFiles},
+        projects::{PROJECTS_SOURCE, ProjectLookup, Projects},
+        reading_highlights::{READING_HIGHLIGHTS_SOURCE, ReadingHighlights},
+        web_scrapbook_files::{WEB_SCRAPBOOK_FILES_SOURCE, WebScrapbookFiles},
     },
     config::Conf,
-    messages::{index_request::IndexRequest, index_results::IndexResults},
+    invocations::invocation_list::InvocationList,
+    messages::{
+        index_request::{IndexRequest, SearchRequest},
+        index_results::IndexResults,
+    },
+    model::compaction::{self, CompactionReport},
+    model::error_ledger::{ErrorLedger, IndexingFailure, IndexingStage},
+    model::file_fingerprint::FileFingerprint,
+    model::file_watcher::FileWatcher,
     model::index_entry::{
-        IndexEntry, IndexEntrySender, IndexPath, IndexPathReceiver, IndexPathSender,
+        ConversionReceiver, ConversionResult, ConversionSender, ConvertedEntries, IndexEntry,
+        IndexEntryReceiver, IndexEntrySender, IndexPath, IndexPathReceiver,
     },
+    model::index_stats::{IndexStats, StatsFields},
+    model::indexing_checkpoint::IndexingCheckpoint,
+    model::indexing_error::IndexingError,
+    model::instance_lock::{self, InstanceLock},
+    model::path_priority::{PRIORITY_WINDOW, PathPriorities, PathQueue},
+    model::reindex_target::ReindexTarget,
+    model::reorder_buffer::ReorderBuffer,
+    model::resource_governor::{self, PauseReason},
+    model::schema_version::{self, SCHEMA_VERSION},
     model::search_result::SearchResult,
-    ui::retsyn_app::PROJECT_DIRS,
+    model::search_service::{SearchService, SearchableIndex, SharedSearchableIndex},
+    model::size_limits::{LargeFilePolicy, SizeLimits},
+    model::volume::{self, VolumeLabels, Volumes},
+    profile::{IndexDirs, Profile},
 };
 
-pub(crate) static INDEX_PATH: LazyLock<PathBuf> =
-    LazyLock::new(|| PROJECT_DIRS.cache_dir().join("tantivy"));
-
-pub(crate) static INDEXING_EPOCH_PATH: LazyLock<PathBuf> = LazyLock::new(|| {
-    PROJECT_DIRS
-        .cache_dir()
-        .to_path_buf()
-        .join("last_indexing_epoch.txt")
-});
-
 const SOURCE: &str = "source";
 const INDEXED_AT: &str = "indexed_at";
 const PATH: &str = "path";
+/// The volume a file is on, see `Volumes`
+const VOLUME: &str = "volume";
+/// Identifies the documents of a file wherever its volume is mounted, see `Volumes::volume_path`
+const VOLUME_PATH: &str = "volume_path";
 const TITLE: &str = "title";
 const BODY: &str = "body";
 
+// the state of the source file when it was indexed, used for change detection
+const MODIFIED: &str = "modified";
+const SIZE: &str = "size";
+const CONTENT_HASH: &str = "content_hash";
+
+/// Set on documents that only hold the start of a file that was above its size limit
+const TRUNCATED: &str = "truncated";
+
+/// Set on the document that stands in for a file that converted to no entries, it only records the state of the file
+/// so that it counts as up to date. These are left out of search results and statistics.
+const PLACEHOLDER: &str = "placeholder";
+
+// optional metadata fields, only some sources provide these
+pub(crate) const KEYWORDS: &str = "keywords";
+pub(crate) const CAMERA: &str = "camera";
+pub(crate) const DATE: &str = "date";
+pub(crate) const PLACE: &str = "place";
+pub(crate) const KIND: &str = "kind";
+pub(crate) const ARTIST: &str = "artist";
+pub(crate) const ALBUM: &str = "album";
+pub(crate) const TRACK: &str = "track";
+pub(crate) const GENRE: &str = "genre";
+pub(crate) const BOOK: &str = "book";
+pub(crate) const AUTHOR: &str = "author";
+pub(crate) const LOCATION: &str = "location";
+pub(crate) const SYMBOL: &str = "symbol";
+pub(crate) const LANG: &str = "lang";
+pub(crate) const LINE: &str = "line";
+pub(crate) const PROJECT: &str = "project";
+pub(crate) const ENCODING: &str = "encoding";
+
+/// All of the optional metadata fields that an IndexEntry may fill in, besides `SYMBOL`
+const METADATA_FIELDS: [&str; 16] = [
+    KEYWORDS, CAMERA, DATE, PLACE, KIND, ARTIST, ALBUM, TRACK, GENRE, BOOK, AUTHOR, LOCATION, LANG,
+    LINE, PROJECT, ENCODING,
+];
+
+/// How many paths may wait between the collectors and the filter
+const PATH_CHANNEL_CAPACITY: usize = 1024;
+
+/// The order in which sources convert a file that several of them report, the most specific converter first
+///
+/// `markdown_files` takes any file, so it only converts the files that no other source reports.
+const SOURCE_PRIORITY: [&str; 8] = [
+    READING_HIGHLIGHTS_SOURCE,
+    AICHAT_SESSION_FILES_SOURCE,
+    WEB_SCRAPBOOK_FILES_SOURCE,
+    CODE_SYMBOLS_SOURCE,
+    MUSIC_FILES_SOURCE,
+    IMAGE_FILES_SOURCE,
+    PROJECTS_SOURCE,
+    MARKDOWN_FILES_SOURCE,
+];
+
+/// Where a source comes in `SOURCE_PRIORITY`, a lower value converts first
+fn source_priority(source: &str) -> usize {
+    SOURCE_PRIORITY
+        .iter()
+        .position(|prioritized| *prioritized == source)
+        .unwrap_or(SOURCE_PRIORITY.len())
+}
+
+/// The sources that reported a file during a run, see `FulltextIndex::claim`
+#[derive(Default)]
+struct SourceClaim {
+    /// The path each source reported the file as, one per source
+    reports: Vec<IndexPath>,
+    /// The source that converts the file in this run, `None` while it waits for a source recorded on its documents
+    converter: Option<&'static str>,
+    /// The sequence number of the conversion of the file that was handed to the converters, if there is one
+    sequence: Option<usize>,
+    /// The sources recorded on the documents of the file when it was first reported, empty if that was the only one
+    recorded_sources: Vec<String>,
+}
+
+/// The sources that reported each file, keyed by volume path
+///
+/// A file is reported more than once if roots overlap, if a symlink leads to it, or if several sources claim it. It is
+/// converted once, by the first of its sources in `SOURCE_PRIORITY`, and every source is recorded on its documents.
+type SourceClaims = HashMap<String, SourceClaim>;
+
+/// The channels and bookkeeping of one indexing run, created by `start_collectors` and drained by `update`
+pub(crate) struct IndexingPipeline {
+    /// `None` once every collector is done
+    path_receiver: Option<IndexPathReceiver>,
+    /// `None` once every path that passed the filter was handed to the converters
+    conversion_sender: Option<ConversionSender>,
+    entry_receiver: IndexEntryReceiver,
+    /// Paths that passed the filter and wait for a converter, the most recently modified or opened first
+    queued_paths: PathQueue,
+    priorities: PathPriorities,
+    /// The sequence number of the next path handed to the converters
+    next_sequence: usize,
+    /// Converted entries that arrived ahead of an earlier path
+    reorder_buffer: ReorderBuffer<ConversionResult>,
+    converters_done: bool,
+    /// Every path the collectors reported, as it was reported, along with the source that reported it
+    reported_paths: HashSet<(String, String)>,
+    /// Every file the collectors reported, anything else in the index was deleted or moved
+    claims: SourceClaims,
+    /// Conversions of files that a source earlier in `SOURCE_PRIORITY` took over, their entries are dropped
+    stale_sequences: HashSet<usize>,
+    /// The source or directory that is being reindexed, `None` for a full update
+    scope: Option<ReindexTarget>,
+    /// Paths that were not in the index yet, these may be the new location of a moved file
+    new_paths: Vec<PathBuf>,
+}
+
+/// Whether a path that a collector reported needs to be converted
+#[derive(PartialEq)]
+enum PathState {
+    New,
+    Changed,
+    UpToDate,
+}
+
 #[derive(Debug)]
 pub(crate) enum IndexStatus {
     Initializing,
     CollectingPaths,
     FilteringPaths,
+    RemovingMissingEntries,
     UpdatingIndex {
         indexed: usize,
         total: usize,
@@ -67,47 +204,128 @@ pub(crate) enum IndexStatus {
         file_path: String,
     },
     UpToDate,
+    /// Merging segments and deleting unused files once indexing is done
+    Compacting,
+    /// Another process writes the index, this one only searches it
+    IndexingElsewhere,
+}
+
+/// The fields that search results are read from
+///
+/// These are looked up by name, an index built with an older schema version may number its fields differently or not
+/// have some of them at all.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ResultFields {
+    pub(crate) source: TantivyField,
+    pub(crate) indexed_at: TantivyField,
+    pub(crate) path: TantivyField,
+    pub(crate) title: TantivyField,
+    pub(crate) body: TantivyField,
+    pub(crate) line: Option<TantivyField>,
+    pub(crate) location: Option<TantivyField>,
+    pub(crate) date: Option<TantivyField>,
+    pub(crate) truncated: Option<TantivyField>,
+    pub(crate) volume: Option<TantivyField>,
+    /// Missing from indexes built before placeholders were added, those have none to leave out
+    pub(crate) placeholder: Option<TantivyField>,
+}
+
+impl ResultFields {
+    pub(crate) fn new(schema: &Schema) -> Result<Self, TantivyError> {
+        Ok(Self {
+            source: schema.get_field(SOURCE)?,
+            indexed_at: schema.get_field(INDEXED_AT)?,
+            path: schema.get_field(PATH)?,
+            title: schema.get_field(TITLE)?,
+            body: schema.get_field(BODY)?,
+            line: schema.get_field(LINE).ok(),
+            location: schema.get_field(LOCATION).ok(),
+            date: schema.get_field(DATE).ok(),
+            truncated: schema.get_field(TRUNCATED).ok(),
+            volume: schema.get_field(VOLUME).ok(),
+            placeholder: schema.get_field(PLACEHOLDER).ok(),
+        })
+    }
+}
+
+impl StatsFields {
+    pub(crate) fn new(schema: &Schema) -> Result<Self, TantivyError> {
+        Ok(Self {
+            source: schema.get_field(SOURCE)?,
+            indexed_at: schema.get_field(INDEXED_AT)?,
+            path: schema.get_field(PATH)?,
+            title: schema.get_field(TITLE)?,
+            body: schema.get_field(BODY)?,
+            size: schema.get_field(SIZE)?,
+            kind: schema.get_field(KIND)?,
+            placeholder: schema.get_field(PLACEHOLDER).ok(),
+        })
+    }
 }
 
 pub struct FulltextIndex {
     // status: IndexStatus,
     config: Conf,
-    last_indexing_epoch: Option<OffsetDateTime>,
+    /// Where the index of the profile this was opened with is kept
+    dirs: IndexDirs,
     index: Index,
     reader: IndexReader,
     writer: IndexWriter,
+    /// The index searches run against, an outdated one while `index` is being rebuilt
+    searchable_index: SharedSearchableIndex,
+    /// Whether `index` is a rebuild that replaces the index at `dirs.index` once it is complete
+    rebuilding: bool,
     request_receiver: Receiver<IndexRequest>,
     results_sender: Sender<IndexResults>,
-    pub(crate) source_field: TantivyField,
-    pub(crate) indexed_at_field: TantivyField,
-    pub(crate) path_field: TantivyField,
-    pub(crate) title_field: TantivyField,
-    pub(crate) body_field: TantivyField,
+    source_field: TantivyField,
+    indexed_at_field: TantivyField,
+    path_field: TantivyField,
+    volume_field: TantivyField,
+    volume_path_field: TantivyField,
+    title_field: TantivyField,
+    body_field: TantivyField,
+    modified_field: TantivyField,
+    size_field: TantivyField,
+    content_hash_field: TantivyField,
+    truncated_field: TantivyField,
+    placeholder_field: TantivyField,
     total_files: usize,
     out_of_date_files: usize,
+    removed_files: usize,
     files_indexed: usize,
+    /// The progress of the current indexing run, committed along with the documents
+    checkpoint: IndexingCheckpoint,
+    uncommitted_files: usize,
+    uncommitted_removals: usize,
+    uncommitted_documents: usize,
+    uncommitted_bytes: usize,
+    last_commit: Instant,
+    /// Files that failed to index, saved with every commit
+    error_ledger: ErrorLedger,
+    /// Reindex requests that arrived while indexing, they run once it is done
+    queued_reindexes: Vec<ReindexTarget>,
+    /// Whether a compaction was requested while indexing, it runs after the queued reindexes
+    compaction_queued: bool,
+    /// Whether indexing was paused from the UI
+    paused: bool,
+    /// When the user last typed a search, indexing waits for them to be idle if `index_only_when_idle` is set
+    last_search_activity: Option<Instant>,
+    /// Keeps other instances from writing the index while this one does
+    instance_lock: InstanceLock,
+    /// The volumes that were available when the current run started
+    volumes: Volumes,
 }
 
 pub(crate) type SearchResultsAndErrors =
     Result<(Vec<SearchResult>, Vec<QueryParserError>), TantivyError>;
 
-fn last_indexing_epoch() -> Option<OffsetDateTime> {
-    match INDEXING_EPOCH_PATH.exists() {
-        true => match fs::read_to_string(INDEXING_EPOCH_PATH.as_path()) {
-            Ok(epoch_file) => match epoch_file.parse::<i64>() {
-                Ok(e) => match OffsetDateTime::from_unix_timestamp(e) {
-                    Ok(dt) => Some(dt),
-                    Err(_) => None,
-                },
-                Err(_) => None,
-            },
-            Err(_) => None,
-        },
-        false => None,
-    }
+/// Opens the index in a directory for reading, through the same managed directory the writer wrote it with
+pub(crate) fn open_index(index_dir: &Path) -> Result<Index, TantivyError> {
+    let directory = ManagedDirectory::wrap(Box::new(MmapDirectory::open(index_dir)?))?;
+    Index::open(directory)
 }
 
-fn tantivy_schema() -> Schema {
+pub(crate) fn tantivy_schema() -> Schema {
     let mut schema_builder = Schema::builder();
 
     // setup date handling optioss for the indexed_at field
@@ -138,60 +356,127 @@ fn tantivy_schema() -> Schema {
     // record when this was added to the index to facilitate updates
     schema_builder.add_date_field(INDEXED_AT, date_options);
 
-    // the path on disk where this resource resides
-    schema_builder.add_text_field(PATH, file_path_options);
+    // the path on disk where this resource resides, as of the last time its volume was seen
+    schema_builder.add_text_field(PATH, file_path_options.clone());
+
+    // the volume the resource is on and its path within that volume, the latter identifies its documents
+    schema_builder.add_text_field(VOLUME, file_path_options.clone());
+    schema_builder.add_text_field(VOLUME_PATH, file_path_options.clone());
 
-    // add the source, the module that discovered this file
-    schema_builder.add_text_field(SOURCE, english_text_options.clone());
+    // add the source, the module that discovered this file. It is not tokenized so a whole source can be deleted by
+    // its name when it is reindexed
+    schema_builder.add_text_field(SOURCE, file_path_options.clone());
 
     // the title of the file
     schema_builder.add_text_field(TITLE, english_text_options.clone());
 
     // the main text of the file
-    schema_builder.add_text_field(BODY, english_text_options);
+    schema_builder.add_text_field(BODY, english_text_options.clone());
+
+    // the modification time, size and content hash of the source file, fast fields so that checking whether a
+    // file changed does not require loading the stored document
+    let modified_options = DateOptions::default()
+        .set_stored()
+        .set_fast()
+        .set_precision(tantivy::schema::DateTimePrecision::Seconds);
+    schema_builder.add_date_field(MODIFIED, modified_options);
+    schema_builder.add_u64_field(SIZE, STORED | FAST);
+    schema_builder.add_u64_field(CONTENT_HASH, STORED | FAST);
+    schema_builder.add_bool_field(TRUNCATED, STORED);
+    schema_builder.add_bool_field(PLACEHOLDER, INDEXED);
+
+    // source specific metadata e.g. photo keywords, so they can be queried via `field:value`
+    for metadata_field in METADATA_FIELDS {
+        schema_builder.add_text_field(metadata_field, english_text_options.clone());
+    }
+
+    // identifiers are matched exactly, stemming would match `parse_query` against `parse_queries` and `parsing`
+    schema_builder.add_text_field(SYMBOL, file_path_options.clone());
 
     schema_builder.build()
 }
 
 impl FulltextIndex {
     fn send_status(&self, status: IndexStatus) {
-        self.results_sender
-            .send(IndexResults::Status(status))
-            .expect("should be able to send status");
+        // the UI may have closed already, there is nobody left to tell
+        if let Err(e) = self.results_sender.send(IndexResults::Status(status)) {
+            debug!("could not send status: {}", e);
+        }
+    }
+
+    /// Logs an error that stopped indexing and passes it on to the UI
+    pub(crate) fn report_error(&self, error: IndexingError) {
+        warn!("indexing stopped: {}", error);
+        if let Err(e) = self.results_sender.send(IndexResults::Error(error)) {
+            debug!("could not send indexing error: {}", e);
+        }
     }
 
+    /// Opens the index for writing, failing with `LockError::LockBusy` if another process writes to it
+    ///
+    /// See `instance_lock::held_by_another_process` and `serve_read_only` for searching the index anyway.
     pub(crate) fn new(
         config: Conf,
+        dirs: IndexDirs,
+        request_receiver: Receiver<IndexRequest>,
+        results_sender: Sender<IndexResults>,
+    ) -> Result<Self, TantivyError> {
+        // this has to come first, the steps below move and create index directories
+        let instance_lock = InstanceLock::acquire(&dirs.cache)?;
+        Self::open(
+            config,
+            dirs,
+            request_receiver,
+            results_sender,
+            instance_lock,
+        )
+    }
+
+    /// Opens the index of the active profile for the CLI, nobody is listening to its status updates so progress is
+    /// logged instead
+    ///
+    /// Indexing stops once nobody can send requests anymore, so the returned sender has to outlive the index.
+    fn open_for_cli(config: Conf) -> Result<(Self, Sender<IndexRequest>), IndexingError> {
+        let dirs = Profile::active().index_dirs(&config);
+        let (request_sender, request_receiver) = channel();
+        let (results_sender, _results_receiver) = channel();
+        match Self::new(config, dirs, request_receiver, results_sender) {
+            Ok(index) => Ok((index, request_sender)),
+            Err(e) if instance_lock::held_by_another_process(&e) => {
+                Err(IndexingError::IndexingElsewhere)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn open(
+        config: Conf,
+        dirs: IndexDirs,
         request_receiver: Receiver<IndexRequest>,
         results_sender: Sender<IndexResults>,
+        instance_lock: InstanceLock,
     ) -> Result<Self, TantivyError> {
         // setup the schema
         let schema = tantivy_schema();
 
         // create the index
-        create_dir_all(&*INDEX_PATH)?;
+        Self::recover_index_swap(&dirs)?;
+        create_dir_all(&dirs.index)?;
         info!(
             "tantivy index directory is: {}",
-            INDEX_PATH.to_string_lossy()
+            dirs.index.to_string_lossy()
         );
-        let index_dir = ManagedDirectory::wrap(Box::new(
-            MmapDirectory::open(INDEX_PATH.as_path())
-                .expect("should be able to create the tantivy mmap directory"),
-        ))
-        .expect("should be able to create the tantivy managed directory");
-
-        // attempt to read the last indexing epoch
-        let last_indexing_epoch = last_indexing_epoch();
-
-        // if the indexing_epoch_file exists `open_or_create` the existing index, otherwise `create` a new one
-        let index = if last_indexing_epoch.is_some() {
-            debug!("opening or creating tantivy index");
-            // calling open or create just in case the epoch file exists but the actual index was deleted
-            Index::open_or_create(index_dir, schema.clone())?
-        } else {
-            debug!("creating new tantivy index");
-            Index::create(index_dir, schema.clone(), IndexSettings::default())?
-        };
+
+        // an index built with another schema version is rebuilt next to it, and searched until that is done
+        let (index_path, outdated_index) = Self::index_for_schema_version(&dirs)?;
+        let rebuilding = index_path != dirs.index;
+        let index_dir =
+            ManagedDirectory::wrap(Box::new(MmapDirectory::open(index_path.as_path())?))?;
+
+        // each document records the state of its source file, so an existing index is always reused even if a
+        // previous run did not complete
+        debug!("opening or creating tantivy index");
+        let index = Index::open_or_create(index_dir, schema.clone())?;
 
         // create the reader here
         let reader = index
@@ -199,387 +484,1828 @@ impl FulltextIndex {
             .reload_policy(ReloadPolicy::OnCommitWithDelay)
             .try_into()?;
 
-        let writer = index
-            .writer(50_000_000)
-            .expect("should be able to obtain an index writer");
+        // the writer's threads start right away, and are lowered in priority along with the thread creating it
+        let cpus = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        let writer_threads = resource_governor::writer_threads(&config, cpus);
+        let writer: IndexWriter = resource_governor::with_lowered_priority(&config, || {
+            index.writer_with_num_threads(writer_threads, config.writer_heap_bytes)
+        })?;
+        writer.set_merge_policy(compaction::merge_policy(&config));
+
+        // pick up where an interrupted run left off, the documents it committed are skipped as up to date
+        let checkpoint = IndexingCheckpoint::resume_or_start(IndexingCheckpoint::load(&index));
+        if checkpoint.files_indexed > 0 {
+            info!(
+                "resuming indexing run started at {} with {} files already indexed",
+                checkpoint.started_at, checkpoint.files_indexed
+            );
+        }
 
+        let searchable_index = match outdated_index {
+            Some(outdated_index) => outdated_index,
+            None => SearchableIndex::new(&index, &reader)?,
+        };
         let source_field = schema.get_field(SOURCE).unwrap();
         let indexed_at_field = schema.get_field(INDEXED_AT).unwrap();
         let path_field = schema.get_field(PATH).unwrap();
+        let volume_field = schema.get_field(VOLUME).unwrap();
+        let volume_path_field = schema.get_field(VOLUME_PATH).unwrap();
         let title_field = schema.get_field(TITLE).unwrap();
         let body_field = schema.get_field(BODY).unwrap();
+        let modified_field = schema.get_field(MODIFIED).unwrap();
+        let size_field = schema.get_field(SIZE).unwrap();
+        let content_hash_field = schema.get_field(CONTENT_HASH).unwrap();
+        let truncated_field = schema.get_field(TRUNCATED).unwrap();
+        let placeholder_field = schema.get_field(PLACEHOLDER).unwrap();
 
         Ok(Self {
             // status: IndexStatus::Initializing,
-            config,
-            last_indexing_epoch,
             index,
             reader,
             writer,
+            searchable_index: Arc::new(RwLock::new(searchable_index)),
+            rebuilding,
             request_receiver,
             results_sender,
             source_field,
             indexed_at_field,
             path_field,
+            volume_field,
+            volume_path_field,
             title_field,
             body_field,
+            modified_field,
+            size_field,
+            content_hash_field,
+            truncated_field,
+            placeholder_field,
             total_files: 0,
             out_of_date_files: 0,
+            removed_files: 0,
             files_indexed: 0,
+            checkpoint,
+            uncommitted_files: 0,
+            uncommitted_removals: 0,
+            uncommitted_documents: 0,
+            uncommitted_bytes: 0,
+            last_commit: Instant::now(),
+            error_ledger: ErrorLedger::load(&dirs.error_ledger),
+            dirs,
+            queued_reindexes: Vec::new(),
+            compaction_queued: false,
+            paused: false,
+            last_search_activity: None,
+            instance_lock,
+            volumes: Volumes::detect(&config.volume_labels),
+            config,
         })
     }
 
-    /// Clear the search index by removing the index directory and epoch file
-    pub fn clear_index() -> std::io::Result<()> {
-        let index_path = PROJECT_DIRS.cache_dir().join("tantivy");
-
-        // Remove the index directory if it exists
-        if index_path.exists() {
-            fs::remove_dir_all(&index_path)?;
-            info!("Removed index directory: {}", index_path.display());
-        }
-
-        // Remove the indexing epoch file if it exists
-        if INDEXING_EPOCH_PATH.exists() {
-            fs::remove_file(&*INDEXING_EPOCH_PATH)?;
-            info!(
-                "Removed indexing epoch file: {}",
-                INDEXING_EPOCH_PATH.display()
-            );
+    /// Answers searches against the index another process writes to, until the UI is gone
+    ///
+    /// The commits of the other process are picked up as they land, so results refresh just like they do while this
+    /// process indexes.
+    pub(crate) fn serve_read_only(
+        dirs: &IndexDirs,
+        volume_labels: VolumeLabels,
+        search_receiver: Receiver<SearchRequest>,
+        results_sender: Sender<IndexResults>,
+    ) -> Result<(), TantivyError> {
+        info!(
+            "another process is indexing, searching read-only in: {}",
+            dirs.index.to_string_lossy()
+        );
+        let searchable_index = SearchableIndex::open_read_only(&dirs.index)?;
+        let _watch_handle = searchable_index.watch_commits(results_sender.clone())?;
+        if let Err(e) = results_sender.send(IndexResults::Status(IndexStatus::IndexingElsewhere)) {
+            debug!("could not send status: {}", e);
         }
 
+        SearchService::new(
+            Arc::new(RwLock::new(searchable_index)),
+            volume_labels,
+            search_receiver,
+            results_sender,
+        )
+        .run();
         Ok(())
     }
 
-    /// Delete the given entry from the index by its path
-    ///
-    /// This takes an `&str` since the IndexEntry has not been constructed yet when this is called.
-    pub(crate) fn delete_entry(&self, entry_path: &str) {
-        let doc_path_term = Term::from_field_text(self.path_field, entry_path);
-        self.writer.delete_term(doc_path_term);
-    }
-
-    /// Loop over the paths that the collectors have found, see if they are indexed and up to date. If so pass them on to the loader.
-    pub(crate) fn filter_paths_to_update(
-        &mut self,
-        path_receiver: IndexPathReceiver,
-        path_converter_sender: IndexPathSender,
-    ) {
-        self.send_status(IndexStatus::FilteringPaths);
-        for index_path in path_receiver {
-            self.total_files += 1;
+    /// Picks the directory to write to, opening the index at `dirs.index` for searching if it has to be rebuilt
+    fn index_for_schema_version(
+        dirs: &IndexDirs,
+    ) -> Result<(PathBuf, Option<SearchableIndex>), TantivyError> {
+        let version = schema_version::read(&dirs.index);
+        if !Index::exists(&MmapDirectory::open(&dirs.index)?)? {
+            schema_version::write(&dirs.index)?;
+            return Ok((dirs.index.clone(), None));
+        }
+        // an index whose fields differ from the schema is rebuilt even if its version matches, opening it would fail
+        let schema_matches = Self::schema_matches(&dirs.index);
+        if version == SCHEMA_VERSION && schema_matches {
+            return Ok((dirs.index.clone(), None));
+        }
+        if !schema_matches {
+            warn!(
+                "the fields of the index in {} do not match its schema version {}",
+                dirs.index.to_string_lossy(),
+                version
+            );
+        }
 
-            // the file path on disk
-            // TODO we'll need to modify this or add a volume identifier if we index from more than one host
-            let path = index_path.path();
-            let path_str = path.to_string_lossy();
-            debug!("checking if {} needs to be updated...", path_str);
+        info!(
+            "index schema version {} is out of date, rebuilding it as version {} in: {}",
+            version,
+            SCHEMA_VERSION,
+            dirs.rebuild.to_string_lossy()
+        );
+        let outdated_index = match SearchableIndex::open_read_only(&dirs.index) {
+            Ok(outdated_index) => Some(outdated_index),
+            Err(e) => {
+                warn!("the old index cannot be searched while rebuilding: {}", e);
+                None
+            }
+        };
 
-            let mut entry_up_to_date = false;
+        // a rebuild that was interrupted resumes from its checkpoint, one for another version starts over
+        if dirs.rebuild.exists()
+            && (schema_version::read(&dirs.rebuild) != SCHEMA_VERSION
+                || Index::exists(&MmapDirectory::open(&dirs.rebuild)?)?
+                    && !Self::schema_matches(&dirs.rebuild))
+        {
+            fs::remove_dir_all(&dirs.rebuild)?;
+        }
+        create_dir_all(&dirs.rebuild)?;
+        schema_version::write(&dirs.rebuild)?;
 
-            // see if the document is already present in the index
-            if self.file_is_indexed(path) {
-                debug!("found document in index: {}", &path_str);
-                // if the last_indexing_epoch is Some and the file's last update time is later than it, then delete the entry from the index by path
-                entry_up_to_date = self.entry_up_to_date(&path);
+        Ok((dirs.rebuild.clone(), outdated_index))
+    }
 
-                // only check the update time if the item is already in the database
-                if !entry_up_to_date {
-                    self.delete_entry(&path_str)
-                }
-            }
+    /// Checks if the index in `index_dir` was built with the fields of `tantivy_schema`
+    fn schema_matches(index_dir: &Path) -> bool {
+        Index::open_in_dir(index_dir).is_ok_and(|index| index.schema() == tantivy_schema())
+    }
 
-            // if the entry does not need an update, continue with the next item
-            if entry_up_to_date {
-                continue;
-            };
+    /// Finishes a swap of a rebuilt index that was interrupted between moving the old index out and the new one in,
+    /// then deletes the old index
+    fn recover_index_swap(dirs: &IndexDirs) -> std::io::Result<()> {
+        if !dirs.index.exists() && dirs.retired.exists() && dirs.rebuild.exists() {
+            info!("completing the swap of the rebuilt index");
+            fs::rename(&dirs.rebuild, &dirs.index)?;
+        }
 
-            self.out_of_date_files += 1;
-            path_converter_sender
-                .send(index_path)
-                .expect("should be able to send path to converter");
+        if dirs.retired.exists() {
+            fs::remove_dir_all(&dirs.retired)?;
+            info!(
+                "removed retired index directory: {}",
+                dirs.retired.display()
+            );
         }
 
-        drop(path_converter_sender);
+        Ok(())
     }
 
-    pub(crate) fn start_collectors(&mut self) -> Receiver<IndexEntry> {
-        self.send_status(IndexStatus::CollectingPaths);
+    /// Checks if the index is being rebuilt for a new schema version, see `finish_rebuild`
+    pub(crate) fn is_rebuilding(&self) -> bool {
+        self.rebuilding
+    }
 
-        let (path_sender, path_receiver) = channel();
-        let (path_converter_sender, path_converter_receiver) = channel();
-        let (entry_sender, entry_receiver) = channel();
+    /// Moves a completely rebuilt index into place and reopens it, the old index is deleted
+    ///
+    /// Each step is a rename, so an interrupted swap leaves either index intact and is completed on the next launch.
+    pub(crate) fn finish_rebuild(self) -> Result<Self, IndexingError> {
+        let FulltextIndex {
+            config,
+            dirs,
+            request_receiver,
+            results_sender,
+            index,
+            reader,
+            writer,
+            searchable_index,
+            instance_lock,
+            ..
+        } = self;
 
-        // spawn loader channels here
-        let aichat_session_files = AichatSessionFiles::new(&self.config.aichat_session_files);
-        let markdown_files = MarkdownFiles::new(&self.config.markdown_files);
-        let web_scrapbook_files = WebScrapbookFiles::new(&self.config.web_scrapbook_files);
+        // close the rebuilt index first, so no file is written while its directory moves. The search thread keeps
+        // the old index open until the reopened one is swapped in below.
+        writer.wait_merging_threads()?;
+        drop((index, reader));
 
-        // start collecting various entries in separate threads here
-        info!("spawning aichat session file entry collection...");
-        let aichat_session_path_sender = path_sender.clone();
-        spawn(move || {
-            aichat_session_files.collect_entries(aichat_session_path_sender);
-        });
+        info!(
+            "swapping in the rebuilt index from: {}",
+            dirs.rebuild.to_string_lossy()
+        );
+        Self::recover_index_swap(&dirs)?;
+        fs::rename(&dirs.index, &dirs.retired)?;
+        fs::rename(&dirs.rebuild, &dirs.index)?;
+        Self::recover_index_swap(&dirs)?;
 
-        info!("spawning markdown file entry collection...");
-        let markdown_path_sender = path_sender.clone();
-        spawn(move || {
-            markdown_files.collect_entries(markdown_path_sender);
-        });
+        // the search thread holds on to the shared index, so the reopened one is swapped into it
+        let mut fulltext_index = Self::open(
+            config,
+            dirs,
+            request_receiver,
+            results_sender,
+            instance_lock,
+        )?;
+        let reopened_index = SearchableIndex::new(&fulltext_index.index, &fulltext_index.reader)?;
+        *searchable_index
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = reopened_index;
+        fulltext_index.searchable_index = searchable_index;
+
+        // the results of the old index are stale now
+        let opstamp = fulltext_index.index.load_metas()?.opstamp;
+        fulltext_index.announce_commit(opstamp);
+        Ok(fulltext_index)
+    }
 
-        info!("spawning web scrapbook file entry collection...");
-        let web_scrapbook_path_sender = path_sender.clone();
-        spawn(move || {
-            web_scrapbook_files.collect_entries(web_scrapbook_path_sender);
-        });
+    /// Tells the search thread and the UI that searches see a new commit
+    fn announce_commit(&self, opstamp: Opstamp) {
+        self.searchable_index
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .record_commit(opstamp);
+        if let Err(e) = self.results_sender.send(IndexResults::Committed(opstamp)) {
+            debug!("could not send commit: {}", e);
+        }
+    }
 
-        // dropping the original path sender so we don't hang the program waiting for more paths
-        drop(path_sender);
+    /// Starts answering searches on a thread of their own, they run against the same index this writes to
+    pub(crate) fn spawn_search_service(&self, search_receiver: Receiver<SearchRequest>) {
+        let search_service = SearchService::new(
+            self.searchable_index.clone(),
+            self.config.volume_labels.clone(),
+            search_receiver,
+            self.results_sender.clone(),
+        );
+        spawn(move || search_service.run());
+    }
 
-        // this is basically happening synchronously here since it depends on the tantivy index
-        // might need to wrap the indexes in Arcs and possibly Mutexes to make this asynchronous
-        info!("filtering paths...");
-        self.filter_paths_to_update(path_receiver, path_converter_sender);
+    /// Clear the search index of the active profile by removing the index directory
+    pub fn clear_index(config: &Conf) -> std::io::Result<()> {
+        let dirs = Profile::active().index_dirs(config);
 
-        info!("spawning path to entry converter...");
-        spawn(move || Self::convert_paths_to_entries(path_converter_receiver, entry_sender));
+        // Remove the index directories if they exist, including a rebuild in progress
+        for index_path in [&dirs.index, &dirs.rebuild, &dirs.retired] {
+            if index_path.exists() {
+                fs::remove_dir_all(index_path)?;
+                info!("Removed index directory: {}", index_path.display());
+            }
+        }
 
-        entry_receiver
+        Ok(())
     }
 
-    /// Updates the fulltext index by reading the IndexEntries from the receiver
-    pub(crate) fn update(
-        &mut self,
-        entry_receiver: Receiver<IndexEntry>,
-    ) -> Result<(), TantivyError> {
-        info!("updating the fulltext index...");
-
-        // setting up the indexing epoch
-        let indexing_epoch_file = AtomicFile::new(&*INDEXING_EPOCH_PATH, AllowOverwrite);
-        let indexing_epoch = OffsetDateTime::now_utc().unix_timestamp();
+    /// Delete the given entry from the index by the path of its file
+    ///
+    /// This takes a path since the IndexEntry has not been constructed yet when this is called.
+    pub(crate) fn delete_entry(&self, path: &Path) {
+        self.delete_volume_path(&self.volumes.volume_path(path));
+    }
 
-        self.send_status(IndexStatus::UpdatingIndex {
-            indexed: self.files_indexed,
-            total: self.out_of_date_files,
-            committing_updates: false,
-            file_path: "".to_owned(),
-        });
+    /// Deletes the documents of a file by its volume path, which works whether its volume is available or not
+    fn delete_volume_path(&self, volume_path: &str) {
+        self.writer
+            .delete_term(Term::from_field_text(self.volume_path_field, volume_path));
+    }
 
-        // loop through all of the IndexEntries on the receiver and add them to the index
-        info!("starting entry indexing loop...");
-        let mut indexing_timer = Instant::now();
-        loop {
-            // TODO consider switching to crossbeam's select macro
-            // TODO count the entries received or the time taken so we can commit in batches
+    /// The term that identifies the documents of a file, see `Volumes::volume_path`
+    fn volume_path_term(&self, path: &Path) -> Term {
+        Term::from_field_text(self.volume_path_field, &self.volumes.volume_path(path))
+    }
 
-            if indexing_timer.elapsed().as_secs() > 6 {
-                self.send_status(IndexStatus::UpdatingIndex {
-                    indexed: self.files_indexed,
-                    total: self.out_of_date_files,
-                    committing_updates: true,
-                    file_path: "".to_owned(),
-                });
+    /// Looks up which volumes are available, so files are identified by the volumes they are on right now
+    fn detect_volumes(&mut self) {
+        self.volumes = Volumes::detect(&self.config.volume_labels);
+    }
 
-                // commit the changes so that searchers can see the changes
-                info!("committing changes to fulltext index...");
-                self.writer
-                    .commit()
-                    .expect("should be able to commit index updates");
-
-                // reset the commit interval timer
-                indexing_timer = Instant::now();
-            }
-
-            // check for new entries in need of indexing
-            match entry_receiver.try_recv() {
-                // status updates are sent from within update_entry
-                Ok(entry) => self.update_entry(&entry),
-                Err(e) => match e {
-                    std::sync::mpsc::TryRecvError::Empty => {
-                        // check for new search requests
-                        if let Ok(request) = self.request_receiver.try_recv() {
-                            self.search(request)
-                        } else {
-                            thread::sleep(Duration::from_millis(20));
-                        }
-                        continue;
-                    }
-                    std::sync::mpsc::TryRecvError::Disconnected => break,
-                },
-            }
+    /// Checks a path that the collectors have found and returns whether it needs to be converted, along with the
+    /// volume path of the file
+    ///
+    /// Changed entries are deleted from the index once the path is handed to a converter, the converted entries
+    /// replace them.
+    fn filter_path(
+        &mut self,
+        index_path: &IndexPath,
+        pipeline: &mut IndexingPipeline,
+    ) -> Option<(PathState, String)> {
+        // when reindexing, the collectors report paths outside of the target as well
+        if let Some(scope) = &pipeline.scope
+            && !scope.contains(index_path.source(), index_path.path())
+        {
+            return None;
         }
 
-        self.send_status(IndexStatus::UpdatingIndex {
-            indexed: self.files_indexed,
-            total: self.out_of_date_files,
-            committing_updates: true,
-            file_path: "".to_owned(),
-        });
+        // the file path on disk, the documents of the file are looked up by its volume path
+        let path = index_path.path();
+        let path_str = path.to_string_lossy();
+        pipeline
+            .reported_paths
+            .insert((index_path.source().to_owned(), path_str.to_string()));
+
+        let volume_path = self.volumes.volume_path(path);
+        let claim = pipeline.claims.entry(volume_path.clone()).or_default();
+        let Some(took_over) = self.claim(&volume_path, claim, index_path) else {
+            debug!(
+                "already reported under another path or by another source: {}",
+                path_str
+            );
+            return None;
+        };
+        // the conversion of the source that reported the file before is dropped
+        if took_over && let Some(sequence) = claim.sequence.take() {
+            pipeline.stale_sequences.insert(sequence);
+        }
 
-        // commit the changes so that searchers can see the changes
-        info!("committing changes to fulltext index...");
-        self.writer
-            .commit()
-            .expect("should be able to commit index updates");
+        self.total_files += 1;
+        debug!("checking if {} needs to be updated...", path_str);
+
+        let path_state =
+            self.converter_path_state(path, claim, took_over, pipeline.scope.is_some());
+        match path_state {
+            // if the entry does not need an update, it is not passed on to the converters
+            PathState::UpToDate => return None,
+            // the entry is deleted once the path is handed to a converter, so it stays searchable until then
+            PathState::Changed => {}
+            // a file that failed before would fail the same way again, until it changes
+            PathState::New
+                if self
+                    .error_ledger
+                    .failed_unchanged(index_path.source(), &path_str) =>
+            {
+                debug!("skipping unchanged file that failed before: {}", path_str);
+                return None;
+            }
+            PathState::New => pipeline.new_paths.push(volume::canonical_path(path)),
+        }
 
-        // write the epoch of the last indexing to use for incremental updates
-        info!(
-            "writing indexing epoch {} to: {}",
-            indexing_epoch,
-            INDEXING_EPOCH_PATH.to_string_lossy()
-        );
-        indexing_epoch_file
-            .write(|f| f.write_all(indexing_epoch.to_string().as_bytes()))
-            .expect("should be able to write to the indexing epoch file");
+        self.out_of_date_files += 1;
+        Some((path_state, volume_path))
+    }
 
-        self.send_status(IndexStatus::UpToDate);
+    /// Records that a source reported a file, returns whether the source converts it and if so, whether it takes over
+    /// from a source that reported it earlier in this run
+    ///
+    /// The first of the reporting sources in `SOURCE_PRIORITY` converts a file. A source waits for one that comes
+    /// before it and is recorded on the documents of the file, that source most likely reports the file as well.
+    /// Whoever converts the file once every source reported it is settled by `settle_claims`.
+    fn claim(
+        &self,
+        volume_path: &str,
+        claim: &mut SourceClaim,
+        index_path: &IndexPath,
+    ) -> Option<bool> {
+        let source = index_path.source();
+        if claim
+            .reports
+            .iter()
+            .any(|reported| reported.source() == source)
+        {
+            return None;
+        }
+        if claim.reports.is_empty() {
+            claim.recorded_sources = self.shared_sources(volume_path, source);
+        }
+        claim.reports.push(index_path.clone());
 
-        Ok(())
+        if let Some(converter) = claim.converter
+            && source_priority(converter) <= source_priority(source)
+        {
+            return None;
+        }
+        if claim
+            .recorded_sources
+            .iter()
+            .any(|recorded| source_priority(recorded) < source_priority(source))
+        {
+            return None;
+        }
+        Some(claim.converter.replace(source).is_some())
     }
 
-    pub(crate) fn update_search_results(&self) {
-        while let Ok(request) = self.request_receiver.recv() {
-            self.search(request)
+    /// The state of a file for the source that converts it, see `claim`
+    ///
+    /// Documents that another source converted, that an earlier conversion of this run added, or that are within a
+    /// reindex are converted again.
+    fn converter_path_state(
+        &self,
+        path: &Path,
+        claim: &SourceClaim,
+        took_over: bool,
+        reindexing: bool,
+    ) -> PathState {
+        if took_over {
+            return PathState::Changed;
+        }
+        let path_state = self.path_state(path);
+        let indexed_converter = claim
+            .recorded_sources
+            .iter()
+            .min_by_key(|recorded| source_priority(recorded));
+        let converted_by_another =
+            indexed_converter.is_some_and(|converter| Some(converter.as_str()) != claim.converter);
+        if path_state == PathState::UpToDate && (converted_by_another || reindexing) {
+            return PathState::Changed;
         }
+        path_state
     }
 
-    fn update_entry(&mut self, entry: &IndexEntry) {
-        self.send_status(IndexStatus::UpdatingIndex {
-            indexed: self.files_indexed,
-            total: self.out_of_date_files,
-            committing_updates: false,
-            file_path: entry.path().to_owned(),
-        });
-
-        // we were using the `doc!()` macro, but it doesn't seem to play well with date fields
-        let mut tantivy_doc = TantivyDocument::default();
-        tantivy_doc.add_text(self.source_field, entry.source());
-        tantivy_doc.add_date(self.indexed_at_field, *entry.indexed_at());
-        tantivy_doc.add_text(self.path_field, entry.path());
-        tantivy_doc.add_text(self.title_field, entry.title());
-        tantivy_doc.add_text(self.body_field, entry.body());
-
-        // add the document to the index
-        match self.writer.add_document(tantivy_doc) {
-            Ok(_) => {
-                info!("added document to index: {}", &entry.path());
-                self.files_indexed += 1;
+    /// Settles which source converts each file that several sources reported, and records every one of them on its
+    /// documents, once they are committed
+    ///
+    /// A file that waited for a source recorded on its documents is converted by the first of the sources that
+    /// reported it, if the recorded one did not. A reindex keeps the sources outside of it that are recorded.
+    fn settle_claims(
+        &mut self,
+        claims: &SourceClaims,
+        scope: Option<&ReindexTarget>,
+    ) -> Result<(), IndexingError> {
+        let mut shared_claims: Vec<(&String, &SourceClaim, Vec<String>)> = Vec::new();
+        for (volume_path, claim) in claims {
+            if claim.reports.len() < 2 && claim.recorded_sources.is_empty() {
+                continue;
             }
-            // TODO switch to the tracing crate
-            Err(e) => {
-                warn!("could not index document: {}: {}", &entry.path(), e)
+            let mut sources: Vec<String> = claim
+                .reports
+                .iter()
+                .map(|reported| reported.source().to_owned())
+                .collect();
+            if let Some(scope) = scope {
+                for recorded in &claim.recorded_sources {
+                    if !scope.includes_source(recorded) && !sources.contains(recorded) {
+                        sources.push(recorded.clone());
+                    }
+                }
             }
+            sources.sort_by_key(|source| source_priority(source));
+            shared_claims.push((volume_path, claim, sources));
+        }
+        if shared_claims.is_empty() {
+            return Ok(());
         }
-    }
 
-    fn convert_paths_to_entries(path_receiver: IndexPathReceiver, entry_sender: IndexEntrySender) {
-        for index_path in path_receiver {
-            debug!("attempting to convert {} to entry...", index_path);
-            let new_entry = match index_path {
-                IndexPath::MarkdownFile(path_buf) => {
-                    MarkdownFiles::convert_path_to_entry(&path_buf)
-                }
-                IndexPath::WebScrapBookFile(path_buf) => {
-                    WebScrapbookFiles::convert_path_to_entry(&path_buf)
-                }
-                IndexPath::AichatSessionFile(path_buf) => {
-                    AichatSessionFiles::convert_path_to_entry(&path_buf)
-                }
+        let mut project_lookup = ProjectLookup::default();
+        let size_limits = SizeLimits::new(&self.config);
+        for (volume_path, claim, sources) in &shared_claims {
+            let Some(first_report) = claim
+                .reports
+                .iter()
+                .min_by_key(|reported| source_priority(reported.source()))
+            else {
+                continue;
             };
+            // a source outside of a reindex comes first, its documents are kept
+            if sources[0] != first_report.source() || claim.converter == Some(first_report.source())
+            {
+                continue;
+            }
 
-            // TODO maybe send these in a Box or Arc to reduce memory allocations
-            entry_sender
-                .send(new_entry)
-                .expect("should be able to send new entry to indexer");
+            debug!(
+                "converting {} with {}, the first source that reported it",
+                volume_path,
+                first_report.source()
+            );
+            self.delete_volume_path(volume_path);
+            let result = Self::convert_index_path(first_report, &mut project_lookup, &size_limits);
+            self.index_conversion(result);
         }
+        self.commit()?;
 
-        drop(entry_sender);
-    }
-
-    pub(crate) fn search(&self, request: IndexRequest) {
-        let searcher = self.reader.searcher();
-        let title = self.title_field;
-        let body = self.body_field;
-        let default_fields = vec![title, body];
-
-        // setup the query here
-        let mut query_parser = QueryParser::for_index(&self.index, default_fields.clone());
+        for (volume_path, _claim, sources) in shared_claims {
+            let indexed_sources = self.indexed_sources(volume_path);
+            // a file that failed to convert has no documents to record them on
+            if indexed_sources.is_empty() || indexed_sources == sources {
+                continue;
+            }
 
-        if request.query_conjunction {
-            query_parser.set_conjunction_by_default();
+            debug!("recording sources {:?} of {}", sources, volume_path);
+            self.rewrite_documents(volume_path, &[self.source_field], |tantivy_doc| {
+                for source in &sources {
+                    tantivy_doc.add_text(self.source_field, source);
+                }
+            });
         }
+        Ok(())
+    }
 
-        // set fields fuzzy here
-        // TODO add advanced search config where individual field can have its fuzziness set independently
-        if request.fuzziness > 0 {
-            for field in &default_fields {
-                query_parser.set_field_fuzzy(*field, true, request.fuzziness, true);
-            }
+    /// Checks whether a path is in the index and if so, whether it changed since it was indexed
+    fn path_state(&self, path: &Path) -> PathState {
+        // see if the document is already present in the index
+        if !self.file_is_indexed(path) {
+            return PathState::New;
         }
 
-        // parse the query here
-        let (query, query_errors) = if request.lenient {
-            query_parser.parse_query_lenient(&request.query)
+        debug!("found document in index: {}", path.to_string_lossy());
+        // compare the file on disk against the state it was in when it was indexed
+        if self.entry_up_to_date(path) {
+            PathState::UpToDate
         } else {
-            match query_parser.parse_query(&request.query) {
-                Ok(query) => (query, vec![]),
-                // if we have an error in non-lenient parsing, return with no results
-                Err(error) => {
-                    self.results_sender
-                        .send(IndexResults::SearchResults {
-                            request_id: request.request_id,
-                            // TODO return the actual opstamp
-                            opstamp: 0,
-                            results: Ok((vec![], vec![error])),
-                        })
-                        .expect("should be able to send results");
+            PathState::Changed
+        }
+    }
 
-                    return;
+    /// Every distinct volume path in the index, read from the term dictionary so no stored documents are loaded
+    ///
+    /// This may include volume paths whose documents were deleted but not merged away yet.
+    fn indexed_volume_paths(&self) -> BTreeSet<String> {
+        self.indexed_volume_paths_with_prefix("")
+    }
+
+    /// The indexed volume paths that start with `prefix`, read from a range of the term dictionary
+    fn indexed_volume_paths_with_prefix(&self, prefix: &str) -> BTreeSet<String> {
+        let mut indexed_volume_paths = BTreeSet::new();
+        for segment_reader in self.reader.searcher().segment_readers() {
+            let inverted_index = match segment_reader.inverted_index(self.volume_path_field) {
+                Ok(inverted_index) => inverted_index,
+                Err(e) => {
+                    warn!("could not read the volume path terms: {}", e);
+                    continue;
+                }
+            };
+            let mut terms = match inverted_index.terms().range().ge(prefix).into_stream() {
+                Ok(terms) => terms,
+                Err(e) => {
+                    warn!("could not read the volume path terms: {}", e);
+                    continue;
                 }
+            };
+            // the terms are sorted, so the ones with the prefix are next to each other
+            while terms.advance() && terms.key().starts_with(prefix.as_bytes()) {
+                indexed_volume_paths.insert(String::from_utf8_lossy(terms.key()).into_owned());
             }
-        };
+        }
+        indexed_volume_paths
+    }
+
+    /// The sources of the first live document for a volume path, empty if there is none
+    fn indexed_sources(&self, volume_path: &str) -> Vec<String> {
+        self.first_document(volume_path)
+            .map(|doc| {
+                doc.get_all(self.source_field)
+                    .filter_map(|v| v.as_str())
+                    .map(|s| s.to_owned())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
 
-        // perform the search
-        let top_docs = searcher
-            .search(&query, &TopDocs::with_limit(request.limit))
-            .unwrap();
-
-        // create a snippet generator so we can draw snippets with highlights
-        let snippet_generator = SnippetGenerator::create(&searcher, &query, body).unwrap();
-
-        let mut documents: Vec<SearchResult> = Vec::default();
-        for (_score, doc_address) in top_docs {
-            let retrieved_doc: TantivyDocument = searcher.doc(doc_address).unwrap();
-            let snippet = snippet_generator.snippet_from_doc(&retrieved_doc);
-            documents.push(SearchResult::new(self, retrieved_doc, snippet));
-        }
-
-        let num_hits = documents.len();
-        match self.results_sender.send(IndexResults::SearchResults {
-            request_id: request.request_id,
-            // TODO return the actual opstamp
-            opstamp: 0,
-            results: Ok((documents, query_errors)),
-        }) {
-            Ok(_) => info!(
-                "sending {} results for query {}: {}",
-                num_hits, request.request_id, request.query
+    /// The sources recorded on the documents of a file if there is any besides `source`, otherwise nothing
+    ///
+    /// The stored documents are only loaded for files that several sources reported.
+    fn shared_sources(&self, volume_path: &str, source: &str) -> Vec<String> {
+        let query = BooleanQuery::new(vec![
+            (
+                Occur::Must,
+                Box::new(TermQuery::new(
+                    Term::from_field_text(self.volume_path_field, volume_path),
+                    IndexRecordOption::Basic,
+                )),
             ),
-            Err(e) => warn!("should be able to send results: {}", e),
-        };
+            (Occur::Must, Box::new(self.other_sources_query(source))),
+        ]);
+        match self.reader.searcher().search(&query, &Count) {
+            Ok(0) => Vec::new(),
+            Ok(_) => self.indexed_sources(volume_path),
+            Err(e) => {
+                warn!("error searching for document: {}", e);
+                Vec::new()
+            }
+        }
     }
 
-    pub(crate) fn file_is_indexed(&self, path: &Path) -> bool {
-        let schema = self.index.schema();
-        let path_field = schema.get_field(PATH).unwrap();
+    /// Matches documents with any source besides `source`
+    fn other_sources_query(&self, source: &str) -> BooleanQuery {
+        BooleanQuery::new(
+            SOURCE_PRIORITY
+                .iter()
+                .filter(|other_source| **other_source != source)
+                .map(|other_source| -> (Occur, Box<dyn Query>) {
+                    (
+                        Occur::Should,
+                        Box::new(TermQuery::new(
+                            Term::from_field_text(self.source_field, other_source),
+                            IndexRecordOption::Basic,
+                        )),
+                    )
+                })
+                .collect(),
+        )
+    }
 
-        // TODO may want to call a custom method so this doesn't get out of sync between indexing and our search here
-        let query_text = &path.to_string_lossy();
+    /// The first live document for a volume path, if there is one
+    fn first_document(&self, volume_path: &str) -> Option<TantivyDocument> {
+        let searcher = self.reader.searcher();
         let query = TermQuery::new(
-            Term::from_field_text(path_field, query_text),
+            Term::from_field_text(self.volume_path_field, volume_path),
             IndexRecordOption::Basic,
         );
+        let (_score, doc_address) = searcher
+            .search(&query, &TopDocs::with_limit(1))
+            .ok()?
+            .into_iter()
+            .next()?;
+        searcher.doc(doc_address).ok()
+    }
+
+    /// Deletes the entries for paths that the collectors no longer report
+    ///
+    /// Entries on a volume that is not available are kept and searchable, they are offline rather than deleted.
+    /// Entries are also kept when a root of their source is missing e.g. the mount point of an unlabelled drive, so
+    /// it does not get wiped. A new path whose contents hash the same as a removed one is treated as a move, and the
+    /// invocation history of the old path is pointed at the new one.
+    fn remove_missing_entries(
+        &mut self,
+        reported_paths: &HashSet<(String, String)>,
+        claims: &SourceClaims,
+        new_paths: &[PathBuf],
+    ) {
+        self.send_status(IndexStatus::RemovingMissingEntries);
+        info!("removing entries for deleted files...");
+
+        let source_roots = self.config.source_roots();
+        let mut removed_fingerprints = Vec::new();
+        let mut offline_files = 0;
+        for volume_path in self.indexed_volume_paths() {
+            if claims.contains_key(&volume_path) {
+                continue;
+            }
+
+            // skip paths whose documents have all been deleted already
+            let sources = self.indexed_sources(&volume_path);
+            if sources.is_empty() {
+                continue;
+            }
+
+            let Some(path) = self.volumes.resolve(&volume_path) else {
+                debug!("volume offline, keeping {}", volume_path);
+                offline_files += 1;
+                continue;
+            };
+            let indexed_path = path.to_string_lossy().to_string();
+            let root_missing = sources.iter().any(|source| {
+                source_roots.get(source.as_str()).is_some_and(|roots| {
+                    roots.iter().any(|root| {
+                        !root.exists() && path.starts_with(volume::canonical_path(root))
+                    })
+                })
+            });
+            if root_missing {
+                debug!("root missing, keeping {}", indexed_path);
+                continue;
+            }
+
+            if let Some(fingerprint) = self.indexed_fingerprint(&volume_path)
+                && fingerprint.content_hash.is_some()
+            {
+                removed_fingerprints.push((indexed_path.clone(), fingerprint));
+            }
+
+            info!("removing deleted file from index: {}", indexed_path);
+            self.delete_volume_path(&volume_path);
+            self.removed_files += 1;
+            self.uncommitted_removals += 1;
+        }
+
+        info!(
+            "removed {} deleted files from the index, kept {} files on offline volumes",
+            self.removed_files, offline_files
+        );
+        self.error_ledger.retain_reported(reported_paths);
+
+        self.detect_moves(removed_fingerprints, new_paths);
+    }
+
+    /// Deletes the entries for a path that no longer exists, and for everything below it if it was a directory
+    ///
+    /// Returns the fingerprints of the removed files so moves can be detected.
+    fn remove_entries_under(&mut self, removed_path: &Path) -> Vec<(String, FileFingerprint)> {
+        // the volume path of the removed path is a prefix of the volume paths of everything below it, so only those
+        // terms are read rather than every path in the index
+        let removed_volume_path = self.volumes.volume_path(removed_path);
+        let children_prefix = match removed_volume_path.ends_with('/') {
+            true => removed_volume_path.clone(),
+            false => format!("{}/", removed_volume_path),
+        };
+        let mut removed_fingerprints = Vec::new();
+        for volume_path in self.indexed_volume_paths_with_prefix(&removed_volume_path) {
+            // skip siblings that share the prefix e.g. `notes.md.bak` when `notes.md` was removed
+            if volume_path != removed_volume_path && !volume_path.starts_with(&children_prefix) {
+                continue;
+            }
+
+            let indexed_path = self
+                .volumes
+                .resolve(&volume_path)
+                .map(|path| path.to_string_lossy().to_string())
+                .unwrap_or_else(|| volume_path.clone());
+            if let Some(fingerprint) = self.indexed_fingerprint(&volume_path)
+                && fingerprint.content_hash.is_some()
+            {
+                removed_fingerprints.push((indexed_path.clone(), fingerprint));
+            }
+
+            info!("removing deleted file from index: {}", indexed_path);
+            self.delete_volume_path(&volume_path);
+            self.removed_files += 1;
+            self.uncommitted_removals += 1;
+        }
+        removed_fingerprints
+    }
+
+    /// Matches new paths against removed ones by content hash and moves the invocation history of each match
+    fn detect_moves(
+        &self,
+        mut removed_fingerprints: Vec<(String, FileFingerprint)>,
+        new_paths: &[PathBuf],
+    ) {
+        if removed_fingerprints.is_empty() {
+            return;
+        }
+
+        // only hash the new files whose size matches a removed one
+        let mut renames = HashMap::new();
+        for new_path in new_paths {
+            let Some(current) = FileFingerprint::stat(new_path) else {
+                continue;
+            };
+            if !removed_fingerprints
+                .iter()
+                .any(|(_, removed)| removed.size == current.size)
+            {
+                continue;
+            }
+
+            let Ok(content_hash) = FileFingerprint::hash_file(new_path) else {
+                continue;
+            };
+            if let Some(index) = removed_fingerprints.iter().position(|(_, removed)| {
+                removed.size == current.size && removed.content_hash == Some(content_hash)
+            }) {
+                let (old_path, _) = removed_fingerprints.swap_remove(index);
+                let new_path = new_path.to_string_lossy().to_string();
+                info!("detected move from {} to {}", old_path, new_path);
+                renames.insert(old_path, new_path);
+            }
+        }
+
+        if !renames.is_empty() {
+            match InvocationList::rename_paths(&self.dirs.cache, &renames) {
+                Ok(count) => info!("moved {} invocations to their new paths", count),
+                Err(e) => warn!("could not move invocations to their new paths: {}", e),
+            }
+        }
+    }
+
+    /// Starts the collectors and the conversion workers, `update` filters the paths in between and indexes the
+    /// converted entries
+    ///
+    /// All channels are bounded so that memory use stays flat no matter how many files there are.
+    pub(crate) fn start_collectors(&mut self) -> IndexingPipeline {
+        self.start_collectors_for(None)
+    }
+
+    /// Starts the collectors that may report paths in `scope`, or all of them if there is none
+    fn start_collectors_for(&mut self, scope: Option<ReindexTarget>) -> IndexingPipeline {
+        self.send_status(IndexStatus::CollectingPaths);
+        self.detect_volumes();
+        let collect = |source: &str| scope.as_ref().is_none_or(|s| s.includes_source(source));
+
+        let workers = match self.config.conversion_workers {
+            0 => thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            workers => workers,
+        };
+        let size_limits = SizeLimits::new(&self.config);
+
+        let (path_sender, path_receiver) = bounded(PATH_CHANNEL_CAPACITY);
+        let (conversion_sender, conversion_receiver) = bounded(workers * 2);
+        let (entry_sender, entry_receiver) = bounded(workers * 2);
+
+        // spawn loader channels here
+        let aichat_session_files = AichatSessionFiles::new(&self.config.aichat_session_files);
+        let markdown_files = MarkdownFiles::new(&self.config.markdown_files);
+        let web_scrapbook_files = WebScrapbookFiles::new(&self.config.web_scrapbook_files);
+        let image_files = ImageFiles::new(&self.config.image_files);
+        let music_files =
+            MusicFiles::new(&self.config.music_files, self.config.music_album_documents);
+        let reading_highlights = ReadingHighlights::new(&self.config.reading_highlight_files);
+        let code_symbols = CodeSymbols::new(&self.config.code_symbol_files);
+        let projects = Projects::new(&self.config.project_dirs);
+
+        // start collecting various entries in separate threads here
+        if collect(AICHAT_SESSION_FILES_SOURCE) {
+            info!("spawning aichat session file entry collection...");
+            let aichat_session_path_sender = path_sender.clone();
+            spawn(move || {
+                aichat_session_files.collect_entries(aichat_session_path_sender);
+            });
+        }
+
+        if collect(MARKDOWN_FILES_SOURCE) {
+            info!("spawning markdown file entry collection...");
+            let markdown_path_sender = path_sender.clone();
+            spawn(move || {
+                markdown_files.collect_entries(markdown_path_sender);
+            });
+        }
+
+        if collect(WEB_SCRAPBOOK_FILES_SOURCE) {
+            info!("spawning web scrapbook file entry collection...");
+            let web_scrapbook_path_sender = path_sender.clone();
+            spawn(move || {
+                web_scrapbook_files.collect_entries(web_scrapbook_path_sender);
+            });
+        }
+
+        if collect(IMAGE_FILES_SOURCE) {
+            info!("spawning image file entry collection...");
+            let image_path_sender = path_sender.clone();
+            spawn(move || {
+                image_files.collect_entries(image_path_sender);
+            });
+        }
+
+        if collect(MUSIC_FILES_SOURCE) {
+            info!("spawning music file entry collection...");
+            let music_path_sender = path_sender.clone();
+            spawn(move || {
+                music_files.collect_entries(music_path_sender);
+            });
+        }
+
+        if collect(READING_HIGHLIGHTS_SOURCE) {
+            info!("spawning reading highlight entry collection...");
+            let reading_highlights_path_sender = path_sender.clone();
+            spawn(move || {
+                reading_highlights.collect_entries(reading_highlights_path_sender);
+            });
+        }
+
+        if collect(CODE_SYMBOLS_SOURCE) {
+            info!("spawning code symbol entry collection...");
+            let code_symbols_path_sender = path_sender.clone();
+            spawn(move || {
+                code_symbols.collect_entries(code_symbols_path_sender);
+            });
+        }
+
+        if collect(PROJECTS_SOURCE) {
+            info!("spawning project entry collection...");
+            let projects_path_sender = path_sender.clone();
+            spawn(move || {
+                projects.collect_entries(projects_path_sender);
+            });
+        }
+
+        // dropping the original path sender so we don't hang the program waiting for more paths
+        drop(path_sender);
+
+        info!("spawning {} path to entry converters...", workers);
+        for _ in 0..workers {
+            let conversion_receiver = conversion_receiver.clone();
+            let entry_sender = entry_sender.clone();
+            let size_limits = size_limits.clone();
+            spawn(move || {
+                Self::convert_paths_to_entries(conversion_receiver, entry_sender, size_limits)
+            });
+        }
+
+        // the workers hold the only copies now, so the entry channel closes once they are all done
+        drop(conversion_receiver);
+        drop(entry_sender);
+
+        IndexingPipeline {
+            path_receiver: Some(path_receiver),
+            conversion_sender: Some(conversion_sender),
+            entry_receiver,
+            queued_paths: PathQueue::new(PRIORITY_WINDOW),
+            priorities: PathPriorities::load(&self.dirs.cache),
+            next_sequence: 0,
+            reorder_buffer: ReorderBuffer::new(workers * 4),
+            converters_done: false,
+            reported_paths: HashSet::new(),
+            claims: SourceClaims::new(),
+            stale_sequences: HashSet::new(),
+            new_paths: Vec::new(),
+            scope,
+        }
+    }
+
+    /// Moves the pipeline along by one step, returns false if there was nothing to do
+    ///
+    /// Paths are filtered here since that needs the index. The converted entries are indexed in the order their
+    /// paths were handed to the converters, so the documents of each source arrive in a stable order.
+    fn advance_pipeline(&mut self, pipeline: &mut IndexingPipeline) -> bool {
+        // index converted entries first, this frees up the converters
+        match pipeline.entry_receiver.try_recv() {
+            Ok((sequence, entries)) => {
+                pipeline.reorder_buffer.insert(sequence, entries);
+            }
+            // the entry channel only closes once every path was converted
+            Err(TryRecvError::Disconnected) => pipeline.converters_done = true,
+            Err(TryRecvError::Empty) => {}
+        }
+
+        let mut indexed_entries = false;
+        while let Some((sequence, result)) = pipeline.reorder_buffer.pop_next() {
+            // status updates are sent from within index_conversion
+            if !pipeline.stale_sequences.remove(&sequence) {
+                self.index_conversion(result);
+            }
+            indexed_entries = true;
+        }
+        if indexed_entries || pipeline.converters_done {
+            return indexed_entries;
+        }
+
+        // filter paths as they arrive, the more of them are queued the better the most recent ones can be picked
+        let mut filtered_path = false;
+        if let Some(path_receiver) = &pipeline.path_receiver
+            && pipeline.queued_paths.has_room()
+        {
+            match path_receiver.try_recv() {
+                Ok(index_path) => {
+                    if let Some((path_state, volume_path)) = self.filter_path(&index_path, pipeline)
+                    {
+                        let recency = pipeline.priorities.recency(index_path.path());
+                        pipeline.queued_paths.push(
+                            recency,
+                            index_path,
+                            volume_path,
+                            matches!(path_state, PathState::Changed),
+                        );
+                    }
+                    filtered_path = true;
+                }
+                Err(TryRecvError::Disconnected) => {
+                    // every collector is done, anything they did not report was deleted or moved. A reindex deleted
+                    // everything in its scope up front, so there is nothing left to remove.
+                    if pipeline.scope.is_none() {
+                        let reported_paths = std::mem::take(&mut pipeline.reported_paths);
+                        let new_paths = std::mem::take(&mut pipeline.new_paths);
+                        self.remove_missing_entries(&reported_paths, &pipeline.claims, &new_paths);
+                    }
+                    pipeline.path_receiver = None;
+                    filtered_path = true;
+                }
+                Err(TryRecvError::Empty) => {}
+            }
+        }
+
+        // hold back queued paths while too many entries are waiting on a slow conversion
+        if pipeline.reorder_buffer.is_full() {
+            return filtered_path;
+        }
+
+        let Some(conversion_sender) = &pipeline.conversion_sender else {
+            return filtered_path;
+        };
+        // only this thread sends, so sending to a channel with room does not block
+        if conversion_sender.is_full() {
+            return filtered_path;
+        }
+        let Some(queued_path) = pipeline.queued_paths.pop() else {
+            if pipeline.path_receiver.is_none() {
+                // close the conversion channel so the workers finish once it is empty
+                pipeline.conversion_sender = None;
+                return true;
+            }
+            return filtered_path;
+        };
+
+        // a source that comes first in `SOURCE_PRIORITY` reported the file after it was queued
+        let Some(claim) = pipeline
+            .claims
+            .get_mut(&queued_path.volume_path)
+            .filter(|claim| claim.converter == Some(queued_path.index_path.source()))
+        else {
+            return true;
+        };
+        if queued_path.changed {
+            self.delete_volume_path(&queued_path.volume_path);
+        }
+        let sequence = pipeline.next_sequence;
+        pipeline.next_sequence += 1;
+        claim.sequence = Some(sequence);
+        if let Err(SendError((sequence, index_path))) =
+            conversion_sender.send((sequence, queued_path.index_path))
+        {
+            // the converters are gone, so record the path as failed instead of losing track of it
+            let failure = IndexingFailure::new(
+                index_path.path(),
+                index_path.source(),
+                IndexingStage::Converting,
+                IndexingError::PipelineStopped("conversion workers").to_string(),
+            );
+            pipeline.reorder_buffer.insert(sequence, Err(failure));
+        }
+        true
+    }
+
+    /// Updates the fulltext index by reading the IndexEntries from the receiver
+    pub(crate) fn update(&mut self, mut pipeline: IndexingPipeline) -> Result<(), IndexingError> {
+        info!("updating the fulltext index...");
+
+        // paths are filtered as they arrive, the status switches to UpdatingIndex with the first converted entries
+        self.send_status(IndexStatus::FilteringPaths);
+
+        // filter the collected paths and add the converted IndexEntries to the index
+        info!("starting entry indexing loop...");
+        loop {
+            if self.commit_due() {
+                self.commit()?;
+            }
+
+            // reindex requests are queued until this run is done
+            match self.request_receiver.try_recv() {
+                Ok(request) => self.handle_request(request),
+                Err(std::sync::mpsc::TryRecvError::Empty) => {}
+                // the UI quit or switched to another profile, the next run resumes from what is committed here
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                    self.commit()?;
+                    return Err(IndexingError::Abandoned);
+                }
+            }
+            self.hold_while_paused()?;
+
+            if self.advance_pipeline(&mut pipeline) {
+                continue;
+            }
+
+            if pipeline.converters_done {
+                break;
+            }
+
+            // wait on the converters rather than sleeping, so a finished conversion is picked up right away
+            if let Ok((sequence, result)) = pipeline
+                .entry_receiver
+                .recv_timeout(Duration::from_millis(20))
+            {
+                pipeline.reorder_buffer.insert(sequence, result);
+            }
+        }
+
+        // index anything left behind by a converter that never reported back
+        for (sequence, result) in pipeline.reorder_buffer.drain() {
+            if !pipeline.stale_sequences.contains(&sequence) {
+                self.index_conversion(result);
+            }
+        }
+        self.settle_claims(&pipeline.claims, pipeline.scope.as_ref())?;
+
+        // a reindex only covers part of the index, so it does not complete a run
+        if pipeline.scope.is_some() {
+            self.commit()?;
+            self.send_status(IndexStatus::UpToDate);
+            return Ok(());
+        }
+
+        // the final commit marks the run as complete, so the next launch starts a new one
+        self.checkpoint.complete = true;
+        self.commit()?;
+
+        if self.config.compact_after_indexing {
+            self.compact_and_report();
+        }
+
+        self.send_status(IndexStatus::UpToDate);
+
+        Ok(())
+    }
+
+    /// Starts watching the collector roots, before the first indexing run so changes made during it are not missed
+    ///
+    /// The changes wait until `watch_for_changes` takes them. `None` if watching is disabled or the watcher cannot be
+    /// started.
+    pub(crate) fn start_watching(&self) -> Option<FileWatcher> {
+        if !self.config.watch_files {
+            return None;
+        }
+        let roots = self.config.source_roots().into_values().flatten().collect();
+        FileWatcher::new(&self.config, roots)
+            .inspect_err(|e| warn!("could not start watching for changes: {}", e))
+            .ok()
+    }
+
+    /// Indexes the changes the file watcher reports as they happen, serving searches and reindex requests in between
+    ///
+    /// Only requests are served if there is no file watcher, see `start_watching`. Returns once the UI is gone.
+    pub(crate) fn watch_for_changes(&mut self, file_watcher: Option<FileWatcher>) {
+        loop {
+            self.run_queued_requests();
+
+            let events = file_watcher
+                .as_ref()
+                .and_then(|file_watcher| Some((file_watcher, file_watcher.next_events()?)));
+            match events {
+                Some((file_watcher, Ok(events))) => {
+                    let changed_paths = events.into_iter().map(|event| event.path).collect();
+                    if let Err(e) = self.update_changed_paths(file_watcher, changed_paths) {
+                        // keep watching, the next change may well commit fine
+                        self.report_error(e);
+                    }
+                }
+                Some((_, Err(e))) => warn!("error watching for changes: {}", e),
+                // without a watcher there are no events, only requests
+                None => match self.request_receiver.try_recv() {
+                    Ok(request) => self.handle_request(request),
+                    Err(std::sync::mpsc::TryRecvError::Empty) => {
+                        thread::sleep(Duration::from_millis(20))
+                    }
+                    Err(std::sync::mpsc::TryRecvError::Disconnected) => break,
+                },
+            }
+        }
+    }
+
+    /// Queues a request until the indexing in progress is done
+    fn handle_request(&mut self, request: IndexRequest) {
+        match request {
+            IndexRequest::Reindex(target) => {
+                info!("queueing reindex of {}", target);
+                self.queued_reindexes.push(target);
+            }
+            IndexRequest::Compact => {
+                info!("queueing compaction");
+                self.compaction_queued = true;
+            }
+            IndexRequest::Pause => {
+                info!("pausing indexing");
+                self.paused = true;
+            }
+            IndexRequest::Resume => {
+                info!("resuming indexing");
+                self.paused = false;
+            }
+            IndexRequest::SearchActivity => self.last_search_activity = Some(Instant::now()),
+            // statistics only read the index, so they are gathered right away
+            IndexRequest::Stats => {
+                if let Err(e) = self.results_sender.send(IndexResults::Stats(self.stats())) {
+                    debug!("could not send index stats: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Gathers statistics about what is in the index, as of the last commit
+    pub(crate) fn stats(&self) -> Result<IndexStats, TantivyError> {
+        let fields = StatsFields::new(&self.index.schema())?;
+        let index_bytes = compaction::index_size(self.index_path())?;
+        IndexStats::collect(&self.reader.searcher(), &fields, index_bytes)
+    }
+
+    /// Why indexing should be on hold right now, if it should
+    fn pause_reason(&self) -> Option<PauseReason> {
+        let idle_after = Duration::from_secs(self.config.idle_after_seconds);
+        if self.paused {
+            Some(PauseReason::User)
+        } else if self.config.index_only_when_idle
+            && self
+                .last_search_activity
+                .is_some_and(|last_search_activity| last_search_activity.elapsed() < idle_after)
+        {
+            Some(PauseReason::SearchActive)
+        } else {
+            None
+        }
+    }
+
+    /// Waits while indexing is paused, handling requests meanwhile
+    ///
+    /// Only the indexing thread waits here, the collectors and converters stall once their channels are full so the
+    /// whole pipeline rests. What was indexed so far is committed first, so it can be searched while paused.
+    fn hold_while_paused(&mut self) -> Result<(), IndexingError> {
+        let Some(mut reason) = self.pause_reason() else {
+            return Ok(());
+        };
+        if self.uncommitted_documents > 0 || self.uncommitted_removals > 0 {
+            self.commit()?;
+        }
+
+        info!("indexing on hold: {:?}", reason);
+        self.send_paused(Some(reason));
+        loop {
+            match self
+                .request_receiver
+                .recv_timeout(Duration::from_millis(100))
+            {
+                Ok(request) => self.handle_request(request),
+                Err(RecvTimeoutError::Timeout) => {}
+                // nobody is left to resume indexing, carry on so the run can end
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            match self.pause_reason() {
+                Some(current_reason) if current_reason != reason => {
+                    reason = current_reason;
+                    self.send_paused(Some(reason));
+                }
+                Some(_) => {}
+                None => break,
+            }
+        }
+        info!("indexing resumed");
+        self.send_paused(None);
+        Ok(())
+    }
+
+    fn send_paused(&self, reason: Option<PauseReason>) {
+        if let Err(e) = self.results_sender.send(IndexResults::Paused(reason)) {
+            debug!("could not send pause: {}", e);
+        }
+    }
+
+    /// Lowers the priority of this thread and the threads it spawns from now on, see `low_priority_indexing`
+    ///
+    /// Spawn the search service first, searches should not wait on anything else running.
+    pub(crate) fn lower_priority(&self) {
+        resource_governor::lower_thread_priority(&self.config);
+    }
+
+    fn run_queued_requests(&mut self) {
+        for target in std::mem::take(&mut self.queued_reindexes) {
+            if let Err(e) = self.reindex(target) {
+                self.report_error(e);
+            }
+        }
+
+        if std::mem::take(&mut self.compaction_queued) {
+            self.compact_and_report();
+            self.send_status(IndexStatus::UpToDate);
+        }
+    }
+
+    /// The directory this writes to, see `is_rebuilding`
+    fn index_path(&self) -> &Path {
+        if self.rebuilding {
+            &self.dirs.rebuild
+        } else {
+            &self.dirs.index
+        }
+    }
+
+    /// Merges segments according to the configured merge policy, then deletes the files no segment uses anymore
+    ///
+    /// Nothing is committed, merging only rearranges the documents that already are.
+    pub(crate) fn compact(&mut self) -> Result<CompactionReport, IndexingError> {
+        info!("compacting the fulltext index...");
+        self.send_status(IndexStatus::Compacting);
+
+        let segments = self.index.searchable_segment_metas()?;
+        let segments_before = segments.len();
+        let bytes_before = compaction::index_size(self.index_path())?;
+        let merge_candidates = compaction::merge_candidates(&self.config, &segments);
+        // tantivy keeps the files of every segment that still has its meta around
+        drop(segments);
+        for segment_ids in merge_candidates {
+            // a merge the writer started in the background after the last commit may hold some of these already
+            if let Err(e) = self.writer.merge(&segment_ids).wait() {
+                warn!("could not merge {} segments: {}", segment_ids.len(), e);
+            }
+        }
+
+        // searchers that still use the merged segments keep their files, so they are let go of first
+        self.reader.reload()?;
+        self.writer.garbage_collect_files().wait()?;
+
+        Ok(CompactionReport {
+            segments_before,
+            segments_after: self.index.searchable_segment_ids()?.len(),
+            bytes_before,
+            bytes_after: compaction::index_size(self.index_path())?,
+        })
+    }
+
+    /// Compacts the index and tells the UI how it went, a failed compaction leaves the index as it was
+    fn compact_and_report(&mut self) {
+        match self.compact() {
+            Ok(report) => {
+                info!("compacted the fulltext index: {}", report);
+                if let Err(e) = self.results_sender.send(IndexResults::Compacted(report)) {
+                    debug!("could not send compaction report: {}", e);
+                }
+            }
+            Err(e) => {
+                warn!("could not compact the fulltext index: {}", e);
+                self.report_error(e);
+            }
+        }
+    }
+
+    /// Deletes the documents of a source or directory subtree and converts their files again
+    ///
+    /// Only the collectors that may report paths in the target run, and nothing outside of it is touched. Files
+    /// that failed before are retried even if they did not change.
+    pub(crate) fn reindex(&mut self, target: ReindexTarget) -> Result<(), IndexingError> {
+        info!("reindexing {}...", target);
+        self.detect_volumes();
+        self.total_files = 0;
+        self.out_of_date_files = 0;
+        self.files_indexed = 0;
+
+        match &target {
+            ReindexTarget::Source(source) => {
+                // files that other sources report as well keep their documents, they are converted again if the
+                // source converts them, see `converter_path_state`
+                let other_sources = self.other_sources_query(source);
+                self.writer.delete_query(Box::new(BooleanQuery::new(vec![
+                    (
+                        Occur::Must,
+                        Box::new(TermQuery::new(
+                            Term::from_field_text(self.source_field, source),
+                            IndexRecordOption::Basic,
+                        )),
+                    ),
+                    (Occur::MustNot, Box::new(other_sources)),
+                ])))?;
+            }
+            ReindexTarget::Subtree(root) => {
+                let root = volume::canonical_path(root);
+                for volume_path in self.indexed_volume_paths() {
+                    if self
+                        .volumes
+                        .resolve(&volume_path)
+                        .is_some_and(|path| path.starts_with(&root))
+                    {
+                        self.delete_volume_path(&volume_path);
+                    }
+                }
+            }
+        }
+        self.error_ledger
+            .forget_where(|failure| target.contains(&failure.source, Path::new(&failure.path)));
+
+        // the deletions have to be visible before the paths are filtered, or every file would look up to date
+        self.commit()?;
+        self.reader.reload()?;
+
+        let pipeline = self.start_collectors_for(Some(target));
+        self.update(pipeline)
+    }
+
+    /// Reindexes a source or directory subtree without starting the UI, returns the number of files indexed
+    ///
+    /// This fails if another process is writing to the index.
+    pub fn reindex_now(config: Conf, target: ReindexTarget) -> Result<usize, IndexingError> {
+        let (mut index, _request_sender) = Self::open_for_cli(config)?;
+        index.reindex(target)?;
+        Ok(index.files_indexed)
+    }
+
+    /// Gathers statistics about the index without indexing anything, for the CLI
+    ///
+    /// Only a reader is opened, without taking the instance lock, so this works while another process indexes.
+    pub fn stats_now(config: Conf) -> Result<IndexStats, IndexingError> {
+        let dirs = Profile::active().index_dirs(&config);
+        let index = open_index(&dirs.index)?;
+        let reader: IndexReader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()?;
+        let fields = StatsFields::new(&index.schema())?;
+        let index_bytes = compaction::index_size(&dirs.index)?;
+        Ok(IndexStats::collect(
+            &reader.searcher(),
+            &fields,
+            index_bytes,
+        )?)
+    }
+
+    /// Compacts the index without indexing anything, for the CLI
+    ///
+    /// Merging needs the index writer, so this fails if another process is writing to the index.
+    pub fn compact_now(config: Conf) -> Result<CompactionReport, IndexingError> {
+        let (mut index, _request_sender) = Self::open_for_cli(config)?;
+        index.compact()
+    }
+
+    /// Runs a batch of changed paths through the same filter, convert and index steps as the initial indexing
+    fn update_changed_paths(
+        &mut self,
+        file_watcher: &FileWatcher,
+        changed_paths: BTreeSet<PathBuf>,
+    ) -> Result<(), IndexingError> {
+        self.hold_while_paused()?;
+        info!("indexing {} changed paths...", changed_paths.len());
+        // a drive that was just mounted or unmounted shows up as a batch of changes
+        self.detect_volumes();
+        self.out_of_date_files = 0;
+        self.files_indexed = 0;
+
+        let mut removed_fingerprints = Vec::new();
+        let mut index_paths = Vec::new();
+        let mut seen = HashSet::new();
+        for changed_path in &changed_paths {
+            if !changed_path.exists() {
+                removed_fingerprints.extend(self.remove_entries_under(changed_path));
+            }
+            for index_path in file_watcher.index_paths_for_change(changed_path) {
+                if seen.insert(index_path.clone()) {
+                    index_paths.push(index_path);
+                }
+            }
+        }
+
+        // every collector was asked about each path, so the first source of a file claims it first
+        index_paths.sort_by_key(|index_path| source_priority(index_path.source()));
+        let mut claims = SourceClaims::new();
+        let mut new_paths = Vec::new();
+        let mut paths_to_convert = Vec::new();
+        for index_path in index_paths {
+            let path = index_path.path();
+            let volume_path = self.volumes.volume_path(path);
+            let claim = claims.entry(volume_path.clone()).or_default();
+            let Some(took_over) = self.claim(&volume_path, claim, &index_path) else {
+                continue;
+            };
+            match self.converter_path_state(path, claim, took_over, false) {
+                PathState::UpToDate => continue,
+                PathState::Changed => self.delete_entry(path),
+                PathState::New => new_paths.push(volume::canonical_path(path)),
+            }
+            paths_to_convert.push(index_path);
+        }
+        self.out_of_date_files = paths_to_convert.len();
+
+        self.detect_moves(removed_fingerprints, &new_paths);
+
+        let mut project_lookup = ProjectLookup::default();
+        let size_limits = SizeLimits::new(&self.config);
+        for index_path in paths_to_convert {
+            let result = Self::convert_index_path(&index_path, &mut project_lookup, &size_limits);
+            // status updates are sent from within index_conversion
+            self.index_conversion(result);
+        }
+
+        self.settle_claims(&claims, None)?;
+        self.commit()?;
+
+        self.send_status(IndexStatus::UpToDate);
+        Ok(())
+    }
+
+    /// Checks the configured commit triggers against what was added since the last commit
+    fn commit_due(&self) -> bool {
+        self.uncommitted_documents > 0
+            && (self.uncommitted_documents >= self.config.commit_every_documents
+                || self.uncommitted_bytes >= self.config.commit_every_bytes
+                || self.last_commit.elapsed().as_secs() >= self.config.commit_every_seconds)
+    }
+
+    /// Commits the changes along with a checkpoint of the current run, and saves the error ledger to match
+    fn commit(&mut self) -> Result<(), IndexingError> {
+        self.send_status(IndexStatus::UpdatingIndex {
+            indexed: self.files_indexed,
+            total: self.out_of_date_files,
+            committing_updates: true,
+            file_path: "".to_owned(),
+        });
+
+        self.checkpoint.files_indexed += self.uncommitted_files;
+        self.checkpoint.files_removed += self.uncommitted_removals;
+
+        // commit the changes so that searchers can see the changes
+        info!("committing changes to fulltext index...");
+        let mut prepared_commit = self.writer.prepare_commit()?;
+        prepared_commit.set_payload(&self.checkpoint.to_payload());
+        let opstamp = prepared_commit.commit()?;
+        self.error_ledger.save();
+
+        // reload right away rather than after the reader's delay, so the UI refreshes its results with the commit
+        self.reader.reload()?;
+        if !self.rebuilding {
+            self.announce_commit(opstamp);
+        }
+
+        self.uncommitted_files = 0;
+        self.uncommitted_removals = 0;
+        self.uncommitted_documents = 0;
+        self.uncommitted_bytes = 0;
+        self.last_commit = Instant::now();
+        Ok(())
+    }
+
+    /// Indexes the entries of a converted path, or records why it could not be converted
+    fn index_conversion(&mut self, result: ConversionResult) {
+        match result {
+            Ok(ConvertedEntries::Complete(entries)) => {
+                self.update_entries(entries.into_iter().map(Ok))
+            }
+            Ok(ConvertedEntries::Streamed(entries)) => self.update_entries(entries),
+            Err(failure) => {
+                self.error_ledger.record(failure);
+                // the file still counts towards the progress shown in the UI
+                self.files_indexed += 1;
+            }
+        }
+    }
+
+    /// Adds all of the entries that were converted from a single path to the index
+    ///
+    /// Streamed entries are read one at a time, each is added before the next is taken.
+    fn update_entries(
+        &mut self,
+        entries: impl Iterator<Item = Result<IndexEntry, IndexingFailure>>,
+    ) {
+        let mut file = None;
+        let mut failure = None;
+        let mut encoding_warning = None;
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    failure = Some(e);
+                    break;
+                }
+            };
+            if file.is_none() {
+                self.send_status(IndexStatus::UpdatingIndex {
+                    indexed: self.files_indexed,
+                    total: self.out_of_date_files,
+                    committing_updates: false,
+                    file_path: entry.path().to_owned(),
+                });
+                file = Some((entry.path().to_owned(), entry.source().to_owned()));
+            }
+            if encoding_warning.is_none() {
+                encoding_warning = entry.encoding_warning().map(str::to_owned);
+            }
+
+            match self.add_entry(&entry) {
+                Ok(()) => {
+                    self.uncommitted_documents += 1;
+                    self.uncommitted_bytes += entry.text_len();
+                }
+                Err(e) => {
+                    failure = Some(IndexingFailure::new(
+                        Path::new(entry.path()),
+                        entry.source(),
+                        IndexingStage::Indexing,
+                        e.to_string(),
+                    ))
+                }
+            }
+        }
+        if let Some((path, source)) = &file {
+            // a file whose encoding is uncertain is indexed anyway, but listed so its text can be checked
+            failure = failure.or(encoding_warning.map(|warning| {
+                IndexingFailure::new(Path::new(path), source, IndexingStage::Decoding, warning)
+            }));
+        }
+        match (failure, file) {
+            (Some(failure), _) => self.error_ledger.record(failure),
+            (None, Some((path, source))) => self.error_ledger.clear(&source, &path),
+            (None, None) => {}
+        }
+
+        self.files_indexed += 1;
+        self.uncommitted_files += 1;
+    }
+
+    fn add_entry(&mut self, entry: &IndexEntry) -> Result<(), IndexingError> {
+        // we were using the `doc!()` macro, but it doesn't seem to play well with date fields
+        let mut tantivy_doc = TantivyDocument::default();
+        tantivy_doc.add_text(self.source_field, entry.source());
+        tantivy_doc.add_date(self.indexed_at_field, *entry.indexed_at());
+        // the path is stored with its symlinks resolved, so it is the same whichever report of the file came first
+        let path = volume::canonical_path(Path::new(entry.path()));
+        tantivy_doc.add_text(self.path_field, path.to_string_lossy());
+        let (volume, volume_path) = self.volumes.locate(&path);
+        tantivy_doc.add_text(self.volume_field, volume);
+        tantivy_doc.add_text(self.volume_path_field, &volume_path);
+        tantivy_doc.add_text(self.title_field, entry.title());
+        tantivy_doc.add_text(self.body_field, entry.body());
+        if let Some(fingerprint) = entry.fingerprint() {
+            self.add_fingerprint(&mut tantivy_doc, fingerprint);
+        }
+        if entry.truncated() {
+            tantivy_doc.add_bool(self.truncated_field, true);
+        }
+        if entry.is_placeholder() {
+            tantivy_doc.add_bool(self.placeholder_field, true);
+        }
+
+        let schema = self.index.schema();
+        for (field_name, value) in entry.metadata() {
+            match schema.get_field(field_name) {
+                Ok(field) => tantivy_doc.add_text(field, value),
+                Err(e) => warn!("unknown metadata field {}: {}", field_name, e),
+            }
+        }
+
+        // add the document to the index
+        self.writer.add_document(tantivy_doc)?;
+        info!("added document to index: {}", &entry.path());
+        Ok(())
+    }
+
+    fn add_fingerprint(&self, tantivy_doc: &mut TantivyDocument, fingerprint: &FileFingerprint) {
+        tantivy_doc.add_date(self.modified_field, fingerprint.modified);
+        tantivy_doc.add_u64(self.size_field, fingerprint.size);
+        if let Some(content_hash) = fingerprint.content_hash {
+            tantivy_doc.add_u64(self.content_hash_field, content_hash);
+        }
+    }
+
+    fn convert_paths_to_entries(
+        conversion_receiver: ConversionReceiver,
+        entry_sender: IndexEntrySender,
+        size_limits: SizeLimits,
+    ) {
+        // every entry is tagged with the project it lives in, so hits can be filtered with e.g. `project:retsyn`
+        let mut project_lookup = ProjectLookup::default();
+
+        for (sequence, index_path) in conversion_receiver {
+            // a converter that panics on a malformed file only fails that file, the worker moves on
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                Self::convert_index_path(&index_path, &mut project_lookup, &size_limits)
+            }))
+            .unwrap_or_else(|panic| {
+                let message = panic
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or(panic.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                Err(IndexingFailure::new(
+                    index_path.path(),
+                    index_path.source(),
+                    IndexingStage::Converting,
+                    IndexingError::Panic(message).to_string(),
+                ))
+            });
+
+            // TODO maybe send these in a Box or Arc to reduce memory allocations
+            if entry_sender.send((sequence, result)).is_err() {
+                warn!("the indexer stopped, no longer converting paths");
+                break;
+            }
+        }
+
+        drop(entry_sender);
+    }
+
+    /// Converts a single path into its entries using the collector that reported it
+    fn convert_index_path(
+        index_path: &IndexPath,
+        project_lookup: &mut ProjectLookup,
+        size_limits: &SizeLimits,
+    ) -> ConversionResult {
+        debug!("attempting to convert {} to entry...", index_path);
+        let project = project_lookup.project(index_path.path());
+        // read the state of the file before converting it, so a change made during conversion is picked up next time.
+        // Files are hashed from what their converter reads, only music is hashed up front since its tags are read
+        // without reading the whole file.
+        let fingerprint = match index_path {
+            IndexPath::MusicFile(path_buf) => FileFingerprint::read(path_buf),
+            _ => FileFingerprint::stat(index_path.path()),
+        };
+
+        // images, music and projects are not read as a whole, only text files have a size limit
+        let size_limit = size_limits.for_source(index_path.source());
+        let over_limit = match index_path {
+            IndexPath::MarkdownFile(_)
+            | IndexPath::WebScrapBookFile(_)
+            | IndexPath::AichatSessionFile(_)
+            | IndexPath::KindleClippings(_)
+            | IndexPath::KoreaderMetadata(_)
+            | IndexPath::CodeFile(_) => fingerprint
+                .map(|f| f.size)
+                .filter(|size| *size > size_limit.max_bytes),
+            _ => None,
+        };
+        let policy = match (over_limit, index_path) {
+            (None, _) => None,
+            // these need the whole file to parse it, a truncated session or lua table is no use
+            (Some(_), IndexPath::AichatSessionFile(_) | IndexPath::KoreaderMetadata(_)) => {
+                Some(LargeFilePolicy::Skip)
+            }
+            (Some(_), IndexPath::MarkdownFile(_)) => Some(size_limit.policy),
+            (Some(_), _) if size_limit.policy == LargeFilePolicy::Stream => {
+                Some(LargeFilePolicy::Truncate)
+            }
+            (Some(_), _) => Some(size_limit.policy),
+        };
+        if let (Some(size), Some(LargeFilePolicy::Skip)) = (over_limit, policy) {
+            return Err(IndexingFailure::new(
+                index_path.path(),
+                index_path.source(),
+                IndexingStage::Converting,
+                format!(
+                    "skipped, the file is {} bytes which is above the limit of {} bytes",
+                    size, size_limit.max_bytes
+                ),
+            ));
+        }
+        let max_bytes = (policy == Some(LargeFilePolicy::Truncate)).then_some(size_limit.max_bytes);
+        let project_name = project.as_ref().map(|p| p.name.clone()).unwrap_or_default();
+        let failure = |e: IndexingError| {
+            IndexingFailure::new(
+                index_path.path(),
+                index_path.source(),
+                IndexingStage::Converting,
+                e.to_string(),
+            )
+        };
+
+        if let (IndexPath::MarkdownFile(path_buf), Some(LargeFilePolicy::Stream)) =
+            (index_path, policy)
+        {
+            // the first parts are indexed before the rest of the file is read, so it is hashed up front
+            let fingerprint = FileFingerprint::read(path_buf);
+            let entries =
+                MarkdownFiles::convert_path_to_chunked_entries(path_buf, size_limit.max_bytes)
+                    .map_err(failure)?;
+            let (path, source) = (path_buf.clone(), index_path.source());
+            return Ok(ConvertedEntries::Streamed(Box::new(entries.map(
+                move |entry| {
+                    match entry {
+                        Ok(entry) => Ok(entry
+                            .with_metadata(PROJECT, project_name.clone())
+                            .with_fingerprint(fingerprint)),
+                        Err(e) => Err(IndexingFailure::new(
+                            &path,
+                            source,
+                            IndexingStage::Converting,
+                            e.to_string(),
+                        )),
+                    }
+                },
+            ))));
+        }
+
+        let new_entries = match index_path {
+            IndexPath::MarkdownFile(path_buf) => {
+                MarkdownFiles::convert_path_to_entry(path_buf, max_bytes).map(|entry| vec![entry])
+            }
+            IndexPath::WebScrapBookFile(path_buf) => {
+                WebScrapbookFiles::convert_path_to_entry(path_buf, max_bytes)
+                    .map(|entry| vec![entry])
+            }
+            IndexPath::AichatSessionFile(path_buf) => {
+                AichatSessionFiles::convert_path_to_entry(path_buf).map(|entry| vec![entry])
+            }
+            IndexPath::ImageFile(path_buf) => {
+                ImageFiles::convert_path_to_entry(path_buf).map(|entry| vec![entry])
+            }
+            IndexPath::MusicFile(path_buf) => Ok(vec![MusicFiles::convert_path_to_entry(path_buf)]),
+            IndexPath::MusicAlbum(path_buf) => {
+                Ok(vec![MusicFiles::convert_album_to_entry(path_buf)])
+            }
+            IndexPath::KindleClippings(path_buf) => {
+                ReadingHighlights::convert_kindle_clippings_to_entries(path_buf, max_bytes)
+            }
+            IndexPath::KoreaderMetadata(path_buf) => {
+                ReadingHighlights::convert_koreader_metadata_to_entries(path_buf)
+            }
+            IndexPath::CodeFile(path_buf) => {
+                CodeSymbols::convert_path_to_entries(path_buf, max_bytes)
+            }
+            IndexPath::Project(path_buf) => match &project {
+                Some(project) => Ok(vec![Projects::convert_path_to_entry(
+                    path_buf,
+                    project.clone(),
+                )]),
+                // the home directory is never a project, even if it has a project marker
+                None => Err(IndexingError::Parse("not a project root".to_owned())),
+            },
+        };
+
+        match new_entries {
+            Ok(new_entries) => {
+                let content_hash = new_entries.iter().find_map(IndexEntry::content_hash);
+                let fingerprint = fingerprint.map(|fingerprint| FileFingerprint {
+                    content_hash: fingerprint.content_hash.or(content_hash),
+                    ..fingerprint
+                });
+                // e.g. a source file without symbols, its state is still recorded so it is not converted every run
+                if new_entries.is_empty() {
+                    return Ok(ConvertedEntries::Complete(vec![
+                        IndexEntry::placeholder(index_path.source(), index_path.path())
+                            .with_fingerprint(fingerprint),
+                    ]));
+                }
+                Ok(ConvertedEntries::Complete(
+                    new_entries
+                        .into_iter()
+                        .map(|entry| {
+                            entry
+                                .with_metadata(PROJECT, project_name.clone())
+                                .with_fingerprint(fingerprint)
+                        })
+                        .collect(),
+                ))
+            }
+            Err(e) => Err(failure(e)),
+        }
+    }
+
+    pub(crate) fn file_is_indexed(&self, path: &Path) -> bool {
+        // the same term the documents are added and deleted with, so this cannot get out of sync with indexing
+        let query = TermQuery::new(self.volume_path_term(path), IndexRecordOption::Basic);
 
         let searcher = self.reader.searcher();
 
@@ -601,35 +2327,193 @@ impl FulltextIndex {
         }
     }
 
-    pub(crate) fn entry_up_to_date(&self, entry: &Path) -> bool {
-        match self.last_indexing_epoch {
-            None => {
-                info!("last indexing epoch is not set");
-                true
+    /// Reads the state of a file when it was indexed from the fast fields of its first document
+    ///
+    /// Returns `None` for documents indexed before fingerprints were recorded.
+    fn indexed_fingerprint(&self, volume_path: &str) -> Option<FileFingerprint> {
+        let searcher = self.reader.searcher();
+        let query = TermQuery::new(
+            Term::from_field_text(self.volume_path_field, volume_path),
+            IndexRecordOption::Basic,
+        );
+
+        let (_score, doc_address) = match searcher.search(&query, &TopDocs::with_limit(1)) {
+            Ok(top_docs) => top_docs.into_iter().next()?,
+            Err(e) => {
+                warn!("error searching for document: {}", e);
+                return None;
             }
-            Some(last_indexing_epoch) => match entry.metadata() {
+        };
+
+        let fast_fields = searcher
+            .segment_reader(doc_address.segment_ord)
+            .fast_fields();
+        let first_u64 = |field: &str| {
+            fast_fields
+                .u64(field)
+                .ok()
+                .and_then(|column| column.first(doc_address.doc_id))
+        };
+
+        Some(FileFingerprint {
+            modified: fast_fields.date(MODIFIED).ok()?.first(doc_address.doc_id)?,
+            size: first_u64(SIZE)?,
+            content_hash: first_u64(CONTENT_HASH),
+        })
+    }
+
+    /// Replaces the stored path and fingerprint on every document for a file without converting it again
+    ///
+    /// This is used when only the modification time changed, so the next run can skip hashing the file, and when its
+    /// volume is mounted somewhere else than it was when the file was indexed.
+    fn refresh_documents(&self, path: &Path, fingerprint: &FileFingerprint) {
+        let canonical_path = volume::canonical_path(path);
+        let refreshed_fields = [
+            self.path_field,
+            self.modified_field,
+            self.size_field,
+            self.content_hash_field,
+        ];
+        self.rewrite_documents(
+            &self.volumes.volume_path(path),
+            &refreshed_fields,
+            |tantivy_doc| {
+                tantivy_doc.add_text(self.path_field, canonical_path.to_string_lossy());
+                self.add_fingerprint(tantivy_doc, fingerprint);
+            },
+        );
+    }
+
+    /// Rewrites every document for a volume path from its stored fields, with the given fields replaced by what
+    /// `fill` adds
+    fn rewrite_documents(
+        &self,
+        volume_path: &str,
+        replaced_fields: &[TantivyField],
+        fill: impl Fn(&mut TantivyDocument),
+    ) {
+        let searcher = self.reader.searcher();
+        let query = TermQuery::new(
+            Term::from_field_text(self.volume_path_field, volume_path),
+            IndexRecordOption::Basic,
+        );
+
+        let doc_addresses: Vec<DocAddress> = match searcher.search(&query, &DocSetCollector) {
+            Ok(doc_addresses) => doc_addresses.into_iter().collect(),
+            Err(e) => {
+                warn!("error searching for document: {}", e);
+                return;
+            }
+        };
+
+        let mut rewritten_docs = Vec::with_capacity(doc_addresses.len());
+        for doc_address in doc_addresses {
+            let stored_doc: TantivyDocument = match searcher.doc(doc_address) {
+                Ok(doc) => doc,
                 Err(e) => {
-                    warn!("could not get metadata: {}", e);
-                    true
+                    warn!("could not load stored document for {}: {}", volume_path, e);
+                    return;
                 }
-                Ok(metadata) => match metadata.modified() {
-                    Err(e) => {
-                        warn!("could not get modification date: {}", e);
-                        true
-                    }
-                    Ok(file_modified) => {
-                        if file_modified > last_indexing_epoch {
-                            // println!(
-                            //     "deleting file from index: {}",
-                            //     entry.path().to_string_lossy()
-                            // );
-                            false
-                        } else {
-                            true
-                        }
-                    }
-                },
-            },
+            };
+
+            let mut tantivy_doc = TantivyDocument::default();
+            for (field, value) in stored_doc.field_values() {
+                if !replaced_fields.contains(&field) {
+                    tantivy_doc.add_field_value(field, value);
+                }
+            }
+            fill(&mut tantivy_doc);
+            rewritten_docs.push(tantivy_doc);
+        }
+
+        // deletes only apply to documents added before them, so the rewritten documents survive
+        self.delete_volume_path(volume_path);
+        for tantivy_doc in rewritten_docs {
+            if let Err(e) = self.writer.add_document(tantivy_doc) {
+                warn!("could not rewrite document: {}: {}", volume_path, e)
+            }
+        }
+    }
+
+    /// Whether the documents of a file were indexed at the path it has now, rather than at the path it had while its
+    /// volume was mounted somewhere else
+    fn indexed_at_path(&self, path: &Path) -> bool {
+        let query = BooleanQuery::intersection(vec![
+            Box::new(TermQuery::new(
+                self.volume_path_term(path),
+                IndexRecordOption::Basic,
+            )),
+            Box::new(TermQuery::new(
+                Term::from_field_text(
+                    self.path_field,
+                    &volume::canonical_path(path).to_string_lossy(),
+                ),
+                IndexRecordOption::Basic,
+            )),
+        ]);
+        match self.reader.searcher().search(&query, &Count) {
+            Ok(count) => count > 0,
+            Err(e) => {
+                warn!("error searching for document: {}", e);
+                true
+            }
+        }
+    }
+
+    /// Checks the file on disk against the state it was in when it was indexed
+    ///
+    /// A file whose modification time changed but whose contents hash the same e.g. after being restored from a
+    /// backup is considered up to date, its stored fingerprint is refreshed instead.
+    pub(crate) fn entry_up_to_date(&self, path: &Path) -> bool {
+        let Some(indexed) = self.indexed_fingerprint(&self.volumes.volume_path(path)) else {
+            debug!("no fingerprint stored for {}", path.to_string_lossy());
+            return false;
+        };
+
+        // a file that cannot be read anymore is converted again, which records why it fails
+        let Some(current) = FileFingerprint::stat(path) else {
+            return false;
+        };
+
+        if current.modified == indexed.modified && current.size == indexed.size {
+            if !self.indexed_at_path(path) {
+                debug!(
+                    "volume moved, refreshing path of {}",
+                    path.to_string_lossy()
+                );
+                self.refresh_documents(path, &indexed);
+            }
+            return true;
+        }
+
+        // only files get a content hash, directories are re-indexed whenever they change
+        let Some(indexed_hash) = indexed.content_hash else {
+            return false;
+        };
+        if current.size != indexed.size {
+            return false;
+        }
+
+        match FileFingerprint::hash_file(path) {
+            Ok(content_hash) if content_hash == indexed_hash => {
+                debug!(
+                    "contents unchanged, refreshing fingerprint for {}",
+                    path.to_string_lossy()
+                );
+                self.refresh_documents(
+                    path,
+                    &FileFingerprint {
+                        content_hash: Some(content_hash),
+                        ..current
+                    },
+                );
+                true
+            }
+            Ok(_) => false,
+            Err(e) => {
+                warn!("could not hash {}: {}", path.to_string_lossy(), e);
+                false
+            }
         }
     }
 }
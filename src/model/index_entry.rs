@@ -1,24 +1,73 @@
+use crossbeam_channel::{Receiver, Sender};
 use std::{
     fmt,
     path::{Path, PathBuf},
-    sync::mpsc::{Receiver, Sender},
 };
 use tantivy::DateTime;
 use time::OffsetDateTime;
 
+use crate::{
+    collectors::{
+        aichat_session_files::AICHAT_SESSION_FILES_SOURCE, code_symbols::CODE_SYMBOLS_SOURCE,
+        image_files::IMAGE_FILES_SOURCE, markdown_files::MARKDOWN_FILES_SOURCE,
+        music_files::MUSIC_FILES_SOURCE, projects::PROJECTS_SOURCE,
+        reading_highlights::READING_HIGHLIGHTS_SOURCE,
+        web_scrapbook_files::WEB_SCRAPBOOK_FILES_SOURCE,
+    },
+    model::{
+        decoded_text::DecodedText, error_ledger::IndexingFailure,
+        file_fingerprint::FileFingerprint, fulltext_index::ENCODING,
+    },
+};
+
+/// All of the entries that were converted from a single path, or why it could not be converted
+///
+/// Some sources like reading highlights produce more than one entry per path.
+pub(crate) type ConversionResult = Result<ConvertedEntries, IndexingFailure>;
+
+/// The entries of a path that are converted as they are taken, so only one of them is held in memory at a time
+pub(crate) type EntryStream = Box<dyn Iterator<Item = Result<IndexEntry, IndexingFailure>> + Send>;
+
+pub(crate) enum ConvertedEntries {
+    /// Every entry of the path, converted up front
+    Complete(Vec<IndexEntry>),
+    /// The entries of a file above its size limit, each chunk of it is read once the entry before it was indexed
+    Streamed(EntryStream),
+}
+
 /// A channel to send IndexEtries to other threads
-pub(crate) type IndexEntrySender = Sender<IndexEntry>;
+///
+/// Each message holds the conversion result of a single path. They are tagged with the sequence number the path was
+/// sent to the converters with, so they can be put back in order.
+pub(crate) type IndexEntrySender = Sender<(usize, ConversionResult)>;
 
 /// A channel to receive IndexEtries from other threads
-pub(crate) type IndexEntryReceiver = Receiver<IndexEntry>;
+pub(crate) type IndexEntryReceiver = Receiver<(usize, ConversionResult)>;
 
 pub(crate) type IndexPathSender = Sender<IndexPath>;
 pub(crate) type IndexPathReceiver = Receiver<IndexPath>;
 
+/// A channel to hand numbered paths to the conversion workers
+pub(crate) type ConversionSender = Sender<(usize, IndexPath)>;
+pub(crate) type ConversionReceiver = Receiver<(usize, IndexPath)>;
+
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub(crate) enum IndexPath {
     MarkdownFile(PathBuf),
     WebScrapBookFile(PathBuf),
     AichatSessionFile(PathBuf),
+    ImageFile(PathBuf),
+    MusicFile(PathBuf),
+    /// A directory of tracks that is indexed as a single album
+    MusicAlbum(PathBuf),
+    /// A Kindle `My Clippings.txt` file holding highlights from many books
+    KindleClippings(PathBuf),
+    /// A KOReader `metadata.*.lua` file from a book's `.sdr` directory
+    KoreaderMetadata(PathBuf),
+    /// A source file whose symbol definitions are indexed
+    CodeFile(PathBuf),
+    /// A project root directory, marked by e.g. `.git` or `Cargo.toml`
+    Project(PathBuf),
 }
 
 impl fmt::Display for IndexPath {
@@ -26,7 +75,16 @@ impl fmt::Display for IndexPath {
         match self {
             IndexPath::MarkdownFile(path) => write!(f, "MarkdownFile({})", path.display()),
             IndexPath::WebScrapBookFile(path) => write!(f, "WebScrapBookFile({})", path.display()),
-            IndexPath::AichatSessionFile(path) => write!(f, "AichatSessionFile({})", path.display()),
+            IndexPath::AichatSessionFile(path) => {
+                write!(f, "AichatSessionFile({})", path.display())
+            }
+            IndexPath::ImageFile(path) => write!(f, "ImageFile({})", path.display()),
+            IndexPath::MusicFile(path) => write!(f, "MusicFile({})", path.display()),
+            IndexPath::MusicAlbum(path) => write!(f, "MusicAlbum({})", path.display()),
+            IndexPath::KindleClippings(path) => write!(f, "KindleClippings({})", path.display()),
+            IndexPath::KoreaderMetadata(path) => write!(f, "KoreaderMetadata({})", path.display()),
+            IndexPath::CodeFile(path) => write!(f, "CodeFile({})", path.display()),
+            IndexPath::Project(path) => write!(f, "Project({})", path.display()),
         }
     }
 }
@@ -37,6 +95,29 @@ impl IndexPath {
             IndexPath::MarkdownFile(path_buf) => path_buf,
             IndexPath::WebScrapBookFile(path_buf) => path_buf,
             IndexPath::AichatSessionFile(path_buf) => path_buf,
+            IndexPath::ImageFile(path_buf) => path_buf,
+            IndexPath::MusicFile(path_buf) => path_buf,
+            IndexPath::MusicAlbum(path_buf) => path_buf,
+            IndexPath::KindleClippings(path_buf) => path_buf,
+            IndexPath::KoreaderMetadata(path_buf) => path_buf,
+            IndexPath::CodeFile(path_buf) => path_buf,
+            IndexPath::Project(path_buf) => path_buf,
+        }
+    }
+
+    /// The name of the source whose collector reported this path
+    pub(crate) fn source(&self) -> &'static str {
+        match self {
+            IndexPath::MarkdownFile(_) => MARKDOWN_FILES_SOURCE,
+            IndexPath::WebScrapBookFile(_) => WEB_SCRAPBOOK_FILES_SOURCE,
+            IndexPath::AichatSessionFile(_) => AICHAT_SESSION_FILES_SOURCE,
+            IndexPath::ImageFile(_) => IMAGE_FILES_SOURCE,
+            IndexPath::MusicFile(_) | IndexPath::MusicAlbum(_) => MUSIC_FILES_SOURCE,
+            IndexPath::KindleClippings(_) | IndexPath::KoreaderMetadata(_) => {
+                READING_HIGHLIGHTS_SOURCE
+            }
+            IndexPath::CodeFile(_) => CODE_SYMBOLS_SOURCE,
+            IndexPath::Project(_) => PROJECTS_SOURCE,
         }
     }
 }
@@ -50,6 +131,18 @@ pub(crate) struct IndexEntry {
     path: String,
     title: String,
     body: String,
+    /// Source specific fields e.g. the camera that took a photo, keyed by their schema field name
+    metadata: Vec<(&'static str, String)>,
+    /// The state of the source file when it was read, so later runs can tell if it changed
+    fingerprint: Option<FileFingerprint>,
+    /// Why the text of the source file may be garbled, recorded in the error ledger
+    encoding_warning: Option<String>,
+    /// Whether only the start of the source file was indexed because it is above its size limit
+    truncated: bool,
+    /// The hash of the source file if the converter read all of it, so it is not read again for the fingerprint
+    content_hash: Option<u64>,
+    /// Whether this only stands in for a file that converted to no entries, see `IndexEntry::placeholder`
+    placeholder: bool,
 }
 
 impl IndexEntry {
@@ -61,9 +154,51 @@ impl IndexEntry {
             path,
             title,
             body,
+            metadata: Vec::default(),
+            fingerprint: None,
+            encoding_warning: None,
+            truncated: false,
+            content_hash: None,
+            placeholder: false,
+        }
+    }
+
+    /// An entry without text for a file that converted to no entries, so the state of the file is still recorded
+    pub(crate) fn placeholder(source: &str, path: &Path) -> Self {
+        Self {
+            placeholder: true,
+            ..Self::new(
+                source.to_owned(),
+                path.to_string_lossy().to_string(),
+                String::new(),
+                String::new(),
+            )
         }
     }
 
+    /// Adds a value for one of the optional metadata fields in the schema
+    ///
+    /// Empty values are skipped so collectors can pass along whatever they managed to extract.
+    pub(crate) fn with_metadata(mut self, field: &'static str, value: impl Into<String>) -> Self {
+        let value = value.into();
+        if !value.trim().is_empty() {
+            self.metadata.push((field, value));
+        }
+        self
+    }
+
+    /// Records the hash of the source file, for converters that read all of it without `DecodedText`
+    pub(crate) fn with_content_hash(mut self, content_hash: u64) -> Self {
+        self.content_hash = Some(content_hash);
+        self
+    }
+
+    /// Records the state of the source file, if it could be read
+    pub(crate) fn with_fingerprint(mut self, fingerprint: Option<FileFingerprint>) -> Self {
+        self.fingerprint = fingerprint;
+        self
+    }
+
     pub(crate) fn source(&self) -> &str {
         &self.source
     }
@@ -83,4 +218,47 @@ impl IndexEntry {
     pub(crate) fn body(&self) -> &str {
         &self.body
     }
+
+    pub(crate) fn metadata(&self) -> &[(&'static str, String)] {
+        &self.metadata
+    }
+
+    /// Records the encoding the source file was read as, whether it may have been read wrong and whether it was
+    /// only read in part
+    pub(crate) fn with_decoded_text(mut self, decoded: &DecodedText) -> Self {
+        self.encoding_warning = decoded.uncertainty();
+        self.truncated = decoded.truncated;
+        self.content_hash = decoded.content_hash;
+        self.with_metadata(ENCODING, decoded.encoding_name())
+    }
+
+    pub(crate) fn encoding_warning(&self) -> Option<&str> {
+        self.encoding_warning.as_deref()
+    }
+
+    /// Whether only the start of the source file was indexed
+    pub(crate) fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// The number of bytes of text this entry adds to the index, used to decide when to commit
+    pub(crate) fn text_len(&self) -> usize {
+        self.source.len()
+            + self.path.len()
+            + self.title.len()
+            + self.body.len()
+            + self.metadata.iter().map(|(_, v)| v.len()).sum::<usize>()
+    }
+
+    pub(crate) fn is_placeholder(&self) -> bool {
+        self.placeholder
+    }
+
+    pub(crate) fn content_hash(&self) -> Option<u64> {
+        self.content_hash
+    }
+
+    pub(crate) fn fingerprint(&self) -> Option<&FileFingerprint> {
+        self.fingerprint.as_ref()
+    }
 }
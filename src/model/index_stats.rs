@@ -0,0 +1,314 @@
+use std::{collections::HashMap, fmt};
+use tantivy::{
+    DateTime, DocAddress, Order, Searcher, TantivyDocument, TantivyError, Term,
+    collector::{Count, DocSetCollector, TopDocs},
+    query::{AllQuery, BooleanQuery, Occur, Query, TermQuery},
+    schema::{Field as TantivyField, IndexRecordOption, Value},
+    tokenizer::{Language, SimpleTokenizer, StopWordFilter, TextAnalyzer},
+};
+use time::{UtcOffset, format_description::well_known::Rfc2822};
+
+/// How many of the largest documents are listed
+const LARGEST_DOCUMENTS: usize = 10;
+
+/// How many of the most common terms are listed
+const TOP_TERMS: usize = 20;
+
+/// The fields the statistics are read from
+pub(crate) struct StatsFields {
+    pub(crate) source: TantivyField,
+    pub(crate) indexed_at: TantivyField,
+    pub(crate) path: TantivyField,
+    pub(crate) title: TantivyField,
+    pub(crate) body: TantivyField,
+    pub(crate) size: TantivyField,
+    pub(crate) kind: TantivyField,
+    pub(crate) placeholder: Option<TantivyField>,
+}
+
+/// What is in the index, see `FulltextIndex::stats`
+#[derive(Debug, Clone)]
+pub struct IndexStats {
+    pub documents: u64,
+    pub segments: usize,
+    /// The size of the index on disk
+    pub index_bytes: u64,
+    /// Sorted by source name
+    pub sources: Vec<SourceStats>,
+    /// The number of documents of each kind e.g. function or track, most common first
+    pub kinds: Vec<(String, u64)>,
+    /// The documents converted from the largest files, largest first
+    pub largest_documents: Vec<LargeDocument>,
+    /// The terms in the most documents, leaving out stop words like "the", most common first
+    pub top_terms: Vec<(String, u64)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SourceStats {
+    pub source: String,
+    pub documents: u64,
+    /// The size of the files the documents were converted from, files split into several documents count once per
+    /// document
+    pub file_bytes: u64,
+    /// When the most recently indexed document of the source was indexed
+    pub last_indexed: Option<DateTime>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LargeDocument {
+    pub path: String,
+    pub title: String,
+    pub file_bytes: u64,
+}
+
+impl IndexStats {
+    /// Reads the statistics from the searcher's view of the index
+    ///
+    /// Every document of every source is visited for the file sizes, and every term of the body field for the top
+    /// terms, so this takes a while on a large index.
+    pub(crate) fn collect(
+        searcher: &Searcher,
+        fields: &StatsFields,
+        index_bytes: u64,
+    ) -> Result<Self, TantivyError> {
+        let mut sources = Vec::new();
+        for source in Self::terms(searcher, fields.source)? {
+            let source_stats = Self::source_stats(searcher, fields, source)?;
+            // a source may have nothing but placeholders
+            if source_stats.documents > 0 {
+                sources.push(source_stats);
+            }
+        }
+
+        let mut kinds = Vec::new();
+        for kind in Self::terms(searcher, fields.kind)? {
+            let documents = searcher.search(&Self::term_query(fields.kind, &kind), &Count)?;
+            if documents > 0 {
+                kinds.push((kind, documents as u64));
+            }
+        }
+        kinds.sort_by(|(a_kind, a), (b_kind, b)| b.cmp(a).then_with(|| a_kind.cmp(b_kind)));
+
+        let placeholders = match fields.placeholder {
+            Some(placeholder) => searcher.search(
+                &TermQuery::new(
+                    Term::from_field_bool(placeholder, true),
+                    IndexRecordOption::Basic,
+                ),
+                &Count,
+            )? as u64,
+            None => 0,
+        };
+
+        Ok(Self {
+            documents: searcher.num_docs() - placeholders,
+            segments: searcher.segment_readers().len(),
+            index_bytes,
+            sources,
+            kinds,
+            largest_documents: Self::largest_documents(searcher, fields)?,
+            top_terms: Self::top_terms(searcher, fields.body)?,
+        })
+    }
+
+    fn term_query(field: TantivyField, text: &str) -> TermQuery {
+        TermQuery::new(Term::from_field_text(field, text), IndexRecordOption::Basic)
+    }
+
+    /// Leaves the placeholders of files without entries out of a query, they are not documents to the user
+    fn without_placeholders(fields: &StatsFields, query: Box<dyn Query>) -> BooleanQuery {
+        let mut clauses = vec![(Occur::Must, query)];
+        if let Some(placeholder) = fields.placeholder {
+            clauses.push((
+                Occur::MustNot,
+                Box::new(TermQuery::new(
+                    Term::from_field_bool(placeholder, true),
+                    IndexRecordOption::Basic,
+                )),
+            ));
+        }
+        BooleanQuery::new(clauses)
+    }
+
+    /// The distinct terms of a field across all segments, sorted
+    fn terms(searcher: &Searcher, field: TantivyField) -> Result<Vec<String>, TantivyError> {
+        let mut terms = Vec::new();
+        for segment_reader in searcher.segment_readers() {
+            let inverted_index = segment_reader.inverted_index(field)?;
+            let mut stream = inverted_index.terms().stream()?;
+            while stream.advance() {
+                terms.push(String::from_utf8_lossy(stream.key()).into_owned());
+            }
+        }
+        terms.sort();
+        terms.dedup();
+        Ok(terms)
+    }
+
+    fn source_stats(
+        searcher: &Searcher,
+        fields: &StatsFields,
+        source: String,
+    ) -> Result<SourceStats, TantivyError> {
+        let query =
+            Self::without_placeholders(fields, Box::new(Self::term_query(fields.source, &source)));
+        let schema = searcher.schema();
+
+        let mut file_bytes = 0;
+        let documents = searcher.search(&query, &DocSetCollector)?;
+        for DocAddress {
+            segment_ord,
+            doc_id,
+        } in &documents
+        {
+            let sizes = searcher
+                .segment_reader(*segment_ord)
+                .fast_fields()
+                .u64(schema.get_field_name(fields.size))?;
+            file_bytes += sizes.first(*doc_id).unwrap_or_default();
+        }
+
+        let last_indexed = searcher
+            .search(
+                &query,
+                &TopDocs::with_limit(1).order_by_fast_field::<DateTime>(
+                    schema.get_field_name(fields.indexed_at),
+                    Order::Desc,
+                ),
+            )?
+            .first()
+            .map(|(indexed_at, _)| *indexed_at);
+
+        Ok(SourceStats {
+            source,
+            documents: documents.len() as u64,
+            file_bytes,
+            last_indexed,
+        })
+    }
+
+    fn largest_documents(
+        searcher: &Searcher,
+        fields: &StatsFields,
+    ) -> Result<Vec<LargeDocument>, TantivyError> {
+        let largest = searcher.search(
+            &Self::without_placeholders(fields, Box::new(AllQuery)),
+            &TopDocs::with_limit(LARGEST_DOCUMENTS)
+                .order_by_u64_field(searcher.schema().get_field_name(fields.size), Order::Desc),
+        )?;
+
+        let mut largest_documents = Vec::new();
+        for (file_bytes, doc_address) in largest {
+            let doc: TantivyDocument = searcher.doc(doc_address)?;
+            let text = |field| {
+                doc.get_first(field)
+                    .and_then(|value| value.as_str())
+                    .unwrap_or_default()
+                    .to_owned()
+            };
+            largest_documents.push(LargeDocument {
+                path: text(fields.path),
+                title: text(fields.title),
+                file_bytes,
+            });
+        }
+        Ok(largest_documents)
+    }
+
+    /// The terms of a field in the most documents, deleted documents count until their segment is merged
+    fn top_terms(
+        searcher: &Searcher,
+        field: TantivyField,
+    ) -> Result<Vec<(String, u64)>, TantivyError> {
+        let mut document_frequencies: HashMap<Vec<u8>, u64> = HashMap::new();
+        for segment_reader in searcher.segment_readers() {
+            let inverted_index = segment_reader.inverted_index(field)?;
+            let mut stream = inverted_index.terms().stream()?;
+            while stream.advance() {
+                *document_frequencies
+                    .entry(stream.key().to_vec())
+                    .or_default() += stream.value().doc_freq as u64;
+            }
+        }
+
+        let mut terms: Vec<(String, u64)> = document_frequencies
+            .into_iter()
+            .map(|(term, frequency)| (String::from_utf8_lossy(&term).into_owned(), frequency))
+            .collect();
+        terms.sort_by(|(a_term, a), (b_term, b)| b.cmp(a).then_with(|| a_term.cmp(b_term)));
+
+        // the body is stemmed but keeps its stop words, which would top the list otherwise
+        let mut stop_words = TextAnalyzer::builder(SimpleTokenizer::default())
+            .filter(StopWordFilter::new(Language::English).expect("english has stop words"))
+            .build();
+        Ok(terms
+            .into_iter()
+            .filter(|(term, _)| term.chars().count() > 2 && !term.chars().all(char::is_numeric))
+            .filter(|(term, _)| stop_words.token_stream(term).advance())
+            .take(TOP_TERMS)
+            .collect())
+    }
+}
+
+/// Formats a timestamp in the local timezone, like the indexing time of search results
+pub(crate) fn format_date_time(date_time: DateTime) -> String {
+    let offset = UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC);
+    date_time
+        .into_offset(offset)
+        .format(&Rfc2822)
+        .unwrap_or_else(|_| "unknown".to_owned())
+}
+
+/// Formats a byte count in megabytes
+pub(crate) fn format_megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / 1_000_000.0)
+}
+
+impl fmt::Display for IndexStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} documents in {} segments, {} on disk",
+            self.documents,
+            self.segments,
+            format_megabytes(self.index_bytes)
+        )?;
+
+        writeln!(f, "\nSources:")?;
+        for source in &self.sources {
+            writeln!(
+                f,
+                "  {}: {} documents from {} of files, last indexed {}",
+                source.source,
+                source.documents,
+                format_megabytes(source.file_bytes),
+                source
+                    .last_indexed
+                    .map(format_date_time)
+                    .unwrap_or_else(|| "never".to_owned())
+            )?;
+        }
+
+        writeln!(f, "\nKinds:")?;
+        for (kind, documents) in &self.kinds {
+            writeln!(f, "  {}: {} documents", kind, documents)?;
+        }
+
+        writeln!(f, "\nLargest documents:")?;
+        for document in &self.largest_documents {
+            writeln!(
+                f,
+                "  {} {} ({})",
+                format_megabytes(document.file_bytes),
+                document.path,
+                document.title
+            )?;
+        }
+
+        writeln!(f, "\nTop terms:")?;
+        for (term, documents) in &self.top_terms {
+            writeln!(f, "  {}: {} documents", term, documents)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,120 @@
+use serde::{Deserialize, Serialize};
+use tantivy::Index;
+use time::OffsetDateTime;
+use tracing::warn;
+
+/// How far an indexing run had got when the index was last committed
+///
+/// This is stored as the payload of each tantivy commit, so it is always in sync with the documents that made it to
+/// disk. Since every document records the state of its file, a run that was interrupted resumes by skipping what was
+/// already committed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct IndexingCheckpoint {
+    /// The unix timestamp of when the run started
+    pub(crate) started_at: i64,
+    /// The number of files indexed by this run so far
+    pub(crate) files_indexed: usize,
+    /// The number of deleted files removed by this run so far
+    pub(crate) files_removed: usize,
+    /// Whether the run got through every collected path
+    pub(crate) complete: bool,
+}
+
+impl IndexingCheckpoint {
+    /// Starts a new run, or picks up the run of an interrupted checkpoint
+    pub(crate) fn resume_or_start(previous: Option<IndexingCheckpoint>) -> Self {
+        match previous {
+            Some(checkpoint) if !checkpoint.complete => Self {
+                complete: false,
+                ..checkpoint
+            },
+            _ => Self {
+                started_at: OffsetDateTime::now_utc().unix_timestamp(),
+                files_indexed: 0,
+                files_removed: 0,
+                complete: false,
+            },
+        }
+    }
+
+    /// Reads the checkpoint of the last commit, if there was one
+    pub(crate) fn load(index: &Index) -> Option<Self> {
+        let payload = match index.load_metas() {
+            Ok(metas) => metas.payload?,
+            Err(e) => {
+                warn!("could not read the index metadata: {}", e);
+                return None;
+            }
+        };
+
+        match serde_json::from_str(&payload) {
+            Ok(checkpoint) => Some(checkpoint),
+            Err(e) => {
+                warn!("could not read the indexing checkpoint: {}", e);
+                None
+            }
+        }
+    }
+
+    pub(crate) fn to_payload(&self) -> String {
+        serde_json::to_string(self).expect("should be able to serialize the indexing checkpoint")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tantivy::{IndexWriter, TantivyDocument, schema::Schema};
+
+    /// Commits an empty index in memory with the checkpoint as its payload
+    fn committed_index(checkpoint: Option<&IndexingCheckpoint>) -> Index {
+        let index = Index::create_in_ram(Schema::builder().build());
+        let mut writer: IndexWriter<TantivyDocument> = index.writer(15_000_000).unwrap();
+        let mut prepared_commit = writer.prepare_commit().unwrap();
+        if let Some(checkpoint) = checkpoint {
+            prepared_commit.set_payload(&checkpoint.to_payload());
+        }
+        prepared_commit.commit().unwrap();
+        index
+    }
+
+    #[test]
+    fn loads_the_checkpoint_of_the_last_commit() {
+        let checkpoint = IndexingCheckpoint {
+            started_at: 1_700_000_000,
+            files_indexed: 42,
+            files_removed: 3,
+            complete: false,
+        };
+        let loaded = IndexingCheckpoint::load(&committed_index(Some(&checkpoint))).unwrap();
+        assert_eq!(loaded.started_at, checkpoint.started_at);
+        assert_eq!(loaded.files_indexed, 42);
+        assert_eq!(loaded.files_removed, 3);
+        assert!(!loaded.complete);
+
+        // an index committed without a checkpoint has none to resume
+        assert!(IndexingCheckpoint::load(&committed_index(None)).is_none());
+    }
+
+    #[test]
+    fn resumes_only_interrupted_runs() {
+        let interrupted = IndexingCheckpoint {
+            started_at: 1_700_000_000,
+            files_indexed: 42,
+            files_removed: 3,
+            complete: false,
+        };
+        let resumed = IndexingCheckpoint::resume_or_start(Some(interrupted.clone()));
+        assert_eq!(resumed.started_at, interrupted.started_at);
+        assert_eq!(resumed.files_indexed, 42);
+
+        let finished = IndexingCheckpoint {
+            complete: true,
+            ..interrupted
+        };
+        let started = IndexingCheckpoint::resume_or_start(Some(finished));
+        assert!(started.started_at > 1_700_000_000);
+        assert_eq!((started.files_indexed, started.files_removed), (0, 0));
+        assert!(!started.complete);
+    }
+}
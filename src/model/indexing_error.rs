@@ -0,0 +1,51 @@
+use std::{fmt, io};
+use tantivy::TantivyError;
+
+/// Everything that can go wrong while indexing, from reading a single file to committing the index
+#[derive(Debug)]
+pub enum IndexingError {
+    /// The index could not be opened, written to or committed
+    Index(TantivyError),
+    /// A file could not be read
+    Io(io::Error),
+    /// A file was read but its contents could not be parsed
+    Parse(String),
+    /// A converter panicked on a file, the worker survives and moves on to the next one
+    Panic(String),
+    /// A thread of the indexing pipeline stopped before it was done
+    PipelineStopped(&'static str),
+    /// Another process writes the index, so this one cannot
+    IndexingElsewhere,
+    /// Nobody is waiting for indexing to finish anymore, e.g. the UI switched to another profile
+    Abandoned,
+}
+
+impl fmt::Display for IndexingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexingError::Index(e) => write!(f, "index error: {}", e),
+            IndexingError::Io(e) => write!(f, "could not read file: {}", e),
+            IndexingError::Parse(message) => write!(f, "could not parse file: {}", message),
+            IndexingError::Panic(message) => write!(f, "converter panicked: {}", message),
+            IndexingError::PipelineStopped(stage) => {
+                write!(f, "the {} stopped unexpectedly", stage)
+            }
+            IndexingError::IndexingElsewhere => write!(f, "retsyn is indexing in another process"),
+            IndexingError::Abandoned => write!(f, "indexing was abandoned"),
+        }
+    }
+}
+
+impl std::error::Error for IndexingError {}
+
+impl From<TantivyError> for IndexingError {
+    fn from(e: TantivyError) -> Self {
+        IndexingError::Index(e)
+    }
+}
+
+impl From<io::Error> for IndexingError {
+    fn from(e: io::Error) -> Self {
+        IndexingError::Io(e)
+    }
+}
@@ -0,0 +1,43 @@
+use std::{
+    fs::{self, File, TryLockError},
+    path::Path,
+};
+use tantivy::{TantivyError, directory::error::LockError};
+
+/// The file locked by the process that writes the index, in the cache directory next to the index
+const INSTANCE_LOCK_FILE: &str = "indexer.lock";
+
+/// Held for as long as this process writes the index
+///
+/// Tantivy only locks the directory it writes to, and a rebuild writes to another one than the index that is searched.
+/// This lock covers all of them, so a second instance never touches the index directories and searches read-only.
+/// The operating system releases it when the process exits, even if it crashes.
+#[derive(Debug)]
+pub(crate) struct InstanceLock {
+    _file: File,
+}
+
+impl InstanceLock {
+    /// Takes the lock without waiting, failing with `LockError::LockBusy` if another process holds it
+    pub(crate) fn acquire(cache_dir: &Path) -> Result<Self, TantivyError> {
+        fs::create_dir_all(cache_dir)?;
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(cache_dir.join(INSTANCE_LOCK_FILE))?;
+        match file.try_lock() {
+            Ok(()) => Ok(Self { _file: file }),
+            Err(TryLockError::WouldBlock) => Err(TantivyError::LockFailure(
+                LockError::LockBusy,
+                Some("retsyn is indexing in another process".to_owned()),
+            )),
+            Err(TryLockError::Error(e)) => Err(e.into()),
+        }
+    }
+}
+
+/// Checks if the index could not be opened for writing because another process writes to it
+pub(crate) fn held_by_another_process(error: &TantivyError) -> bool {
+    matches!(error, TantivyError::LockFailure(LockError::LockBusy, _))
+}
@@ -1,3 +1,20 @@
+pub mod compaction;
+pub mod decoded_text;
+pub mod error_ledger;
+pub mod file_fingerprint;
+pub mod file_watcher;
 pub mod fulltext_index;
 pub mod index_entry;
+pub mod index_stats;
+pub mod indexing_checkpoint;
+pub mod indexing_error;
+pub mod instance_lock;
+pub mod path_priority;
+pub mod reindex_target;
+pub mod reorder_buffer;
+pub mod resource_governor;
+pub mod schema_version;
 pub mod search_result;
+pub mod search_service;
+pub mod size_limits;
+pub mod volume;
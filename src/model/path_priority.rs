@@ -0,0 +1,209 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashMap},
+    fs,
+    path::Path,
+    time::UNIX_EPOCH,
+};
+use tracing::{info, warn};
+
+use crate::{invocations::invocation_list::InvocationList, model::index_entry::IndexPath};
+
+/// How many filtered paths may wait for a converter, the most recent ones are picked from within this window
+///
+/// Once it is full no more paths are filtered, so the collectors block on the path channel rather than a whole
+/// walk piling up in memory.
+pub(crate) const PRIORITY_WINDOW: usize = 4096;
+
+/// Decides which of the paths waiting for a converter goes first
+///
+/// A path is as recent as the last time its file was modified or opened from the results, whichever is later. The
+/// most recent paths are converted first, so the files people care about become searchable early in a long run.
+pub(crate) struct PathPriorities {
+    /// The last time each path was opened or revealed, in seconds since the epoch
+    last_opened: HashMap<String, u64>,
+}
+
+impl PathPriorities {
+    /// Reads the invocation history of a profile, without it paths are ordered by modification time alone
+    pub(crate) fn load(cache_dir: &Path) -> Self {
+        let last_opened = match InvocationList::load_from_cache(cache_dir) {
+            Ok(invocations) => invocations.last_invoked_by_path(),
+            Err(e) => {
+                warn!("could not load invocations to prioritize indexing: {}", e);
+                HashMap::new()
+            }
+        };
+        info!(
+            "prioritizing indexing by modification time and {} opened paths",
+            last_opened.len()
+        );
+        Self { last_opened }
+    }
+
+    /// The later of when the file was last modified and last opened, in seconds since the epoch
+    pub(crate) fn recency(&self, path: &Path) -> u64 {
+        let modified = fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|modified| modified.as_secs())
+            .unwrap_or_default();
+        let opened = self
+            .last_opened
+            .get(path.to_string_lossy().as_ref())
+            .copied()
+            .unwrap_or_default();
+        modified.max(opened)
+    }
+}
+
+/// A path that passed the filter and waits for a converter, the most recent one is taken first
+pub(crate) struct QueuedPath {
+    recency: u64,
+    /// Paths that are equally recent keep the order they were collected in
+    arrival: Reverse<usize>,
+    pub(crate) index_path: IndexPath,
+    /// Identifies the file, see `Volumes::volume_path`
+    pub(crate) volume_path: String,
+    /// Whether the path is in the index already, its entry is deleted once it is handed to a converter
+    pub(crate) changed: bool,
+}
+
+impl QueuedPath {
+    fn new(
+        recency: u64,
+        arrival: usize,
+        index_path: IndexPath,
+        volume_path: String,
+        changed: bool,
+    ) -> Self {
+        Self {
+            recency,
+            arrival: Reverse(arrival),
+            index_path,
+            volume_path,
+            changed,
+        }
+    }
+}
+
+impl Ord for QueuedPath {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.recency, self.arrival).cmp(&(other.recency, other.arrival))
+    }
+}
+
+impl PartialOrd for QueuedPath {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for QueuedPath {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueuedPath {}
+
+/// The paths that passed the filter and wait for a converter, at most a window of them
+pub(crate) struct PathQueue {
+    queued: BinaryHeap<QueuedPath>,
+    /// The number of paths queued so far, equally recent paths are converted in that order
+    arrivals: usize,
+    window: usize,
+}
+
+impl PathQueue {
+    pub(crate) fn new(window: usize) -> Self {
+        Self {
+            queued: BinaryHeap::new(),
+            arrivals: 0,
+            window,
+        }
+    }
+
+    /// Whether another path may be queued, a full queue holds off filtering until a path was converted
+    pub(crate) fn has_room(&self) -> bool {
+        self.queued.len() < self.window
+    }
+
+    pub(crate) fn push(
+        &mut self,
+        recency: u64,
+        index_path: IndexPath,
+        volume_path: String,
+        changed: bool,
+    ) {
+        self.queued.push(QueuedPath::new(
+            recency,
+            self.arrivals,
+            index_path,
+            volume_path,
+            changed,
+        ));
+        self.arrivals += 1;
+    }
+
+    /// Takes the most recent path, of equally recent ones the one queued first
+    pub(crate) fn pop(&mut self) -> Option<QueuedPath> {
+        self.queued.pop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn push(queue: &mut PathQueue, recency: u64, name: &str) {
+        let path = PathBuf::from(format!("/notes/{}.md", name));
+        let volume_path = path.to_string_lossy().into_owned();
+        queue.push(recency, IndexPath::MarkdownFile(path), volume_path, false);
+    }
+
+    fn pop_all(queue: &mut PathQueue) -> Vec<String> {
+        std::iter::from_fn(|| queue.pop())
+            .map(|queued_path| queued_path.volume_path)
+            .collect()
+    }
+
+    #[test]
+    fn takes_most_recent_paths_first() {
+        let mut queue = PathQueue::new(8);
+        push(&mut queue, 100, "old");
+        push(&mut queue, 300, "today");
+        push(&mut queue, 200, "first-yesterday");
+        push(&mut queue, 200, "second-yesterday");
+        push(&mut queue, 0, "never-touched");
+
+        assert_eq!(
+            pop_all(&mut queue),
+            [
+                "/notes/today.md",
+                "/notes/first-yesterday.md",
+                "/notes/second-yesterday.md",
+                "/notes/old.md",
+                "/notes/never-touched.md",
+            ]
+        );
+    }
+
+    #[test]
+    fn prioritizes_within_window() {
+        let mut queue = PathQueue::new(2);
+        push(&mut queue, 100, "old");
+        assert!(queue.has_room());
+        push(&mut queue, 200, "newer");
+        assert!(!queue.has_room());
+
+        // a path that arrives once there is room again overtakes the older one still waiting
+        assert_eq!(queue.pop().unwrap().volume_path, "/notes/newer.md");
+        assert!(queue.has_room());
+        push(&mut queue, 300, "newest");
+        assert_eq!(pop_all(&mut queue), ["/notes/newest.md", "/notes/old.md"]);
+    }
+}
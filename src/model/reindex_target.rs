@@ -0,0 +1,78 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::collectors::{
+    aichat_session_files::AICHAT_SESSION_FILES_SOURCE, code_symbols::CODE_SYMBOLS_SOURCE,
+    image_files::IMAGE_FILES_SOURCE, markdown_files::MARKDOWN_FILES_SOURCE,
+    music_files::MUSIC_FILES_SOURCE, projects::PROJECTS_SOURCE,
+    reading_highlights::READING_HIGHLIGHTS_SOURCE, web_scrapbook_files::WEB_SCRAPBOOK_FILES_SOURCE,
+};
+
+/// The name of every source, as stored in the `source` field of its documents
+pub(crate) const SOURCES: [&str; 8] = [
+    AICHAT_SESSION_FILES_SOURCE,
+    MARKDOWN_FILES_SOURCE,
+    WEB_SCRAPBOOK_FILES_SOURCE,
+    IMAGE_FILES_SOURCE,
+    MUSIC_FILES_SOURCE,
+    READING_HIGHLIGHTS_SOURCE,
+    CODE_SYMBOLS_SOURCE,
+    PROJECTS_SOURCE,
+];
+
+/// The documents to rebuild when reindexing on demand
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReindexTarget {
+    /// Every document of one source e.g. `web_scrapbook_files`
+    Source(String),
+    /// Every document of any source whose file is in this directory or below it
+    Subtree(PathBuf),
+}
+
+impl fmt::Display for ReindexTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReindexTarget::Source(source) => write!(f, "source {}", source),
+            ReindexTarget::Subtree(path) => write!(f, "directory {}", path.display()),
+        }
+    }
+}
+
+impl ReindexTarget {
+    /// Reindexes a source, if it is the name of one
+    pub fn source(source: &str) -> Result<Self, String> {
+        if SOURCES.contains(&source) {
+            Ok(ReindexTarget::Source(source.to_owned()))
+        } else {
+            Err(format!(
+                "unknown source {}, expected one of: {}",
+                source,
+                SOURCES.join(", ")
+            ))
+        }
+    }
+
+    /// Reindexes a directory, a leading tilde is expanded and a relative path is taken from the current directory
+    pub fn subtree(path: &Path) -> std::io::Result<Self> {
+        let expanded = PathBuf::from(shellexpand::tilde(&path.to_string_lossy()).into_owned());
+        Ok(ReindexTarget::Subtree(std::path::absolute(expanded)?))
+    }
+
+    /// Checks if a path reported by a source is part of the target
+    pub(crate) fn contains(&self, source: &str, path: &Path) -> bool {
+        match self {
+            ReindexTarget::Source(target_source) => target_source == source,
+            ReindexTarget::Subtree(root) => path.starts_with(root),
+        }
+    }
+
+    /// Checks if the collector of a source may report paths that are part of the target
+    pub(crate) fn includes_source(&self, source: &str) -> bool {
+        match self {
+            ReindexTarget::Source(target_source) => target_source == source,
+            ReindexTarget::Subtree(_) => true,
+        }
+    }
+}
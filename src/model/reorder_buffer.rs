@@ -0,0 +1,96 @@
+use std::collections::{BTreeMap, btree_map};
+
+/// Puts the conversions that come back from the worker pool into the order their paths were handed out in
+///
+/// Each path gets the next sequence number when it is handed to a converter. A conversion that finishes ahead of an
+/// earlier one is held here until every conversion before it was taken.
+pub(crate) struct ReorderBuffer<T> {
+    /// Conversions that arrived ahead of an earlier one, keyed by sequence number
+    waiting: BTreeMap<usize, T>,
+    /// The sequence number of the conversion that is taken next
+    next: usize,
+    /// How many conversions may wait before no more paths should be handed out
+    capacity: usize,
+}
+
+impl<T> ReorderBuffer<T> {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            waiting: BTreeMap::new(),
+            next: 0,
+            capacity,
+        }
+    }
+
+    pub(crate) fn insert(&mut self, sequence: usize, item: T) {
+        self.waiting.insert(sequence, item);
+    }
+
+    /// Takes the next conversion in order along with its sequence number, `None` until it arrived
+    pub(crate) fn pop_next(&mut self) -> Option<(usize, T)> {
+        let item = self.waiting.remove(&self.next)?;
+        let sequence = self.next;
+        self.next += 1;
+        Some((sequence, item))
+    }
+
+    /// Whether so many conversions wait on a slow one that no more paths should be handed out
+    pub(crate) fn is_full(&self) -> bool {
+        self.waiting.len() >= self.capacity
+    }
+
+    /// Takes every waiting conversion in order, even if some before it never arrived
+    pub(crate) fn drain(&mut self) -> btree_map::IntoIter<usize, T> {
+        std::mem::take(&mut self.waiting).into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takes_conversions_in_sequence_order() {
+        let mut buffer = ReorderBuffer::new(8);
+        buffer.insert(2, "c");
+        buffer.insert(1, "b");
+        // nothing can be taken until the first conversion arrives
+        assert_eq!(buffer.pop_next(), None);
+
+        buffer.insert(0, "a");
+        buffer.insert(4, "e");
+        let taken: Vec<_> = std::iter::from_fn(|| buffer.pop_next()).collect();
+        assert_eq!(taken, [(0, "a"), (1, "b"), (2, "c")]);
+
+        // the conversion after a missing one waits
+        assert_eq!(buffer.pop_next(), None);
+        buffer.insert(3, "d");
+        assert_eq!(buffer.pop_next(), Some((3, "d")));
+        assert_eq!(buffer.pop_next(), Some((4, "e")));
+        assert_eq!(buffer.pop_next(), None);
+    }
+
+    #[test]
+    fn fills_up_with_conversions_waiting_on_a_slow_one() {
+        let mut buffer = ReorderBuffer::new(2);
+        buffer.insert(1, "b");
+        assert!(!buffer.is_full());
+        buffer.insert(2, "c");
+        assert!(buffer.is_full());
+
+        buffer.insert(0, "a");
+        assert_eq!(buffer.pop_next(), Some((0, "a")));
+        assert!(buffer.is_full());
+        assert_eq!(buffer.pop_next(), Some((1, "b")));
+        assert!(!buffer.is_full());
+    }
+
+    #[test]
+    fn drains_conversions_behind_one_that_never_arrived() {
+        let mut buffer = ReorderBuffer::new(8);
+        buffer.insert(3, "d");
+        buffer.insert(1, "b");
+        assert_eq!(buffer.drain().collect::<Vec<_>>(), [(1, "b"), (3, "d")]);
+        assert_eq!(buffer.pop_next(), None);
+    }
+}
@@ -0,0 +1,135 @@
+use std::thread;
+use tracing::{info, warn};
+
+use crate::config::Conf;
+
+/// Why indexing is on hold
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PauseReason {
+    /// Paused from the UI until it is resumed
+    User,
+    /// Waiting for the user to stop searching, see `index_only_when_idle`
+    SearchActive,
+}
+
+/// The least writer memory tantivy accepts for each of its indexing threads
+const WRITER_THREAD_MIN_HEAP_BYTES: usize = 15_000_000;
+
+/// The most indexing threads tantivy picks on its own
+const MAX_WRITER_THREADS: usize = 8;
+
+/// The number of threads the index writer uses on a machine with `cpus` CPUs, see `writer_threads`
+///
+/// A number that would leave a thread less than the memory it needs is lowered, rather than failing to open the index.
+pub(crate) fn writer_threads(config: &Conf, cpus: usize) -> usize {
+    let affordable_threads = (config.writer_heap_bytes / WRITER_THREAD_MIN_HEAP_BYTES).max(1);
+    match config.writer_threads {
+        0 => cpus.clamp(1, MAX_WRITER_THREADS).min(affordable_threads),
+        threads if threads > affordable_threads => {
+            warn!(
+                "{} writer threads need more than {} bytes of writer memory, using {} threads",
+                threads, config.writer_heap_bytes, affordable_threads
+            );
+            affordable_threads
+        }
+        threads => threads,
+    }
+}
+
+/// Lowers the CPU and IO priority of the calling thread, if `low_priority_indexing` is set
+///
+/// Threads spawned from this one inherit its priority, the search service must be spawned before calling this so
+/// searching stays as responsive as the UI. An unprivileged process cannot raise its priority again.
+pub(crate) fn lower_thread_priority(config: &Conf) {
+    if !config.low_priority_indexing {
+        return;
+    }
+    info!("lowering the CPU and IO priority of indexing");
+    lower_current_thread_priority();
+}
+
+/// Runs `f` on a thread with lowered priority, so the threads it spawns inherit that priority
+///
+/// The index writer spawns its indexing and merging threads when it is created, this lowers them without lowering the
+/// calling thread.
+pub(crate) fn with_lowered_priority<T: Send>(config: &Conf, f: impl FnOnce() -> T + Send) -> T {
+    thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                if config.low_priority_indexing {
+                    lower_current_thread_priority();
+                }
+                f()
+            })
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+/// The nice value of indexing threads, the lowest priority there is
+#[cfg(target_os = "linux")]
+const INDEXING_NICENESS: libc::c_int = 19;
+
+/// The best effort IO class at its lowest level, unlike the idle class this still makes progress on a busy disk
+#[cfg(target_os = "linux")]
+const INDEXING_IO_PRIORITY: libc::c_int = (2 << 13) | 7;
+
+#[cfg(target_os = "linux")]
+fn lower_current_thread_priority() {
+    // on linux both priorities belong to the thread rather than the process, addressed by its thread id
+    // SAFETY: these syscalls only read their integer arguments
+    unsafe {
+        let thread_id = libc::gettid() as libc::id_t;
+        if libc::setpriority(libc::PRIO_PROCESS, thread_id, INDEXING_NICENESS) != 0 {
+            warn!(
+                "could not lower the CPU priority of indexing: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+        // IOPRIO_WHO_PROCESS is 1, the syscall has no wrapper in libc
+        if libc::syscall(libc::SYS_ioprio_set, 1, thread_id, INDEXING_IO_PRIORITY) != 0 {
+            warn!(
+                "could not lower the IO priority of indexing: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn lower_current_thread_priority() {
+    tracing::debug!("lowering the priority of indexing is only supported on linux");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use confique::Config;
+
+    fn config(writer_threads: usize, writer_heap_bytes: usize) -> Conf {
+        let mut config = Conf::builder().load().unwrap();
+        config.writer_threads = writer_threads;
+        config.writer_heap_bytes = writer_heap_bytes;
+        config
+    }
+
+    #[test]
+    fn picks_one_writer_thread_per_cpu_within_the_writer_memory() {
+        assert_eq!(writer_threads(&config(0, 500_000_000), 4), 4);
+        assert_eq!(
+            writer_threads(&config(0, 500_000_000), 32),
+            MAX_WRITER_THREADS
+        );
+        assert_eq!(writer_threads(&config(0, 50_000_000), 16), 3);
+        assert_eq!(writer_threads(&config(0, 500_000_000), 0), 1);
+    }
+
+    #[test]
+    fn lowers_writer_threads_that_do_not_fit_the_writer_memory() {
+        assert_eq!(writer_threads(&config(2, 50_000_000), 16), 2);
+        assert_eq!(writer_threads(&config(12, 500_000_000), 4), 12);
+        assert_eq!(writer_threads(&config(6, 50_000_000), 16), 3);
+        // a single thread is always attempted, tantivy reports a writer memory that is too small for it
+        assert_eq!(writer_threads(&config(2, 10_000_000), 16), 1);
+    }
+}
@@ -0,0 +1,48 @@
+use atomicwrites::{AtomicFile, OverwriteBehavior::AllowOverwrite};
+use std::{fs, io::Write, path::Path};
+
+/// The version of the schema built by `tantivy_schema`
+///
+/// Bump this whenever a field is added, removed or changes its options, or the values a field is indexed with change
+/// e.g. how paths are spelled. An index with another version is rebuilt in the background on the next launch.
+pub(crate) const SCHEMA_VERSION: u32 = 7;
+
+/// The file in the index directory that records the schema version the index was built with
+const SCHEMA_VERSION_FILE: &str = "schema_version";
+
+/// Reads the schema version of the index in `index_dir`
+///
+/// Indexes built before the version was recorded count as version 0.
+pub(crate) fn read(index_dir: &Path) -> u32 {
+    fs::read_to_string(index_dir.join(SCHEMA_VERSION_FILE))
+        .ok()
+        .and_then(|version| version.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// Records that the index in `index_dir` is built with the current schema version
+pub(crate) fn write(index_dir: &Path) -> std::io::Result<()> {
+    AtomicFile::new(index_dir.join(SCHEMA_VERSION_FILE), AllowOverwrite)
+        .write(|f| f.write_all(SCHEMA_VERSION.to_string().as_bytes()))
+        .map_err(std::io::Error::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_written_schema_version() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let index_dir = temp_dir.path();
+
+        // an index from before versions were recorded
+        assert_eq!(read(index_dir), 0);
+
+        write(index_dir).unwrap();
+        assert_eq!(read(index_dir), SCHEMA_VERSION);
+
+        fs::write(index_dir.join(SCHEMA_VERSION_FILE), "not a version").unwrap();
+        assert_eq!(read(index_dir), 0);
+    }
+}
@@ -1,4 +1,8 @@
-use std::path::PathBuf;
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+};
 
 use egui::{Color32, Frame};
 use egui_commonmark::{CommonMarkCache, CommonMarkViewer};
@@ -10,7 +14,18 @@ use tantivy::{
 use time::{UtcOffset, format_description::well_known::Rfc2822};
 use tracing::{info, warn};
 
-use crate::model::fulltext_index::FulltextIndex;
+use crate::{
+    collectors::{
+        code_symbols::CODE_SYMBOLS_SOURCE, image_files::IMAGE_FILES_SOURCE,
+        music_files::MUSIC_FILES_SOURCE, reading_highlights::READING_HIGHLIGHTS_SOURCE,
+    },
+    config::Conf,
+    model::{fulltext_index::ResultFields, volume::Volumes},
+    ui::thumbnail_cache::ThumbnailCache,
+};
+
+/// Image formats that the image crate is built to decode for previews
+const PREVIEWABLE_IMAGE_EXTENSIONS: [&str; 4] = ["jpg", "jpeg", "png", "webp"];
 
 #[derive(Debug)]
 pub(crate) struct SearchResult {
@@ -18,6 +33,14 @@ pub(crate) struct SearchResult {
     indexed_at: DateTime,
     pub(crate) path: String,
     pub(crate) title: String,
+    /// The line within the file that the item starts on, e.g. for code symbols
+    line: Option<usize>,
+    /// Where the item is within the file, for files that hold more than one item e.g. the location of a highlight
+    anchor: Option<String>,
+    /// Whether only the start of the file was indexed because it is above its size limit
+    truncated: bool,
+    /// Whether the volume the file is on is not available, its documents stay searchable until it is back
+    offline: bool,
     body: Field,
     snippet: Snippet,
     tantivy_doc: TantivyDocument,
@@ -25,41 +48,77 @@ pub(crate) struct SearchResult {
 
 impl SearchResult {
     pub(crate) fn new(
-        fulltext_index: &FulltextIndex,
+        fields: &ResultFields,
+        volumes: &Volumes,
         doc: TantivyDocument,
         snippet: Snippet,
     ) -> Self {
         Self {
             source: doc
-                .get_first(fulltext_index.source_field)
+                .get_first(fields.source)
                 .map(|t| t.as_str())
                 .flatten()
                 .unwrap_or_default()
                 .to_owned(),
             indexed_at: doc
-                .get_first(fulltext_index.indexed_at_field)
+                .get_first(fields.indexed_at)
                 .map(|t| t.as_datetime())
                 .flatten()
                 .unwrap_or_default()
                 .to_owned(),
             path: doc
-                .get_first(fulltext_index.path_field)
+                .get_first(fields.path)
                 .map(|t| t.as_str())
                 .flatten()
                 .unwrap_or_default()
                 .to_owned(),
             title: doc
-                .get_first(fulltext_index.title_field)
+                .get_first(fields.title)
                 .map(|t| t.as_str())
                 .flatten()
                 .unwrap_or_default()
                 .to_owned(),
-            body: fulltext_index.body_field,
+            line: fields
+                .line
+                .and_then(|field| doc.get_first(field))
+                .and_then(|t| t.as_str())
+                .and_then(|l| l.parse().ok()),
+            anchor: Self::anchor(fields, &doc),
+            truncated: fields
+                .truncated
+                .and_then(|field| doc.get_first(field))
+                .and_then(|t| t.as_bool())
+                .unwrap_or(false),
+            // documents indexed before volumes were recorded are taken to be online
+            offline: fields
+                .volume
+                .and_then(|field| doc.get_first(field))
+                .and_then(|t| t.as_str())
+                .is_some_and(|volume| !volumes.is_available(volume)),
+            body: fields.body,
             snippet,
             tantivy_doc: doc,
         }
     }
 
+    /// The line of the item, or its location and date for highlights that have no line
+    fn anchor(fields: &ResultFields, doc: &TantivyDocument) -> Option<String> {
+        let text = |field: Option<Field>| {
+            field
+                .and_then(|field| doc.get_first(field))
+                .and_then(|t| t.as_str())
+                .map(|t| t.to_owned())
+        };
+        text(fields.line).or_else(|| {
+            let location = text(fields.location)?;
+            Some(format!(
+                "{}@{}",
+                location,
+                text(fields.date).unwrap_or_default()
+            ))
+        })
+    }
+
     pub(crate) fn indexed_at(&self) -> String {
         self.indexed_at
             .into_offset(
@@ -74,10 +133,26 @@ impl SearchResult {
         &self.path
     }
 
+    /// Identifies the item among the results, the path alone is shared by all items of the same file
+    pub(crate) fn key(&self) -> String {
+        match &self.anchor {
+            Some(anchor) => format!("{}#{}", self.path, anchor),
+            None => self.path.clone(),
+        }
+    }
+
     pub(crate) fn title(&self) -> &str {
         &self.title
     }
 
+    pub(crate) fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    pub(crate) fn offline(&self) -> bool {
+        self.offline
+    }
+
     pub(crate) fn body(&self) -> String {
         // TODO might want to grab this from the file directly rather than storing the whole field
         self.tantivy_doc
@@ -114,7 +189,40 @@ impl SearchResult {
             });
     }
 
-    pub(crate) fn draw_preview_area(&self, ui: &mut egui::Ui) {
+    /// Checks if the preview can show the image itself rather than its indexed metadata
+    fn has_image_preview(&self) -> bool {
+        Path::new(&self.path)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .map(|e| PREVIEWABLE_IMAGE_EXTENSIONS.contains(&e.as_str()))
+            .unwrap_or(false)
+    }
+
+    pub(crate) fn draw_preview_area(&self, ui: &mut egui::Ui, thumbnails: &ThumbnailCache) {
+        if self.offline {
+            ui.colored_label(
+                Color32::DARK_RED,
+                "The drive this file is on is not available, this is the text it had when it was last indexed.",
+            );
+            ui.separator();
+        } else if self.source == IMAGE_FILES_SOURCE {
+            if self.has_image_preview() {
+                thumbnails.show(ui, &self.path);
+                return;
+            }
+            // e.g. HEIC photos, their metadata is indexed but they cannot be decoded
+            ui.label("No preview available for this image format.");
+            ui.separator();
+        }
+
+        if self.truncated {
+            ui.colored_label(
+                Color32::DARK_RED,
+                "This file is above its size limit, only the start of it was indexed.",
+            );
+            ui.separator();
+        }
+
         ui.style_mut().url_in_tooltip = true;
         let text = self.body();
         let mut cache = CommonMarkCache::default();
@@ -122,6 +230,13 @@ impl SearchResult {
     }
 
     pub(crate) fn open(&self) {
+        if self.offline {
+            warn!(
+                "unable to open item, its drive is not available: {}",
+                self.path
+            );
+            return;
+        }
         info!("Revealing item: {}", self.path);
         if let Some(path) = PathBuf::from(self.path.clone()).parent() {
             // TODO handle errors in open and display them to the user in the UI
@@ -132,7 +247,43 @@ impl SearchResult {
         }
     }
 
-    pub(crate) fn reveal(&self) {
+    /// Hands the item to a user configured command, the path is appended after any arguments in the command
+    fn open_with_command(&self, command: &str) {
+        info!("Opening item: {} with: {}", self.path, command);
+        let mut args = command.split_whitespace();
+        let Some(program) = args.next() else {
+            warn!("unable to open item, the command is empty: {}", self.path);
+            return;
+        };
+
+        // TODO handle errors in the command and display them to the user in the UI
+        match Command::new(program).args(args).arg(&self.path).spawn() {
+            Ok(_) => info!("successfully opened item: {}", self.path),
+            Err(e) => warn!("unable to open item with {}: {}", program, e),
+        }
+    }
+
+    pub(crate) fn reveal(&self, config: &Conf) {
+        if self.offline {
+            warn!(
+                "unable to open item, its drive is not available: {}",
+                self.path
+            );
+            return;
+        }
+        if self.source == MUSIC_FILES_SOURCE {
+            self.open_with_command(&config.music_player_command);
+            return;
+        }
+
+        // jump straight to the definition or highlight if we know where it is and the user has an editor set
+        if let (CODE_SYMBOLS_SOURCE | READING_HIGHLIGHTS_SOURCE, Some(line), Ok(editor)) =
+            (self.source.as_str(), self.line, env::var("EDITOR"))
+        {
+            self.open_with_command(&format!("{} +{}", editor, line));
+            return;
+        }
+
         info!("Opening item: {}", self.path);
         // TODO handle errors in reveal and display them to the user in the UI
         match open::that(self.path.clone()) {
@@ -0,0 +1,329 @@
+use std::{
+    path::Path,
+    sync::{
+        Arc, RwLock,
+        atomic::{AtomicU64, Ordering},
+        mpsc::{Receiver, Sender},
+    },
+    time::{Duration, Instant},
+};
+use tantivy::{
+    Directory, Index, IndexReader, Opstamp, ReloadPolicy, Searcher, TantivyDocument, TantivyError,
+    Term,
+    collector::TopDocs,
+    directory::{WatchCallback, WatchHandle},
+    query::{BooleanQuery, Occur, Query, QueryParser, TermQuery},
+    schema::IndexRecordOption,
+    snippet::SnippetGenerator,
+};
+use tracing::{info, warn};
+
+use crate::{
+    messages::{index_request::SearchRequest, index_results::IndexResults},
+    model::{
+        fulltext_index::{ResultFields, open_index},
+        search_result::SearchResult,
+        volume::{VolumeLabels, Volumes},
+    },
+};
+
+/// The index that searches run against, along with the fields its results are read from
+#[derive(Clone)]
+pub(crate) struct SearchableIndex {
+    index: Index,
+    reader: IndexReader,
+    fields: ResultFields,
+    /// The opstamp of the last commit the reader was reloaded to, shared with every clone
+    opstamp: Arc<AtomicU64>,
+}
+
+impl SearchableIndex {
+    /// Searches an index that is being written to, the reader picks up its commits
+    pub(crate) fn new(index: &Index, reader: &IndexReader) -> Result<Self, TantivyError> {
+        Ok(Self {
+            index: index.clone(),
+            reader: reader.clone(),
+            fields: ResultFields::new(&index.schema())?,
+            opstamp: Arc::new(AtomicU64::new(index.load_metas()?.opstamp)),
+        })
+    }
+
+    /// Opens an index that this process does not write to, with the schema it was built with
+    pub(crate) fn open_read_only(index_dir: &Path) -> Result<Self, TantivyError> {
+        let index = open_index(index_dir)?;
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()?;
+        let fields = ResultFields::new(&index.schema())?;
+        let opstamp = Arc::new(AtomicU64::new(index.load_metas()?.opstamp));
+        Ok(Self {
+            index,
+            reader,
+            fields,
+            opstamp,
+        })
+    }
+
+    /// Records a commit once the reader was reloaded, results are tagged with it from then on
+    pub(crate) fn record_commit(&self, opstamp: Opstamp) {
+        self.opstamp.store(opstamp, Ordering::Release);
+    }
+
+    pub(crate) fn opstamp(&self) -> Opstamp {
+        self.opstamp.load(Ordering::Acquire)
+    }
+
+    /// Reloads the reader whenever another process commits to the index, and tells the UI about it
+    ///
+    /// The commits are only watched for as long as the returned handle lives.
+    pub(crate) fn watch_commits(
+        &self,
+        results_sender: Sender<IndexResults>,
+    ) -> Result<WatchHandle, TantivyError> {
+        let searchable_index = self.clone();
+        let on_commit = WatchCallback::new(move || {
+            let opstamp = match searchable_index
+                .reader
+                .reload()
+                .and_then(|_| searchable_index.index.load_metas())
+            {
+                Ok(metas) => metas.opstamp,
+                Err(e) => {
+                    warn!("could not reload the index after a commit: {}", e);
+                    return;
+                }
+            };
+            searchable_index.record_commit(opstamp);
+            if let Err(e) = results_sender.send(IndexResults::Committed(opstamp)) {
+                info!("could not send commit: {}", e);
+            }
+        });
+        self.index.directory().watch(on_commit)
+    }
+}
+
+/// How long the detected volumes are trusted before they are detected again, drives come and go between searches
+const VOLUME_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The index shared between the indexing thread and the search thread
+///
+/// The indexing thread swaps in another index when a rebuild takes over, searches pick it up from then on.
+pub(crate) type SharedSearchableIndex = Arc<RwLock<SearchableIndex>>;
+
+/// Answers search requests on a thread of its own, so searching never waits on indexing
+pub(crate) struct SearchService {
+    searchable_index: SharedSearchableIndex,
+    /// Results on volumes that are not available are marked offline
+    volume_labels: VolumeLabels,
+    volumes: Volumes,
+    /// When `volumes` were detected, they are detected again once `VOLUME_REFRESH_INTERVAL` passed
+    volumes_detected_at: Instant,
+    request_receiver: Receiver<SearchRequest>,
+    results_sender: Sender<IndexResults>,
+}
+
+impl SearchService {
+    pub(crate) fn new(
+        searchable_index: SharedSearchableIndex,
+        volume_labels: VolumeLabels,
+        request_receiver: Receiver<SearchRequest>,
+        results_sender: Sender<IndexResults>,
+    ) -> Self {
+        Self {
+            searchable_index,
+            volumes: Volumes::detect(&volume_labels),
+            volumes_detected_at: Instant::now(),
+            volume_labels,
+            request_receiver,
+            results_sender,
+        }
+    }
+
+    /// Serves searches until the UI is gone
+    ///
+    /// Requests that pile up while a search runs are coalesced, only the latest one is answered since the UI
+    /// ignores results for anything older.
+    pub(crate) fn run(mut self) {
+        while let Ok(mut request) = self.request_receiver.recv() {
+            while let Ok(newer_request) = self.request_receiver.try_recv() {
+                if newer_request.request_id >= request.request_id {
+                    info!("skipping superseded search request {}", request.request_id);
+                    request = newer_request;
+                }
+            }
+            self.search(request);
+        }
+        info!("search requests closed, stopping the search service");
+    }
+
+    fn search(&mut self, request: SearchRequest) {
+        // a poisoned lock still holds a usable index, the swap that panicked either happened or it did not
+        let searchable_index = self
+            .searchable_index
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone();
+        let SearchableIndex {
+            index,
+            reader,
+            fields,
+            ..
+        } = &searchable_index;
+        // read before taking the searcher, so the results are at least as new as the commit they are tagged with
+        let opstamp = searchable_index.opstamp();
+        let searcher = reader.searcher();
+        let default_fields = vec![fields.title, fields.body];
+
+        // setup the query here
+        let mut query_parser = QueryParser::for_index(index, default_fields.clone());
+
+        if request.query_conjunction {
+            query_parser.set_conjunction_by_default();
+        }
+
+        // set fields fuzzy here
+        // TODO add advanced search config where individual field can have its fuzziness set independently
+        if request.fuzziness > 0 {
+            for field in &default_fields {
+                query_parser.set_field_fuzzy(*field, true, request.fuzziness, true);
+            }
+        }
+
+        // parse the query here
+        let (query, query_errors) = if request.lenient {
+            query_parser.parse_query_lenient(&request.query)
+        } else {
+            match query_parser.parse_query(&request.query) {
+                Ok(query) => (query, vec![]),
+                // if we have an error in non-lenient parsing, return with no results
+                Err(error) => {
+                    if let Err(e) = self.results_sender.send(IndexResults::SearchResults {
+                        request_id: request.request_id,
+                        opstamp,
+                        results: Ok((vec![], vec![error])),
+                    }) {
+                        warn!("should be able to send results: {}", e);
+                    }
+
+                    return;
+                }
+            }
+        };
+
+        // the placeholders of files without entries have no text, but would still match e.g. `source:` queries
+        let query: Box<dyn Query> = match fields.placeholder {
+            Some(placeholder) => Box::new(BooleanQuery::new(vec![
+                (Occur::Must, query),
+                (
+                    Occur::MustNot,
+                    Box::new(TermQuery::new(
+                        Term::from_field_bool(placeholder, true),
+                        IndexRecordOption::Basic,
+                    )),
+                ),
+            ])),
+            None => query,
+        };
+
+        // search errors are passed on to the UI along with the results
+        let results =
+            Self::collect_search_results(&searcher, fields, self.volumes(), &*query, request.limit)
+                .map(|documents| (documents, query_errors));
+
+        let num_hits = results.as_ref().map(|(d, _)| d.len()).unwrap_or_default();
+        match self.results_sender.send(IndexResults::SearchResults {
+            request_id: request.request_id,
+            opstamp,
+            results,
+        }) {
+            Ok(_) => info!(
+                "sending {} results for query {}: {}",
+                num_hits, request.request_id, request.query
+            ),
+            Err(e) => warn!("should be able to send results: {}", e),
+        };
+    }
+
+    /// The volumes that are available, detected again if the last detection is too old to trust
+    fn volumes(&mut self) -> &Volumes {
+        if self.volumes_detected_at.elapsed() >= VOLUME_REFRESH_INTERVAL {
+            self.volumes = Volumes::detect(&self.volume_labels);
+            self.volumes_detected_at = Instant::now();
+        }
+        &self.volumes
+    }
+
+    /// Runs a parsed query and loads the top documents along with their snippets
+    fn collect_search_results(
+        searcher: &Searcher,
+        fields: &ResultFields,
+        volumes: &Volumes,
+        query: &dyn Query,
+        limit: usize,
+    ) -> Result<Vec<SearchResult>, TantivyError> {
+        // perform the search
+        let top_docs = searcher.search(query, &TopDocs::with_limit(limit))?;
+
+        // create a snippet generator so we can draw snippets with highlights
+        let snippet_generator = SnippetGenerator::create(searcher, query, fields.body)?;
+
+        let mut documents: Vec<SearchResult> = Vec::default();
+        for (_score, doc_address) in top_docs {
+            let retrieved_doc: TantivyDocument = searcher.doc(doc_address)?;
+            let snippet = snippet_generator.snippet_from_doc(&retrieved_doc);
+            documents.push(SearchResult::new(fields, volumes, retrieved_doc, snippet));
+        }
+        Ok(documents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+
+    use super::*;
+    use crate::model::fulltext_index::tantivy_schema;
+
+    fn request(request_id: usize) -> SearchRequest {
+        SearchRequest {
+            request_id,
+            query: format!("query {}", request_id),
+            limit: 10,
+            lenient: true,
+            query_conjunction: false,
+            fuzziness: 0,
+        }
+    }
+
+    #[test]
+    fn answers_only_the_latest_queued_request() {
+        let index = Index::create_in_ram(tantivy_schema());
+        let reader = index.reader().unwrap();
+        let searchable_index = SearchableIndex::new(&index, &reader).unwrap();
+        let (request_sender, request_receiver) = channel();
+        let (results_sender, results_receiver) = channel();
+
+        // the requests pile up before the service gets to them, one that arrives late is older than the rest
+        for request_id in [1, 2, 4, 3] {
+            request_sender.send(request(request_id)).unwrap();
+        }
+        drop(request_sender);
+        SearchService::new(
+            Arc::new(RwLock::new(searchable_index)),
+            VolumeLabels::new(),
+            request_receiver,
+            results_sender,
+        )
+        .run();
+
+        let answered: Vec<_> = results_receiver
+            .iter()
+            .map(|results| match results {
+                IndexResults::SearchResults { request_id, .. } => request_id,
+                _ => panic!("only search results are sent"),
+            })
+            .collect();
+        assert_eq!(answered, [4]);
+    }
+}
@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::config::Conf;
+
+/// What to do with a file that is larger than the size limit of its source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LargeFilePolicy {
+    /// Leave the file out of the index, it is listed in the error ledger instead
+    Skip,
+    /// Index the first bytes of the file up to the limit, the document is marked as truncated
+    Truncate,
+    /// Index the whole file as a series of documents of up to the limit each
+    Stream,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SizeLimit {
+    pub(crate) max_bytes: u64,
+    pub(crate) policy: LargeFilePolicy,
+}
+
+/// The size limit of every source, resolved from the config once so the conversion workers can share it
+#[derive(Debug, Clone)]
+pub(crate) struct SizeLimits {
+    default: SizeLimit,
+    per_source: HashMap<String, SizeLimit>,
+}
+
+impl SizeLimits {
+    pub(crate) fn new(config: &Conf) -> Self {
+        Self {
+            default: SizeLimit {
+                max_bytes: config.max_file_bytes,
+                policy: config.large_file_policy,
+            },
+            per_source: config.source_size_limits.clone(),
+        }
+    }
+
+    pub(crate) fn for_source(&self, source: &str) -> SizeLimit {
+        self.per_source.get(source).copied().unwrap_or(self.default)
+    }
+}
@@ -0,0 +1,336 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::debug;
+
+/// Labels for volumes keyed by name, each naming the directory a volume is mounted at, see `volume_labels`
+pub(crate) type VolumeLabels = HashMap<String, PathBuf>;
+
+/// Filesystems whose files live on another host, identified by what they were mounted from e.g. `nas:/export`
+#[cfg(target_os = "linux")]
+const NETWORK_FILESYSTEMS: [&str; 7] = ["nfs", "nfs4", "cifs", "smb3", "smbfs", "fuse.sshfs", "9p"];
+
+/// A mounted filesystem, or a labelled directory, that indexed files live on
+#[derive(Debug, Clone)]
+struct Volume {
+    /// The filesystem UUID, the network source, a configured label, or the host and mount point as a last resort
+    id: String,
+    mount_point: PathBuf,
+    /// The directory of the filesystem that is mounted at `mount_point`, `/` unless a subdirectory or subvolume is
+    /// mounted
+    root: PathBuf,
+}
+
+/// The volumes that are available right now
+///
+/// A file is identified by the volume it is on and its path within that volume, so the same files are recognized when
+/// a drive is mounted somewhere else, and the files of a drive that is not mounted are known to be offline rather than
+/// deleted.
+#[derive(Debug, Clone)]
+pub(crate) struct Volumes {
+    /// The deepest mount point first, so the first volume containing a path is the one it is on
+    volumes: Vec<Volume>,
+}
+
+impl Volumes {
+    /// Reads the mounted filesystems and the labelled directories that are present
+    ///
+    /// A labelled directory counts as present if it is not empty, since the mount point of an unmounted drive usually
+    /// still exists.
+    pub(crate) fn detect(labels: &VolumeLabels) -> Self {
+        let mut volumes: Vec<Volume> = labels
+            .iter()
+            .map(|(label, dir)| Volume {
+                id: label.clone(),
+                mount_point: PathBuf::from(shellexpand::tilde(&dir.to_string_lossy()).into_owned()),
+                root: PathBuf::from("/"),
+            })
+            .filter(|volume| {
+                fs::read_dir(&volume.mount_point).is_ok_and(|mut entries| entries.next().is_some())
+            })
+            .collect();
+        volumes.extend(mounted_volumes());
+
+        // a path outside of every mount point e.g. a relative one is taken to be on this host
+        volumes.push(Volume {
+            id: host_name(),
+            mount_point: PathBuf::new(),
+            root: PathBuf::new(),
+        });
+
+        // labels come first, so a label on a mount point wins over the filesystem UUID
+        volumes.sort_by_key(|volume| std::cmp::Reverse(volume.mount_point.components().count()));
+        debug!("detected {} volumes", volumes.len());
+        Self { volumes }
+    }
+
+    /// The id of the volume a path is on, and the volume path that identifies it, see `volume_path`
+    ///
+    /// Symlinks are resolved first, so a file has the same volume path however it was reached.
+    pub(crate) fn locate(&self, path: &Path) -> (&str, String) {
+        let path = canonical_path(path);
+        for volume in &self.volumes {
+            if let Ok(relative) = path.strip_prefix(&volume.mount_point) {
+                let volume_path = volume.root.join(relative);
+                return (
+                    &volume.id,
+                    format!("{}:{}", volume.id, volume_path.to_string_lossy()),
+                );
+            }
+        }
+        // the host volume contains every path
+        unreachable!("no volume contains {}", path.to_string_lossy())
+    }
+
+    /// Identifies a path regardless of where its volume is mounted, the volume id and the path within the volume
+    /// e.g. `0a3c-41f2:/photos/2024/beach.jpg`
+    pub(crate) fn volume_path(&self, path: &Path) -> String {
+        self.locate(path).1
+    }
+
+    /// Where the file with the given volume path is right now, `None` if its volume is not available
+    pub(crate) fn resolve(&self, volume_path: &str) -> Option<PathBuf> {
+        self.volumes.iter().find_map(|volume| {
+            let volume_path = Path::new(volume_path.strip_prefix(&volume.id)?.strip_prefix(':')?);
+            // the host volume only holds relative paths, an absolute one belongs to a volume with a longer id
+            if volume_path.is_absolute() != volume.root.is_absolute() {
+                return None;
+            }
+            let relative = volume_path.strip_prefix(&volume.root).ok()?;
+            Some(volume.mount_point.join(relative))
+        })
+    }
+
+    pub(crate) fn is_available(&self, volume_id: &str) -> bool {
+        self.volumes.iter().any(|volume| volume.id == volume_id)
+    }
+}
+
+/// The path with symlinks resolved and `.` and `..` removed, so a file reached through a symlink or through another
+/// spelling of its root is recognized as the same file
+///
+/// A path that does not exist anymore e.g. a deleted file is resolved as far as its nearest ancestor that does.
+pub(crate) fn canonical_path(path: &Path) -> PathBuf {
+    if let Ok(canonical) = fs::canonicalize(path) {
+        return canonical;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(file_name)) if !parent.as_os_str().is_empty() => {
+            canonical_path(parent).join(file_name)
+        }
+        _ => path.to_path_buf(),
+    }
+}
+
+/// The name of this host, which identifies volumes that have nothing better to go by
+fn host_name() -> String {
+    fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .map(|name| name.trim().to_owned())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "localhost".to_owned())
+}
+
+#[cfg(target_os = "linux")]
+fn mounted_volumes() -> Vec<Volume> {
+    let mountinfo = match fs::read_to_string("/proc/self/mountinfo") {
+        Ok(mountinfo) => mountinfo,
+        Err(e) => {
+            tracing::warn!("could not read the mounted filesystems: {}", e);
+            return vec![];
+        }
+    };
+
+    // the UUIDs of block devices are links to the devices in /dev/disk/by-uuid
+    let uuids: HashMap<PathBuf, String> = fs::read_dir("/dev/disk/by-uuid")
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let device = fs::canonicalize(entry.path()).ok()?;
+            Some((device, entry.file_name().to_string_lossy().into_owned()))
+        })
+        .collect();
+    let host_name = host_name();
+
+    mountinfo
+        .lines()
+        .filter_map(|line| {
+            // e.g. `36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw,errors=continue`
+            let (mount, filesystem) = line.split_once(" - ")?;
+            let mut mount_fields = mount.split(' ').skip(3);
+            let root = PathBuf::from(unescape_mount_field(mount_fields.next()?));
+            let mount_point = PathBuf::from(unescape_mount_field(mount_fields.next()?));
+            let mut filesystem_fields = filesystem.split(' ');
+            let filesystem_type = filesystem_fields.next()?;
+            let source = unescape_mount_field(filesystem_fields.next()?);
+
+            let uuid = fs::canonicalize(&source)
+                .ok()
+                .and_then(|device| uuids.get(&device).cloned());
+            let id = match uuid {
+                Some(uuid) => uuid,
+                None if NETWORK_FILESYSTEMS.contains(&filesystem_type) => source,
+                None => format!("{}:{}", host_name, mount_point.to_string_lossy()),
+            };
+            Some(Volume {
+                id,
+                mount_point,
+                root,
+            })
+        })
+        .collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn mounted_volumes() -> Vec<Volume> {
+    debug!("detecting mounted volumes is only supported on linux, only labelled volumes are known");
+    vec![]
+}
+
+/// Decodes the octal escapes of spaces, tabs, newlines and backslashes in a field of `/proc/self/mountinfo`
+#[cfg(target_os = "linux")]
+fn unescape_mount_field(field: &str) -> String {
+    let mut unescaped = String::with_capacity(field.len());
+    let mut rest = field;
+    while let Some(index) = rest.find('\\') {
+        unescaped.push_str(&rest[..index]);
+        let escape = rest.get(index + 1..index + 4);
+        match escape.and_then(|digits| u8::from_str_radix(digits, 8).ok()) {
+            Some(byte) => {
+                unescaped.push(byte as char);
+                rest = &rest[index + 4..];
+            }
+            None => {
+                unescaped.push('\\');
+                rest = &rest[index + 1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A USB drive, a subdirectory of a network share, and this host, deepest mount point first like `detect`
+    fn volumes() -> Volumes {
+        Volumes {
+            volumes: vec![
+                Volume {
+                    id: "0a3c-41f2".to_owned(),
+                    mount_point: PathBuf::from("/media/usb"),
+                    root: PathBuf::from("/"),
+                },
+                Volume {
+                    id: "nas:/export".to_owned(),
+                    mount_point: PathBuf::from("/mnt/photos"),
+                    root: PathBuf::from("/photos"),
+                },
+                Volume {
+                    id: "myhost".to_owned(),
+                    mount_point: PathBuf::new(),
+                    root: PathBuf::new(),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn locates_paths_within_their_volume() {
+        let volumes = volumes();
+        assert_eq!(
+            volumes.locate(Path::new("/media/usb/photos/beach.jpg")),
+            ("0a3c-41f2", "0a3c-41f2:/photos/beach.jpg".to_owned())
+        );
+        // a subdirectory of the share is mounted, the volume path is within the whole share
+        assert_eq!(
+            volumes.volume_path(Path::new("/mnt/photos/2024/beach.jpg")),
+            "nas:/export:/photos/2024/beach.jpg"
+        );
+        assert_eq!(
+            volumes.volume_path(Path::new("notes/todo.md")),
+            "myhost:notes/todo.md"
+        );
+    }
+
+    #[test]
+    fn resolves_volume_paths_where_their_volume_is_mounted() {
+        let volumes = volumes();
+        for path in [
+            "/media/usb/photos/beach.jpg",
+            "/mnt/photos/2024/beach.jpg",
+            "notes/todo.md",
+        ] {
+            let volume_path = volumes.volume_path(Path::new(path));
+            assert_eq!(volumes.resolve(&volume_path), Some(PathBuf::from(path)));
+        }
+
+        // the same drive mounted somewhere else
+        let mut moved = volumes.clone();
+        moved.volumes[0].mount_point = PathBuf::from("/run/media/usb");
+        assert_eq!(
+            moved.resolve("0a3c-41f2:/photos/beach.jpg"),
+            Some(PathBuf::from("/run/media/usb/photos/beach.jpg"))
+        );
+
+        // a drive that is not mounted, and a path outside of the mounted part of the share
+        assert_eq!(volumes.resolve("77aa-0001:/photos/beach.jpg"), None);
+        assert!(!volumes.is_available("77aa-0001"));
+        assert_eq!(volumes.resolve("nas:/export:/music/song.flac"), None);
+        // an absolute path with the host's id belongs to a volume whose id starts with it
+        assert_eq!(volumes.resolve("myhost:/mnt:/notes/todo.md"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn canonical_paths_resolve_symlinks_of_missing_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        fs::create_dir_all(dir.join("notes")).unwrap();
+        fs::write(dir.join("notes/todo.md"), "").unwrap();
+        std::os::unix::fs::symlink(dir.join("notes"), dir.join("link")).unwrap();
+        let notes = fs::canonicalize(dir.join("notes")).unwrap();
+
+        assert_eq!(
+            canonical_path(&dir.join("link/todo.md")),
+            notes.join("todo.md")
+        );
+        assert_eq!(
+            canonical_path(&dir.join("link/../notes/./todo.md")),
+            notes.join("todo.md")
+        );
+        // a deleted file is resolved through its nearest ancestor that exists
+        assert_eq!(
+            canonical_path(&dir.join("link/gone/done.md")),
+            notes.join("gone/done.md")
+        );
+        assert_eq!(
+            canonical_path(Path::new("retsyn-missing/todo.md")),
+            PathBuf::from("retsyn-missing/todo.md")
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn unescapes_mount_fields() {
+        assert_eq!(
+            unescape_mount_field("/media/my\\040drive"),
+            "/media/my drive"
+        );
+        assert_eq!(
+            unescape_mount_field("tab\\011and\\012newline"),
+            "tab\tand\nnewline"
+        );
+        assert_eq!(unescape_mount_field("back\\134slash"), "back\\slash");
+        // anything that is not an octal escape is kept as it is
+        assert_eq!(unescape_mount_field("not\\9xescaped\\"), "not\\9xescaped\\");
+        assert_eq!(unescape_mount_field("plain"), "plain");
+    }
+}
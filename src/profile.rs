@@ -0,0 +1,198 @@
+use confique::Config;
+use std::{
+    fmt, fs,
+    path::PathBuf,
+    sync::{LazyLock, RwLock},
+};
+use tracing::{info, warn};
+
+use crate::{config::Conf, model::size_limits::LargeFilePolicy, ui::retsyn_app::PROJECT_DIRS};
+
+/// The environment variable that selects a profile if `--profile` is not given
+pub const PROFILE_ENV_VAR: &str = "RETSYN_PROFILE";
+
+/// The profile used if none is selected, it keeps its files where retsyn kept them before there were profiles
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// The name of the config file in the config directory of a profile
+const CONFIG_FILE: &str = "retsyn.toml";
+
+/// The directory below the config and cache directories that holds the named profiles
+const PROFILES_DIR: &str = "profiles";
+
+static ACTIVE_PROFILE: LazyLock<RwLock<Profile>> =
+    LazyLock::new(|| RwLock::new(Profile::default()));
+
+/// A named set of config, search index and invocation history e.g. "work" or "personal"
+///
+/// Each profile is indexed and searched on its own, and another process can index one profile while this one indexes
+/// another.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    name: String,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            name: DEFAULT_PROFILE.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl Profile {
+    /// A profile by name, the name becomes a directory so it is limited to letters, digits, `-` and `_`
+    pub fn new(name: &str) -> Result<Self, String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("the profile name is empty".to_owned());
+        }
+        if !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(format!(
+                "the profile name {:?} may only contain letters, digits, '-' and '_'",
+                name
+            ));
+        }
+        Ok(Self {
+            name: name.to_owned(),
+        })
+    }
+
+    /// The profile the config, index and invocation history are read from and written to
+    pub fn active() -> Self {
+        ACTIVE_PROFILE
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Makes this the active profile, an index that is already open keeps using the profile it was opened with
+    pub fn activate(self) {
+        info!("activating profile: {}", self);
+        *ACTIVE_PROFILE
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = self;
+    }
+
+    /// The default profile and every profile that has a config, sorted by name after the default
+    pub fn list() -> Vec<Self> {
+        let mut names: Vec<String> = fs::read_dir(PROJECT_DIRS.config_dir().join(PROFILES_DIR))
+            .into_iter()
+            .flatten()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.path().join(CONFIG_FILE).is_file())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .filter(|name| name != DEFAULT_PROFILE)
+            .collect();
+        names.sort();
+
+        let mut profiles = vec![Self::default()];
+        profiles.extend(names.iter().filter_map(|name| Self::new(name).ok()));
+        profiles
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.name == DEFAULT_PROFILE
+    }
+
+    pub fn config_dir(&self) -> PathBuf {
+        if self.is_default() {
+            PROJECT_DIRS.config_dir().to_path_buf()
+        } else {
+            PROJECT_DIRS
+                .config_dir()
+                .join(PROFILES_DIR)
+                .join(&self.name)
+        }
+    }
+
+    /// Holds the index unless `index_dir` is configured, and everything else the profile keeps between runs
+    pub fn cache_dir(&self) -> PathBuf {
+        if self.is_default() {
+            PROJECT_DIRS.cache_dir().to_path_buf()
+        } else {
+            PROJECT_DIRS.cache_dir().join(PROFILES_DIR).join(&self.name)
+        }
+    }
+
+    pub fn config_path(&self) -> PathBuf {
+        self.config_dir().join(CONFIG_FILE)
+    }
+
+    pub(crate) fn error_ledger_path(&self) -> PathBuf {
+        self.cache_dir().join("indexing_errors.json")
+    }
+
+    /// Loads the config of the profile, overridden by environment variables
+    ///
+    /// Roots that overlap and sources that are set to stream large files but cannot are logged as warnings, see
+    /// `Conf::overlapping_roots` and `Conf::unstreamable_sources`.
+    pub fn load_config(&self) -> Result<Conf, confique::Error> {
+        let config = Conf::builder().env().file(self.config_path()).load()?;
+        for overlap in config.overlapping_roots() {
+            warn!("overlapping roots in profile {}: {}", self, overlap);
+        }
+        for (source, fallback) in config.unstreamable_sources() {
+            warn!(
+                "large file policy \"stream\" in profile {} is not supported by {}, its large files are {} instead",
+                self,
+                source,
+                match fallback {
+                    LargeFilePolicy::Skip => "skipped",
+                    _ => "truncated",
+                }
+            );
+        }
+        Ok(config)
+    }
+
+    /// Where the index of the profile is kept with the given config
+    pub(crate) fn index_dirs(&self, config: &Conf) -> IndexDirs {
+        let cache_dir = self.cache_dir();
+        let index = match &config.index_dir {
+            Some(index_dir) => {
+                PathBuf::from(shellexpand::tilde(&index_dir.to_string_lossy()).into_owned())
+            }
+            None => cache_dir.join("tantivy"),
+        };
+        let sibling = |suffix: &str| {
+            let mut file_name = index.file_name().unwrap_or_default().to_os_string();
+            file_name.push(suffix);
+            index.with_file_name(file_name)
+        };
+
+        IndexDirs {
+            rebuild: sibling(".rebuild"),
+            retired: sibling(".retired"),
+            index,
+            error_ledger: self.error_ledger_path(),
+            cache: cache_dir,
+        }
+    }
+}
+
+/// Where a profile keeps its index and the files that go along with it
+#[derive(Debug, Clone)]
+pub(crate) struct IndexDirs {
+    /// The cache directory of the profile, holding the lock of the indexing process
+    pub(crate) cache: PathBuf,
+    pub(crate) index: PathBuf,
+    /// Where an index with a new schema version is built while the old one keeps serving searches
+    pub(crate) rebuild: PathBuf,
+    /// Where the old index is moved to when a rebuilt one takes its place, until it is deleted
+    pub(crate) retired: PathBuf,
+    pub(crate) error_ledger: PathBuf,
+}
@@ -1,9 +1,14 @@
-use std::process::exit;
+use std::path::Path;
 
 use egui::RichText;
 use tracing::warn;
 
-use crate::{config::Conf, ui::retsyn_app::RetsynApp};
+use crate::{
+    config::Conf,
+    model::reindex_target::{ReindexTarget, SOURCES},
+    profile::Profile,
+    ui::retsyn_app::{RetsynApp, UiScreenMode},
+};
 
 impl RetsynApp {
     pub(crate) fn draw_config_screen(&mut self, ui: &mut egui::Ui) {
@@ -15,6 +20,50 @@ impl RetsynApp {
         egui::ScrollArea::vertical()
             .auto_shrink([false, false])
             .show(ui, |ui| {
+                ui.group(|ui| {
+                    ui.heading("Profile");
+                    ui.add_space(10.0);
+                    ui.label("Each profile has its own configuration, index and history. Switching opens the index of the other profile:");
+                    ui.add_space(10.0);
+
+                    let mut switch_to = None;
+                    ui.horizontal_wrapped(|ui| {
+                        for profile in &self.profiles {
+                            if ui
+                                .selectable_label(*profile == self.profile, profile.name())
+                                .clicked()
+                            {
+                                switch_to = Some(profile.clone());
+                            }
+                        }
+                    });
+
+                    ui.add_space(10.0);
+
+                    ui.horizontal(|ui| {
+                        let text_edit = egui::TextEdit::singleline(&mut self.new_profile_name)
+                            .hint_text("Profile name")
+                            .desired_width(ui.available_width() - 120.0);
+                        ui.add(text_edit);
+
+                        if ui.button("Create Profile").clicked()
+                            && !self.new_profile_name.trim().is_empty()
+                        {
+                            match Profile::new(&self.new_profile_name) {
+                                Ok(profile) => switch_to = Some(profile),
+                                Err(e) => warn!("cannot create profile: {}", e),
+                            }
+                        }
+                    });
+
+                    if let Some(profile) = switch_to {
+                        self.new_profile_name.clear();
+                        self.switch_profile(profile);
+                    }
+                });
+
+                ui.add_space(20.0);
+
                 ui.group(|ui| {
                     ui.heading("Markdown Files");
                     ui.add_space(10.0);
@@ -56,6 +105,76 @@ impl RetsynApp {
 
                 ui.add_space(20.0);
 
+                ui.group(|ui| {
+                    ui.heading("Reindex");
+                    ui.add_space(10.0);
+                    ui.label("Delete the documents of a source or directory and index their files again, e.g. after changing how a source is converted:");
+                    ui.add_space(10.0);
+
+                    let mut target = None;
+                    ui.horizontal_wrapped(|ui| {
+                        for source in SOURCES {
+                            if ui.button(source).clicked() {
+                                target = Some(ReindexTarget::Source(source.to_owned()));
+                            }
+                        }
+                    });
+
+                    ui.add_space(10.0);
+
+                    ui.horizontal(|ui| {
+                        let text_edit = egui::TextEdit::singleline(&mut self.reindex_path)
+                            .hint_text("Directory")
+                            .desired_width(ui.available_width() - 220.0);
+                        ui.add(text_edit);
+
+                        if ui.button("Browse...").clicked()
+                            && let Some(folder) = rfd::FileDialog::new().pick_folder()
+                        {
+                            self.reindex_path = folder.to_string_lossy().to_string();
+                        }
+
+                        if ui.button("Reindex Directory").clicked()
+                            && !self.reindex_path.trim().is_empty()
+                        {
+                            match ReindexTarget::subtree(Path::new(self.reindex_path.trim())) {
+                                Ok(subtree) => target = Some(subtree),
+                                Err(e) => warn!("cannot reindex {}: {}", self.reindex_path, e),
+                            }
+                        }
+                    });
+
+                    // head back so the progress of the reindex can be followed
+                    if let Some(target) = target {
+                        self.reindex(target);
+                        self.set_ui_screen_mode(UiScreenMode::Main);
+                    }
+                });
+
+                ui.add_space(20.0);
+
+                ui.group(|ui| {
+                    ui.heading("Maintenance");
+                    ui.add_space(10.0);
+                    ui.label(format!(
+                        "Merge the segments of the index according to the \"{}\" merge policy and delete the files they no longer use:",
+                        self.config.merge_policy.as_str()
+                    ));
+                    ui.add_space(10.0);
+
+                    ui.horizontal(|ui| {
+                        if ui.button("Compact Index").clicked() {
+                            self.compact_index();
+                        }
+                        match &self.last_compaction {
+                            Some(report) => ui.label(format!("Last compaction: {}", report)),
+                            None => ui.label("Not compacted since launch"),
+                        };
+                    });
+                });
+
+                ui.add_space(20.0);
+
                 ui.horizontal(|ui| {
                     if ui.button("Save Configuration").clicked() {
                         // Convert strings back to PathBuf
@@ -77,9 +196,9 @@ impl RetsynApp {
                                     println!("Configuration saved to: {}", path.display());
                                 }
 
-                                // TODO restart the index with new configuration. We need to add the ability to gracefully shutdown worker threads so that we can restart.
-                                // for now, we'll just exit
-                                exit(0)
+                                // the old index thread stops once the new one takes over its channels
+                                self.restart_index(self.config.clone());
+                                self.set_ui_screen_mode(UiScreenMode::Main);
                             }
                             Err(e) => {
                                 warn!("Error saving configuration: {}", e);
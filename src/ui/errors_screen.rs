@@ -0,0 +1,53 @@
+use egui::{Color32, RichText};
+
+use crate::ui::retsyn_app::{RetsynApp, UiScreenMode};
+
+impl RetsynApp {
+    pub(crate) fn draw_errors_screen(&mut self, ui: &mut egui::Ui) {
+        ui.vertical_centered(|ui| {
+            ui.heading(RichText::new("Indexing Errors").size(24.0));
+            ui.add_space(20.0);
+        });
+
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                if let Some(indexing_error) = &self.indexing_error {
+                    ui.group(|ui| {
+                        ui.heading("Indexing Stopped");
+                        ui.add_space(10.0);
+                        ui.colored_label(Color32::RED, indexing_error);
+                    });
+
+                    ui.add_space(20.0);
+                }
+
+                ui.group(|ui| {
+                    ui.heading("Files With Indexing Problems");
+                    ui.add_space(10.0);
+                    ui.label("Files that failed are retried on the next run once they change. Files with decoding problems were indexed, but their text may be garbled.");
+                    ui.add_space(10.0);
+
+                    if self.error_ledger.is_empty() {
+                        ui.label("No errors, every file was indexed.");
+                    }
+
+                    for failure in self.error_ledger.failures() {
+                        ui.horizontal(|ui| {
+                            ui.label(RichText::new(&failure.path).strong().monospace());
+                            ui.label("—");
+                            ui.label(format!("{} while {}", failure.source, failure.stage));
+                        });
+                        ui.colored_label(Color32::RED, &failure.message);
+                        ui.add_space(5.0);
+                    }
+                });
+
+                ui.add_space(20.0);
+
+                if ui.button("Back").clicked() {
+                    self.set_ui_screen_mode(UiScreenMode::Main);
+                }
+            });
+    }
+}
@@ -46,11 +46,23 @@ impl RetsynApp {
                         ui.label("Toggle preview pane");
                     });
 
+                    ui.horizontal(|ui| {
+                        ui.label(RichText::new("Stats button").strong());
+                        ui.label("—");
+                        ui.label("Show what is in the index: documents per source and kind, sizes, the largest documents and the most common terms");
+                    });
+
                     ui.horizontal(|ui| {
                         ui.label(RichText::new("Help button").strong());
                         ui.label("—");
                         ui.label("Show this screen");
                     });
+
+                    ui.horizontal(|ui| {
+                        ui.label(RichText::new("Problems indexing link").strong());
+                        ui.label("—");
+                        ui.label("Show the files that failed to index or whose encoding was guessed, failed files are retried once they change");
+                    });
                 });
 
                 ui.add_space(20.0);
@@ -62,6 +74,7 @@ impl RetsynApp {
                     let shortcuts = vec![
                         ("Ctrl+H or Ctrl+?", "Show/hide this help screen"),
                         ("Ctrl+,", "Show/hide configuration screen"),
+                        ("Ctrl+I", "Show/hide index stats screen"),
                         ("Ctrl+P", "Toggle preview pane"),
                         ("Ctrl+U", "Clear search text"),
                         ("Escape", "Clear search or close window"),
@@ -114,6 +127,26 @@ impl RetsynApp {
                         ("title:keyword", "Search only in the title field"),
                         ("body:keyword", "Search only in the body field"),
                         ("path:keyword", "Search only in the file path"),
+                        (
+                            "keywords:sunset camera:fujifilm",
+                            "Search photo metadata, see also date: and place:",
+                        ),
+                        (
+                            "artist:bowie kind:album",
+                            "Search music tags, see also album:, track:, genre: and date:",
+                        ),
+                        (
+                            "author:tolkien kind:note",
+                            "Search reading highlights, see also book: and location:",
+                        ),
+                        (
+                            "symbol:parse_query kind:fn lang:rust",
+                            "Search source code definitions, opening one jumps to its line in $EDITOR",
+                        ),
+                        (
+                            "kind:project readme",
+                            "Search projects as a whole, or filter any hits with project:retsyn",
+                        ),
                         ("title: IN [a b c]", "Search for title is either a, b, or c"),
                         ("\"term\"*", "Wildcard search (prefix matching)"),
                         ("term^2.0", "Boost these terms during ranking"),
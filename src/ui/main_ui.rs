@@ -4,7 +4,7 @@ use tracing::debug;
 
 use crate::{
     invocations::{invocation::Action, invocation_list::InvocationList},
-    model::fulltext_index::IndexStatus,
+    model::{fulltext_index::IndexStatus, resource_governor::PauseReason},
     ui::retsyn_app::{RetsynApp, UiScreenMode},
 };
 
@@ -21,6 +21,16 @@ impl RetsynApp {
                 return;
             }
 
+            if self.show_stats() {
+                self.draw_stats_screen(ui);
+                return;
+            }
+
+            if self.show_errors() {
+                self.draw_errors_screen(ui);
+                return;
+            }
+
             ui.vertical(|ui| {
                 ui.horizontal(|ui| {
                     let response = ui.add(
@@ -31,6 +41,7 @@ impl RetsynApp {
 
                     if response.changed() {
                         self.last_input_time = Some(Instant::now());
+                        self.report_search_activity();
                     }
 
                     response.request_focus();
@@ -51,6 +62,7 @@ impl RetsynApp {
                             fuzz_col,
                             snippet_col,
                             preview_col,
+                            stats_col,
                             help_col,
                         ]| {
                             if lenient_col
@@ -115,6 +127,16 @@ impl RetsynApp {
                                 self.show_preview = !self.show_preview;
                             };
 
+                            if stats_col
+                                .add_sized(
+                                    [stats_col.available_width(), 0.0],
+                                    Button::new("Stats").selected(false),
+                                )
+                                .clicked()
+                            {
+                                self.show_index_stats();
+                            };
+
                             if help_col
                                 .add_sized(
                                     [help_col.available_width(), 0.0],
@@ -134,7 +156,8 @@ impl RetsynApp {
                 match &self.index_status {
                     IndexStatus::Initializing
                     | IndexStatus::CollectingPaths
-                    | IndexStatus::FilteringPaths => {
+                    | IndexStatus::FilteringPaths
+                    | IndexStatus::RemovingMissingEntries => {
                         ui.label("Preparing to index...");
                     }
                     IndexStatus::UpdatingIndex {
@@ -162,8 +185,78 @@ impl RetsynApp {
                     IndexStatus::UpToDate => {
                         ui.label("Done indexing");
                     }
+                    IndexStatus::Compacting => {
+                        ui.label("Compacting the index...");
+                    }
+                    IndexStatus::IndexingElsewhere => {
+                        ui.label("Indexing in another process");
+                    }
                 };
 
+                // draw pause controls, searching keeps working while indexing is paused
+                if !matches!(self.index_status, IndexStatus::IndexingElsewhere) {
+                    ui.horizontal(|ui| {
+                        let label = if self.indexing_paused {
+                            "Resume Indexing"
+                        } else {
+                            "Pause Indexing"
+                        };
+                        if ui.small_button(label).clicked() {
+                            self.toggle_indexing_pause();
+                        }
+                        match self.indexing_held {
+                            Some(PauseReason::User) => {
+                                ui.colored_label(Color32::DARK_RED, "Indexing paused");
+                            }
+                            Some(PauseReason::SearchActive) => {
+                                ui.label("Indexing waits until you stop searching");
+                            }
+                            None => {}
+                        }
+                    });
+                }
+
+                // draw the profile switcher, once there is more than one profile to switch to
+                if self.profiles.len() > 1 {
+                    let mut switch_to = None;
+                    ui.horizontal(|ui| {
+                        ui.label("Profile:");
+                        egui::ComboBox::from_id_salt("profile")
+                            .selected_text(self.profile.name())
+                            .show_ui(ui, |ui| {
+                                for profile in &self.profiles {
+                                    if ui
+                                        .selectable_label(*profile == self.profile, profile.name())
+                                        .clicked()
+                                    {
+                                        switch_to = Some(profile.clone());
+                                    }
+                                }
+                            });
+                    });
+                    if let Some(profile) = switch_to {
+                        self.switch_profile(profile);
+                    }
+                }
+
+                // draw indexing errors, the details are on their own screen
+                if let Some(indexing_error) = &self.indexing_error {
+                    ui.colored_label(
+                        Color32::RED,
+                        format!("Indexing stopped: {}", indexing_error),
+                    );
+                }
+                if !self.error_ledger.is_empty()
+                    && ui
+                        .link(format!(
+                            "{} files had problems indexing",
+                            self.error_ledger.len()
+                        ))
+                        .clicked()
+                {
+                    self.set_ui_screen_mode(UiScreenMode::Errors);
+                }
+
                 // draw query errors
                 ui.add_space(10.0);
                 match &self.matched_items {
@@ -216,7 +309,7 @@ impl RetsynApp {
                                 .auto_shrink([false, false])
                                 .show(&mut columns[1], |ui| match self.selected_item() {
                                     Some(selected_item) => {
-                                        selected_item.draw_preview_area(ui);
+                                        selected_item.draw_preview_area(ui, &self.thumbnails);
                                         ui.ctx().output(|o| {
                                             for command in &o.commands {
                                                 match command {
@@ -1,7 +1,10 @@
 pub mod config_screen;
+pub mod errors_screen;
 pub mod help_screen;
 pub mod main_ui;
 pub mod navigation_and_key_events;
 pub mod recent_queries;
 pub mod retsyn_app;
 pub mod search_results;
+pub mod stats_screen;
+pub mod thumbnail_cache;
@@ -27,6 +27,16 @@ impl RetsynApp {
             return;
         }
 
+        // Toggle stats screen with Ctrl+I
+        if ctx.input(|i| i.key_pressed(egui::Key::I) && i.modifiers.ctrl) {
+            if self.show_stats() {
+                self.set_ui_screen_mode(UiScreenMode::Main);
+            } else {
+                self.show_index_stats();
+            }
+            return;
+        }
+
         // Toggle preview pane with Ctrl+P
         if ctx.input(|i| i.key_pressed(egui::Key::P) && i.modifiers.ctrl) {
             self.show_preview = !self.show_preview;
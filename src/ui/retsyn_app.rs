@@ -4,7 +4,7 @@ use std::{
         LazyLock,
         mpsc::{Receiver, Sender, channel},
     },
-    thread::spawn,
+    thread::{JoinHandle, spawn},
     time::{Duration, Instant},
     vec,
 };
@@ -13,7 +13,8 @@ use confique::Config;
 use directories::ProjectDirs;
 use eframe::CreationContext;
 use egui::Context;
-use tracing::{error, info, warn};
+use tantivy::Opstamp;
+use tracing::{debug, error, info, warn};
 
 use crate::{
     config::Conf,
@@ -21,9 +22,21 @@ use crate::{
         invocation::{Action, Invocation},
         invocation_list::InvocationList,
     },
-    messages::{index_request::IndexRequest, index_results::IndexResults},
+    messages::{
+        index_request::{IndexRequest, SearchRequest},
+        index_results::IndexResults,
+    },
+    model::compaction::CompactionReport,
+    model::error_ledger::ErrorLedger,
     model::fulltext_index::{FulltextIndex, IndexStatus, SearchResultsAndErrors},
+    model::index_stats::IndexStats,
+    model::indexing_error::IndexingError,
+    model::instance_lock,
+    model::reindex_target::ReindexTarget,
+    model::resource_governor::PauseReason,
     model::search_result::SearchResult,
+    profile::{IndexDirs, Profile},
+    ui::thumbnail_cache::ThumbnailCache,
 };
 
 const INTERFRAME_MILLIS: u64 = 16;
@@ -40,6 +53,8 @@ pub(crate) enum UiScreenMode {
     Main,
     Config,
     Help,
+    Errors,
+    Stats,
 }
 
 pub struct RetsynApp {
@@ -48,7 +63,21 @@ pub struct RetsynApp {
     last_search_text: String,
     last_request_id: usize,
     last_response_id: usize,
+    /// The index commit the shown results were searched at
+    results_opstamp: Opstamp,
     pub(crate) index_status: IndexStatus,
+    /// The error that stopped indexing, if any
+    pub(crate) indexing_error: Option<String>,
+    /// Whether indexing was paused with the button, it stays paused until resumed
+    pub(crate) indexing_paused: bool,
+    /// Why the index thread is holding indexing, if it is
+    pub(crate) indexing_held: Option<PauseReason>,
+    /// The statistics shown on the stats screen, `None` until they arrive
+    pub(crate) index_stats: Option<Result<IndexStats, String>>,
+    /// What the last compaction of the index did, if it ran since launch
+    pub(crate) last_compaction: Option<CompactionReport>,
+    /// The files that failed to index, reloaded whenever indexing is done
+    pub(crate) error_ledger: ErrorLedger,
     pub(crate) matched_items: SearchResultsAndErrors,
     pub(crate) selected_index: Option<usize>,
     pub(crate) last_input_time: Option<Instant>,
@@ -61,14 +90,27 @@ pub struct RetsynApp {
     ui_screen_mode: UiScreenMode,
     pub(crate) show_snippets: bool,
     pub(crate) show_preview: bool,
+    /// The decoded images the preview shows, the most recently shown ones are kept
+    pub(crate) thumbnails: ThumbnailCache,
+    /// The profile whose index is searched and whose config is edited
+    pub(crate) profile: Profile,
+    /// The profiles to switch to, read when this one was opened
+    pub(crate) profiles: Vec<Profile>,
+    /// The name entered on the config screen to create a profile with
+    pub(crate) new_profile_name: String,
     pub(crate) config: Conf,
     pub(crate) config_markdown_files: Vec<String>,
+    /// The directory entered on the config screen to reindex
+    pub(crate) reindex_path: String,
     pub(crate) limit_results: usize,
     pub(crate) lenient: bool,
     pub(crate) query_conjunction: bool,
     pub(crate) fuzziness: u8,
     request_sender: Sender<IndexRequest>,
+    search_sender: Sender<SearchRequest>,
     results_receiver: Receiver<IndexResults>,
+    /// The thread that opened the index, it runs until the channels above are replaced or dropped
+    index_thread: Option<JoinHandle<()>>,
     last_repaint_request: Instant,
 }
 
@@ -89,13 +131,25 @@ impl RetsynApp {
         matches!(self.ui_screen_mode, UiScreenMode::Help)
     }
 
+    pub(crate) fn show_errors(&self) -> bool {
+        matches!(self.ui_screen_mode, UiScreenMode::Errors)
+    }
+
+    pub(crate) fn show_stats(&self) -> bool {
+        matches!(self.ui_screen_mode, UiScreenMode::Stats)
+    }
+
     pub fn new(cc: &CreationContext) -> Self {
         let egui_ctx = cc.egui_ctx.clone();
 
-        let config_file = PROJECT_DIRS.config_dir().to_path_buf().join("retsyn.toml");
+        // needed to draw thumbnails of images in the preview
+        egui_extras::install_image_loaders(&egui_ctx);
+
+        let profile = Profile::active();
+        let config_file = profile.config_path();
         let config_exists = Conf::config_exists();
 
-        let config = match Conf::builder().env().file(&config_file).load() {
+        let config = match profile.load_config() {
             Ok(config) => config,
             Err(_) => {
                 // If config doesn't exist, create a default one
@@ -118,30 +172,15 @@ impl RetsynApp {
             }
         };
 
-        // Convert PathBuf to String for editing
-        let config_markdown_files: Vec<String> = config
-            .markdown_files
-            .iter()
-            .map(|p| p.to_string_lossy().to_string())
-            .collect();
+        let config_markdown_files = Self::editable_markdown_files(&config);
 
         if !config_exists {
             // TODO unify errors and return an error instead
             exit(1);
         }
 
-        let (request_sender, request_receiver) = channel();
-        let (results_sender, results_receiver) = channel();
-
-        let fulltext_index_config = config.clone();
-        spawn(move || {
-            let mut index =
-                FulltextIndex::new(fulltext_index_config, request_receiver, results_sender)
-                    .unwrap();
-            let entry_receiver = index.start_collectors();
-            index.update(entry_receiver).unwrap();
-            index.update_search_results();
-        });
+        let (request_sender, search_sender, results_receiver, index_thread) =
+            Self::start_index(config.clone(), profile.index_dirs(&config), None);
 
         // TODO pull this from config
         let dark_mode = false;
@@ -165,30 +204,206 @@ impl RetsynApp {
             last_search_text: String::new(),
             last_request_id: 0,
             last_response_id: 0,
+            results_opstamp: 0,
             index_status: IndexStatus::Initializing,
+            indexing_error: None,
+            indexing_paused: false,
+            indexing_held: None,
+            index_stats: None,
+            last_compaction: None,
+            error_ledger: ErrorLedger::load(&profile.error_ledger_path()),
             matched_items: Ok((vec![], vec![])),
             selected_index: None,
             last_input_time: None,
             debounce_duration: DEBOUNCE_DURATION,
-            recent_queries: InvocationList::load_from_cache().unwrap_or_default(),
+            recent_queries: InvocationList::load_from_cache(&profile.cache_dir())
+                .unwrap_or_default(),
             invocations: Default::default(),
             scroll_to_selected: false,
             dark_mode,
             ui_screen_mode,
             show_snippets: true,
             show_preview: true,
+            thumbnails: ThumbnailCache::default(),
+            profile,
+            profiles: Profile::list(),
+            new_profile_name: String::new(),
             config,
             config_markdown_files,
+            reindex_path: String::new(),
             limit_results: 50,
             lenient: true,
             query_conjunction: true,
             fuzziness: 0,
             request_sender,
+            search_sender,
             results_receiver,
+            index_thread: Some(index_thread),
             last_repaint_request: Instant::now(),
         }
     }
 
+    /// Converts the markdown directories to strings for editing
+    fn editable_markdown_files(config: &Conf) -> Vec<String> {
+        config
+            .markdown_files
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect()
+    }
+
+    /// Opens the index on a thread of its own and returns the channels to talk to it
+    ///
+    /// The thread stops once the returned senders are dropped, which is how a restarted index replaces the old one. The
+    /// thread of the index it replaces is waited for first, as it holds the lock on the index until it is done.
+    fn start_index(
+        config: Conf,
+        dirs: IndexDirs,
+        previous_thread: Option<JoinHandle<()>>,
+    ) -> (
+        Sender<IndexRequest>,
+        Sender<SearchRequest>,
+        Receiver<IndexResults>,
+        JoinHandle<()>,
+    ) {
+        let (request_sender, request_receiver) = channel();
+        let (search_sender, search_receiver) = channel();
+        let (results_sender, results_receiver) = channel();
+
+        let index_thread = spawn(move || {
+            if let Some(previous_thread) = previous_thread {
+                info!("waiting for the previous index to close...");
+                if previous_thread.join().is_err() {
+                    warn!("the previous index thread panicked");
+                }
+            }
+
+            let error_sender = results_sender.clone();
+            let volume_labels = config.volume_labels.clone();
+            let mut index =
+                match FulltextIndex::new(config, dirs.clone(), request_receiver, results_sender) {
+                    Ok(index) => index,
+                    // a second instance searches what the first one indexes
+                    Err(e) if instance_lock::held_by_another_process(&e) => {
+                        if let Err(e) = FulltextIndex::serve_read_only(
+                            &dirs,
+                            volume_labels,
+                            search_receiver,
+                            error_sender.clone(),
+                        ) {
+                            error!("could not open the fulltext index read-only: {}", e);
+                            let _ = error_sender.send(IndexResults::Error(IndexingError::Index(e)));
+                        }
+                        return;
+                    }
+                    Err(e) => {
+                        error!("could not open the fulltext index: {}", e);
+                        let _ = error_sender.send(IndexResults::Error(IndexingError::Index(e)));
+                        return;
+                    }
+                };
+            // searches run on a thread of their own, so they are answered while this one indexes
+            index.spawn_search_service(search_receiver);
+            index.lower_priority();
+
+            // changes made while the collectors walk the roots are indexed once the run is done
+            let file_watcher = index.start_watching();
+            let pipeline = index.start_collectors();
+            if let Err(e) = index.update(pipeline) {
+                // whatever was committed so far can still be searched
+                return index.report_error(e);
+            }
+
+            // an index rebuilt for a new schema version replaces the old one once it is complete
+            if index.is_rebuilding() {
+                index = match index.finish_rebuild() {
+                    Ok(index) => index,
+                    Err(e) => {
+                        error!("could not swap in the rebuilt index: {}", e);
+                        let _ = error_sender.send(IndexResults::Error(e));
+                        return;
+                    }
+                };
+            }
+            index.watch_for_changes(file_watcher);
+        });
+
+        (
+            request_sender,
+            search_sender,
+            results_receiver,
+            index_thread,
+        )
+    }
+
+    /// Switches to another profile without restarting, a profile without a config starts out with the default one
+    pub(crate) fn switch_profile(&mut self, profile: Profile) {
+        if profile == self.profile {
+            return;
+        }
+
+        let config_exists = profile.config_path().exists();
+        if !config_exists && let Err(e) = Conf::write_default_config_to(&profile.config_path()) {
+            warn!("could not create a config for profile {}: {}", profile, e);
+            return;
+        }
+        let config = match profile.load_config() {
+            Ok(config) => config,
+            Err(e) => {
+                warn!("could not load the config of profile {}: {}", profile, e);
+                return;
+            }
+        };
+
+        // the invocations so far belong to the profile they were made in
+        self.save_invocations();
+        self.invocations = Default::default();
+
+        profile.clone().activate();
+        self.recent_queries =
+            InvocationList::load_from_cache(&profile.cache_dir()).unwrap_or_default();
+        self.profile = profile;
+        self.profiles = Profile::list();
+        self.restart_index(config);
+
+        // a new profile needs its directories configured
+        if !config_exists {
+            self.set_ui_screen_mode(UiScreenMode::Config);
+        }
+    }
+
+    /// Replaces the index thread with one that uses the given config, the old thread stops on its own
+    pub(crate) fn restart_index(&mut self, config: Conf) {
+        info!("restarting the index of profile {}", self.profile);
+        let (request_sender, search_sender, results_receiver, index_thread) = Self::start_index(
+            config.clone(),
+            self.profile.index_dirs(&config),
+            self.index_thread.take(),
+        );
+        self.request_sender = request_sender;
+        self.search_sender = search_sender;
+        self.results_receiver = results_receiver;
+        self.index_thread = Some(index_thread);
+
+        // nothing the old thread had to say is of interest anymore
+        self.index_status = IndexStatus::Initializing;
+        self.indexing_error = None;
+        self.indexing_paused = false;
+        self.indexing_held = None;
+        self.index_stats = None;
+        self.last_compaction = None;
+        self.error_ledger = ErrorLedger::load(&self.profile.error_ledger_path());
+        self.last_response_id = self.last_request_id;
+        self.results_opstamp = 0;
+        self.config_markdown_files = Self::editable_markdown_files(&config);
+        self.config = config;
+
+        // search the restarted index for what is in the search box
+        self.matched_items = Ok((vec![], vec![]));
+        self.selected_index = None;
+        self.update_search();
+    }
+
     /// Returns the currently selected item as a reference
     ///
     /// This is useful to render the preview if it is shown.
@@ -211,7 +426,7 @@ impl RetsynApp {
         fuzziness: u8,
     ) {
         self.last_request_id = self.last_request_id.saturating_add(1);
-        match self.request_sender.send(IndexRequest {
+        match self.search_sender.send(SearchRequest {
             request_id: self.last_request_id,
             query: query.to_string(),
             limit,
@@ -230,6 +445,58 @@ impl RetsynApp {
         }
     }
 
+    /// Asks the index to rebuild a source or directory, it starts once any indexing in progress is done
+    pub(crate) fn reindex(&mut self, target: ReindexTarget) {
+        info!("requesting reindex of {}", target);
+        if let Err(e) = self.request_sender.send(IndexRequest::Reindex(target)) {
+            warn!("could not send reindex request: {}", e);
+        }
+    }
+
+    /// Asks the index to merge its segments and delete unused files, it starts once any indexing in progress is done
+    pub(crate) fn compact_index(&mut self) {
+        info!("requesting compaction of the index");
+        if let Err(e) = self.request_sender.send(IndexRequest::Compact) {
+            warn!("could not send compaction request: {}", e);
+        }
+    }
+
+    /// Shows the stats screen and asks the index for fresh statistics, the previous ones are shown until they arrive
+    pub(crate) fn show_index_stats(&mut self) {
+        self.set_ui_screen_mode(UiScreenMode::Stats);
+        self.refresh_index_stats();
+    }
+
+    pub(crate) fn refresh_index_stats(&mut self) {
+        if let Err(e) = self.request_sender.send(IndexRequest::Stats) {
+            warn!("could not send stats request: {}", e);
+            self.index_stats = Some(Err("the index is not available".to_owned()));
+        }
+    }
+
+    /// Pauses indexing or resumes it, a paused index keeps serving searches
+    pub(crate) fn toggle_indexing_pause(&mut self) {
+        self.indexing_paused = !self.indexing_paused;
+        let request = if self.indexing_paused {
+            IndexRequest::Pause
+        } else {
+            IndexRequest::Resume
+        };
+        if let Err(e) = self.request_sender.send(request) {
+            warn!("could not send pause request: {}", e);
+        }
+    }
+
+    /// Tells the index the user is typing a search, so indexing can wait for them to be idle
+    pub(crate) fn report_search_activity(&mut self) {
+        if !self.config.index_only_when_idle {
+            return;
+        }
+        if let Err(e) = self.request_sender.send(IndexRequest::SearchActivity) {
+            debug!("could not send search activity: {}", e);
+        }
+    }
+
     pub(crate) fn clear_search(&mut self) {
         self.search_text.clear();
         self.matched_items = Ok((vec![], vec![]));
@@ -238,26 +505,63 @@ impl RetsynApp {
 
     fn retrieve_results(&mut self) {
         let mut results_received: usize = 0;
+        let mut committed = false;
 
         // we're looping here to soak up all pending results
         // if this becomes a performance issue we can bail early
         for index_results in self.results_receiver.try_iter() {
             results_received += 1;
             match index_results {
-                IndexResults::Error(_) => todo!(),
-                IndexResults::Status(index_status) => self.index_status = index_status,
+                IndexResults::Error(indexing_error) => {
+                    self.indexing_error = Some(indexing_error.to_string())
+                }
+                IndexResults::Status(index_status) => {
+                    // the ledger is saved with every commit, pick up the latest once indexing is done
+                    if matches!(index_status, IndexStatus::UpToDate) {
+                        self.error_ledger = ErrorLedger::load(&self.profile.error_ledger_path());
+                    }
+                    self.index_status = index_status
+                }
+                IndexResults::Compacted(report) => self.last_compaction = Some(report),
+                IndexResults::Stats(stats) => {
+                    self.index_stats = Some(stats.map_err(|e| e.to_string()))
+                }
+                IndexResults::Paused(reason) => self.indexing_held = reason,
+                IndexResults::Committed(opstamp) => {
+                    committed |= opstamp != self.results_opstamp;
+                }
                 IndexResults::SearchResults {
                     request_id,
-                    // TODO check the opstamp to see if there has been an index commit since our last search
                     opstamp,
                     results,
                 } => {
+                    // keep the selected item selected when it moves, results shift around as the index changes
+                    let selected_key = self.selected_key();
                     self.last_response_id = request_id;
-                    self.matched_items = results
+                    self.results_opstamp = opstamp;
+                    self.matched_items = results;
+                    if let Some(index) = selected_key.and_then(|key| self.position_of(&key))
+                        && self.selected_index != Some(index)
+                    {
+                        self.selected_index = Some(index);
+                        self.scroll_to_selected = true;
+                    }
                 }
             }
         }
 
+        // the shown results may be missing documents that were just indexed, search again
+        if committed && !self.last_search_text.is_empty() {
+            info!("index committed, refreshing results");
+            self.search(
+                &self.last_search_text.clone(),
+                self.limit_results,
+                self.lenient,
+                self.query_conjunction,
+                self.fuzziness,
+            );
+        }
+
         // TODO determine if this is the selection preservation behavior that we want
         self.selected_index = Some(
             self.matched_items
@@ -268,7 +572,10 @@ impl RetsynApp {
         );
 
         if results_received > 0
-            || !matches!(self.index_status, IndexStatus::UpToDate)
+            || !matches!(
+                self.index_status,
+                IndexStatus::UpToDate | IndexStatus::IndexingElsewhere
+            )
             || self.last_request_id > self.last_response_id
         {
             // request screen repaint on changes
@@ -277,6 +584,18 @@ impl RetsynApp {
         }
     }
 
+    /// The key of the selected result, if any
+    fn selected_key(&self) -> Option<String> {
+        let (matched_items, _errors) = self.matched_items.as_ref().ok()?;
+        Some(matched_items.get(self.selected_index?)?.key())
+    }
+
+    /// The index of the result with the given key, if it is among the results, see `SearchResult::key`
+    fn position_of(&self, key: &str) -> Option<usize> {
+        let (matched_items, _errors) = self.matched_items.as_ref().ok()?;
+        matched_items.iter().position(|item| item.key() == key)
+    }
+
     pub(crate) fn update_search(&mut self) {
         if self.search_text.is_empty() {
             self.matched_items = Ok((vec![], vec![]));
@@ -301,7 +620,7 @@ impl RetsynApp {
             if index < matched_items.len() {
                 let item = &matched_items[index];
                 if reveal {
-                    item.reveal();
+                    item.reveal(&self.config);
                     // TODO add action to invocations
                     self.invocations.add_invocation_by_item(
                         Action::Reveal,
@@ -320,7 +639,7 @@ impl RetsynApp {
     /// Save invocations to CSV file
     fn save_invocations(&self) {
         let now = time::OffsetDateTime::now_utc();
-        let cache_file = Invocation::cache_file(now);
+        let cache_file = Invocation::cache_file(&self.profile.cache_dir(), now);
 
         match Invocation::append_invocations_to_csv(&self.invocations, &cache_file) {
             Ok(()) => {
@@ -1,4 +1,4 @@
-use egui::RichText;
+use egui::{Color32, RichText};
 use tracing::info;
 
 use crate::ui::retsyn_app::RetsynApp;
@@ -19,6 +19,12 @@ impl RetsynApp {
                             ui.selectable_label(is_selected, RichText::new(item.title()).heading());
                         ui.label(item.path());
                         ui.label(item.indexed_at());
+                        if item.truncated() {
+                            ui.colored_label(Color32::DARK_RED, "(partially indexed)");
+                        }
+                        if item.offline() {
+                            ui.colored_label(Color32::DARK_RED, "(offline)");
+                        }
 
                         if self.scroll_to_selected && is_selected {
                             response.scroll_to_me(Some(egui::Align::Center));
@@ -0,0 +1,123 @@
+use egui::{Color32, RichText};
+
+use crate::{
+    model::index_stats::{format_date_time, format_megabytes},
+    ui::retsyn_app::{RetsynApp, UiScreenMode},
+};
+
+impl RetsynApp {
+    pub(crate) fn draw_stats_screen(&mut self, ui: &mut egui::Ui) {
+        ui.vertical_centered(|ui| {
+            ui.heading(RichText::new("Index Stats").size(24.0));
+            ui.add_space(20.0);
+        });
+
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                match &self.index_stats {
+                    None => {
+                        ui.label("Gathering statistics...");
+                    }
+                    Some(Err(e)) => {
+                        ui.colored_label(
+                            Color32::RED,
+                            format!("Could not read the index stats: {}", e),
+                        );
+                    }
+                    Some(Ok(stats)) => {
+                        ui.group(|ui| {
+                            ui.heading("Overview");
+                            ui.add_space(10.0);
+                            ui.label(format!(
+                                "{} documents in {} segments, {} on disk",
+                                stats.documents,
+                                stats.segments,
+                                format_megabytes(stats.index_bytes)
+                            ));
+                        });
+
+                        ui.add_space(20.0);
+
+                        ui.group(|ui| {
+                            ui.heading("Sources");
+                            ui.add_space(10.0);
+                            egui::Grid::new("stats_sources")
+                                .striped(true)
+                                .show(ui, |ui| {
+                                    ui.label(RichText::new("Source").strong());
+                                    ui.label(RichText::new("Documents").strong());
+                                    ui.label(RichText::new("File size").strong());
+                                    ui.label(RichText::new("Last indexed").strong());
+                                    ui.end_row();
+                                    for source in &stats.sources {
+                                        ui.label(RichText::new(&source.source).monospace());
+                                        ui.label(source.documents.to_string());
+                                        ui.label(format_megabytes(source.file_bytes));
+                                        ui.label(
+                                            source
+                                                .last_indexed
+                                                .map(format_date_time)
+                                                .unwrap_or_else(|| "never".to_owned()),
+                                        );
+                                        ui.end_row();
+                                    }
+                                });
+                        });
+
+                        ui.add_space(20.0);
+
+                        ui.group(|ui| {
+                            ui.heading("Kinds");
+                            ui.add_space(10.0);
+                            if stats.kinds.is_empty() {
+                                ui.label("No documents have a kind.");
+                            }
+                            ui.horizontal_wrapped(|ui| {
+                                for (kind, documents) in &stats.kinds {
+                                    ui.label(format!("{} ({})", kind, documents));
+                                }
+                            });
+                        });
+
+                        ui.add_space(20.0);
+
+                        ui.group(|ui| {
+                            ui.heading("Largest Documents");
+                            ui.add_space(10.0);
+                            for document in &stats.largest_documents {
+                                ui.horizontal(|ui| {
+                                    ui.label(format_megabytes(document.file_bytes));
+                                    ui.label("—");
+                                    ui.label(RichText::new(&document.path).strong().monospace());
+                                });
+                            }
+                        });
+
+                        ui.add_space(20.0);
+
+                        ui.group(|ui| {
+                            ui.heading("Top Terms");
+                            ui.add_space(10.0);
+                            ui.horizontal_wrapped(|ui| {
+                                for (term, documents) in &stats.top_terms {
+                                    ui.label(format!("{} ({})", term, documents));
+                                }
+                            });
+                        });
+                    }
+                }
+
+                ui.add_space(20.0);
+
+                ui.horizontal(|ui| {
+                    if ui.button("Refresh").clicked() {
+                        self.refresh_index_stats();
+                    }
+                    if ui.button("Back").clicked() {
+                        self.set_ui_screen_mode(UiScreenMode::Main);
+                    }
+                });
+            });
+    }
+}
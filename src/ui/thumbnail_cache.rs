@@ -0,0 +1,114 @@
+use std::{
+    collections::VecDeque,
+    path::Path,
+    sync::{Arc, Mutex},
+    thread::spawn,
+};
+
+use egui::{ColorImage, Image, TextureHandle, TextureOptions};
+use image::ImageReader;
+use tracing::{debug, warn};
+
+/// The longest side of a preview in pixels, larger images are scaled down to fit
+const THUMBNAIL_SIZE: u32 = 1024;
+
+/// How many previews are kept, the least recently shown one is dropped and decoded again if it is shown again
+const CACHED_THUMBNAILS: usize = 16;
+
+enum Thumbnail {
+    Decoding,
+    Ready(TextureHandle),
+    Failed(String),
+}
+
+/// Previews of images, decoded in the background and scaled down so a large photo does not stall the UI or fill the
+/// memory with its full resolution
+#[derive(Default)]
+pub(crate) struct ThumbnailCache {
+    /// Keyed by path, the most recently shown last
+    thumbnails: Arc<Mutex<VecDeque<(String, Thumbnail)>>>,
+}
+
+impl ThumbnailCache {
+    /// Shows the preview of an image, a spinner is shown until it is decoded
+    pub(crate) fn show(&self, ui: &mut egui::Ui, path: &str) {
+        let mut thumbnails = self
+            .thumbnails
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        match thumbnails
+            .iter()
+            .position(|(cached_path, _)| cached_path == path)
+        {
+            Some(index) => {
+                let thumbnail = thumbnails.remove(index).expect("the index was just found");
+                thumbnails.push_back(thumbnail);
+            }
+            None => {
+                thumbnails.push_back((path.to_owned(), Thumbnail::Decoding));
+                if thumbnails.len() > CACHED_THUMBNAILS {
+                    thumbnails.pop_front();
+                }
+                self.decode(ui.ctx().clone(), path.to_owned());
+            }
+        }
+
+        match thumbnails.back().map(|(_, thumbnail)| thumbnail) {
+            Some(Thumbnail::Ready(texture)) => {
+                ui.add(
+                    Image::new(texture)
+                        .max_width(ui.available_width())
+                        .fit_to_original_size(1.0),
+                );
+            }
+            Some(Thumbnail::Failed(error)) => {
+                ui.label(format!("No preview available: {}", error));
+            }
+            Some(Thumbnail::Decoding) | None => {
+                ui.spinner();
+            }
+        }
+    }
+
+    fn decode(&self, ctx: egui::Context, path: String) {
+        let thumbnails = self.thumbnails.clone();
+        spawn(move || {
+            debug!("decoding preview of {}", path);
+            let thumbnail = match Self::read_thumbnail(Path::new(&path)) {
+                Ok(image) => {
+                    Thumbnail::Ready(ctx.load_texture(&path, image, TextureOptions::LINEAR))
+                }
+                Err(e) => {
+                    warn!("could not decode preview of {}: {}", path, e);
+                    Thumbnail::Failed(e.to_string())
+                }
+            };
+
+            let mut thumbnails = thumbnails
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            // the preview may have been dropped from the cache while it was decoded
+            if let Some((_, cached)) = thumbnails
+                .iter_mut()
+                .find(|(cached_path, _)| *cached_path == path)
+            {
+                *cached = thumbnail;
+            }
+            drop(thumbnails);
+            ctx.request_repaint();
+        });
+    }
+
+    /// Decodes an image and scales it down to `THUMBNAIL_SIZE`, only the scaled down copy is kept
+    fn read_thumbnail(path: &Path) -> image::ImageResult<ColorImage> {
+        let mut image = ImageReader::open(path)?.with_guessed_format()?.decode()?;
+        if image.width().max(image.height()) > THUMBNAIL_SIZE {
+            image = image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+        }
+        let image = image.to_rgba8();
+        Ok(ColorImage::from_rgba_unmultiplied(
+            [image.width() as usize, image.height() as usize],
+            image.as_raw(),
+        ))
+    }
+}